@@ -4,10 +4,10 @@ use anyhow::Result;
 use clap::Parser;
 use opts::*;
 use potr::*;
-use std::sync::atomic;
+use std::{process::ExitCode, sync::atomic};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let opts = Opts::parse();
     let log_filter = if opts.verbose {
         "potr=debug"
@@ -17,8 +17,260 @@ async fn main() -> Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_filter)).init();
     tracing::debug!("Opts: {:?}", opts);
 
-    let config = opts.to_potr_config();
-    let translator_config = opts.to_translator_config()?;
+    match run(opts).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let e = PotrError::from(e);
+            eprintln!("Error: {}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+/// Exit code of each kind of error, so scripts can tell them apart. 1 is used by the other
+/// errors, and 2 by clap for invalid arguments.
+fn exit_code(error: &PotrError) -> u8 {
+    match error {
+        PotrError::Translate(e) => match e {
+            TranslateError::Auth(_) => 3,
+            TranslateError::QuotaExceeded(_) => 4,
+            TranslateError::RateLimited { .. } => 5,
+            TranslateError::UnsupportedLanguage { .. } => 6,
+            TranslateError::ContentFiltered(_) => 7,
+            TranslateError::InvalidResponse(_) => 8,
+            TranslateError::InvalidConfig(_) | TranslateError::Unsupported(_) => 9,
+            TranslateError::Request(_) => 10,
+        },
+        PotrError::Config(_) => 9,
+        PotrError::Io(_) => 11,
+        PotrError::Parse(_) => 12,
+        PotrError::BelowThreshold { .. } => 13,
+        PotrError::Other(_) => 1,
+    }
+}
+
+async fn run(opts: Opts) -> Result<()> {
+    let profile_config = opts.load_profile_config()?;
+    match opts.into_command() {
+        Command::Translate(translate_opts) => {
+            let profile_config = translate_opts
+                .to_profile_config()
+                .or(profile_config)
+                .or(TranslateOpts::default_profile_config());
+            let mut config = profile_config.to_potr_config()?;
+            if translate_opts.dry_run {
+                let translator_config = profile_config.to_translator_config(String::new());
+                return run_dry_run(config, translator_config);
+            }
+
+            let api_key = translate_opts
+                .engine
+                .get_api_key(profile_config.engine.unwrap())?;
+            let translator_config = profile_config.to_translator_config(api_key);
+
+            // Rules and back-translation using other engines can only get their API keys from
+            // environment variables.
+            for rule in config.rules.iter_mut() {
+                if let Some(engine) = rule.engine.filter(|e| *e != translator_config.engine) {
+                    rule.api_key = Some(get_api_key_from_env(engine)?);
+                }
+            }
+            if let Some(engine) = config
+                .quality
+                .engine
+                .filter(|e| *e != translator_config.engine)
+            {
+                config.quality.api_key = Some(get_api_key_from_env(engine)?);
+            }
+
+            run_potr(config, translator_config).await
+        }
+        Command::Clear(process_opts) => {
+            let profile_config = process_opts
+                .to_profile_config(TranslatorEngine::Clear)
+                .or(profile_config);
+            let config = process_potr_config(profile_config.to_potr_config()?);
+            let translator_config = profile_config.to_translator_config(String::new());
+            if process_opts.dry_run {
+                return run_dry_run(config, translator_config);
+            }
+            run_potr(config, translator_config).await
+        }
+        Command::Clone(process_opts) => {
+            let profile_config = process_opts
+                .to_profile_config(TranslatorEngine::Clone)
+                .or(profile_config);
+            let config = process_potr_config(profile_config.to_potr_config()?);
+            let translator_config = profile_config.to_translator_config(String::new());
+            if process_opts.dry_run {
+                return run_dry_run(config, translator_config);
+            }
+            run_potr(config, translator_config).await
+        }
+        Command::Merge(merge_opts) => {
+            PoMerger::new(merge_opts.to_merge_config()).run()?;
+            Ok(())
+        }
+        Command::Compile(compile_opts) => {
+            MoCompiler::new(compile_opts.to_compile_config()).run()?;
+            Ok(())
+        }
+        Command::Stats(stats_opts) => {
+            let stats = PoStatsCollector::new(stats_opts.to_stats_config()).run()?;
+            print!("{}", stats.format(stats_opts.format)?);
+            match stats_opts.threshold {
+                Some(threshold) => Ok(stats.check_threshold(threshold)?),
+                None => Ok(()),
+            }
+        }
+        Command::Check(check_opts) => {
+            let report = PoChecker::new(check_opts.to_check_config()).run()?;
+            print!("{}", report.format(check_opts.format)?);
+            if !report.issues.is_empty() {
+                anyhow::bail!("{} issues found.", report.issues.len());
+            }
+            Ok(())
+        }
+        Command::Review(review_opts) => {
+            let profile_config = review_opts
+                .to_profile_config()
+                .or(profile_config)
+                .or(TranslateOpts::default_profile_config());
+            let mut config = profile_config.to_review_config()?;
+            config.review_file_path = review_opts.review_file_path.clone();
+
+            let api_key = review_opts
+                .engine
+                .get_api_key(profile_config.engine.unwrap())?;
+            let translator_config = profile_config.to_translator_config(api_key);
+            PoReviewer::new(config, translator_config).run().await?;
+            Ok(())
+        }
+        Command::Tui(tui_opts) => {
+            let profile_config = tui_opts
+                .to_profile_config()
+                .or(profile_config)
+                .or(TranslateOpts::default_profile_config());
+            let config = profile_config.to_tui_config()?;
+
+            let api_key = tui_opts
+                .engine
+                .get_api_key(profile_config.engine.unwrap())?;
+            let translator_config = profile_config.to_translator_config(api_key);
+            let mut translators = vec![potr::create(translator_config.clone())?];
+            for engine in &tui_opts.alt_engines {
+                let mut alt_config = translator_config.clone();
+                alt_config.engine = *engine;
+                alt_config.api_key = get_api_key_from_env(*engine)?;
+                alt_config.model = None;
+                translators.push(potr::create(alt_config)?);
+            }
+
+            let summary = TuiReviewer::new(config, translators).run().await?;
+            println!(
+                "Reviewed {} messages, changed {}, remaining {}.",
+                summary.reviewed_count, summary.changed_count, summary.remaining_count
+            );
+            Ok(())
+        }
+        Command::Export(export_opts) => {
+            let profile_config = export_opts.to_profile_config().or(profile_config);
+            let potr_config = profile_config.to_potr_config()?;
+            let format = export_opts
+                .format
+                .unwrap_or_else(|| ExchangeFormat::from_path(&export_opts.output_file_path));
+            let exported_count = match format.to_spreadsheet_format() {
+                Some(sheet_format) => {
+                    let mut config = profile_config.to_spreadsheet_config()?;
+                    config.sheet_file_path = export_opts.output_file_path.clone();
+                    config.format = sheet_format;
+                    SpreadsheetExporter::new(config, potr_config).run()?
+                }
+                None => {
+                    let mut config = profile_config.to_xliff_config()?;
+                    config.xliff_file_path = export_opts.output_file_path.clone();
+                    config.version = export_opts.version;
+                    config.source_lang = export_opts.source_lang;
+                    XliffExporter::new(config, potr_config).run()?
+                }
+            };
+            println!("Exported {} messages.", exported_count);
+            Ok(())
+        }
+        Command::Import(import_opts) => {
+            let profile_config = import_opts.to_profile_config().or(profile_config);
+            let format = import_opts
+                .format
+                .unwrap_or_else(|| ExchangeFormat::from_path(&import_opts.input_file_path));
+            match format.to_spreadsheet_format() {
+                Some(sheet_format) => {
+                    let mut config = profile_config.to_spreadsheet_config()?;
+                    config.sheet_file_path = import_opts.input_file_path.clone();
+                    config.format = sheet_format;
+                    run_spreadsheet_import(config)
+                }
+                None => {
+                    let mut config = profile_config.to_xliff_config()?;
+                    config.xliff_file_path = import_opts.input_file_path.clone();
+                    run_xliff_import(config)
+                }
+            }
+        }
+    }
+}
+
+fn run_xliff_import(config: XliffConfig) -> Result<()> {
+    let report = XliffImporter::new(config).run()?;
+    println!(
+        "Imported {} messages, untranslated {}, outdated {}, conflicts {}.",
+        report.imported_count,
+        report.untranslated_count,
+        report.outdated_count,
+        report.conflicts.len()
+    );
+    for conflict in &report.conflicts {
+        println!("Conflict: {}", conflict);
+    }
+    if !report.conflicts.is_empty() {
+        anyhow::bail!("{} conflicts found.", report.conflicts.len());
+    }
+    Ok(())
+}
+
+fn run_spreadsheet_import(config: SpreadsheetConfig) -> Result<()> {
+    let report = SpreadsheetImporter::new(config).run()?;
+    println!(
+        "Applied {} rows, unchanged {}, rejected {}.",
+        report.applied_count,
+        report.unchanged_count,
+        report.rejected.len()
+    );
+    for rejected in &report.rejected {
+        println!("Rejected: {}", rejected);
+    }
+    if !report.rejected.is_empty() {
+        anyhow::bail!("{} rows rejected.", report.rejected.len());
+    }
+    Ok(())
+}
+
+/// Clear and clone always use their own engine, so the engines in rules are ignored, and there is
+/// nothing to back-translate.
+fn process_potr_config(mut config: PotrConfig) -> PotrConfig {
+    for rule in config.rules.iter_mut() {
+        rule.engine = None;
+    }
+    config.quality.enabled = false;
+    config
+}
+
+fn run_dry_run(config: PotrConfig, translator_config: TranslatorConfig) -> Result<()> {
+    let report = Potr::new(config, translator_config).dry_run()?;
+    print!("{}", report);
+    Ok(())
+}
+
+async fn run_potr(config: PotrConfig, translator_config: TranslatorConfig) -> Result<()> {
     tracing::debug!(
         "Config loaded: PotrConfig = {:?}, TranslatorConfig = {:?}",
         config,
@@ -34,5 +286,5 @@ async fn main() -> Result<()> {
     })
     .expect("Error setting Ctrl-C handler");
 
-    potr.run().await
+    Ok(potr.run().await?)
 }
@@ -1,20 +1,178 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use potr::*;
-use regex::Regex;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Parser)]
-#[clap(name = "potr", author = "r12f", about = "https://github.com/r12f/potr")]
+#[clap(
+    name = "potr",
+    author = "r12f",
+    about = "https://github.com/r12f/potr",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 pub struct Opts {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Options of the translate command. Running potr without subcommand is an alias of
+    /// `potr translate`, which keeps the command line compatible with older versions.
+    #[clap(flatten)]
+    pub translate: TranslateOpts,
+
+    /// Config file path. By default, potr.toml is searched from the current directory upward.
+    #[clap(long = "config", global = true)]
+    pub config_file_path: Option<String>,
+
+    /// Profile in the config file to use. By default, only the defaults in config file are used.
+    #[clap(long, global = true, env = "POTR_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Print verbose logs.
+    #[clap(short, long, global = true)]
+    pub verbose: bool,
+}
+
+impl Opts {
+    /// Load settings from config file, using the profile specified in command line.
+    pub fn load_profile_config(&self) -> Result<ProfileConfig> {
+        let config_file_path = match &self.config_file_path {
+            Some(path) => Some(PathBuf::from(path)),
+            None => ConfigFile::discover(&std::env::current_dir()?),
+        };
+
+        match config_file_path {
+            Some(path) => Ok(ConfigFile::load(&path)?.profile(self.profile.as_deref())?),
+            None => match &self.profile {
+                Some(profile) => anyhow::bail!(
+                    "Profile \"{}\" is specified, but config file {} is not found.",
+                    profile,
+                    CONFIG_FILE_NAME
+                ),
+                None => Ok(ProfileConfig::default()),
+            },
+        }
+    }
+
+    pub fn into_command(self) -> Command {
+        match self.command {
+            Some(command) => command,
+            None => Command::Translate(Box::new(self.translate)),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Translate messages in PO file. This is the default command.
+    Translate(Box<TranslateOpts>),
+
+    /// Clear the translations of messages in PO file.
+    Clear(ProcessOpts),
+
+    /// Clone msgid into msgstr for messages in PO file.
+    Clone(ProcessOpts),
+
+    /// Update PO file from a POT template, same as msgmerge.
+    Merge(MergeOpts),
+
+    /// Compile PO file into binary MO file, same as msgfmt.
+    Compile(CompileOpts),
+
+    /// Show the translation progress of PO file.
+    Stats(StatsOpts),
+
+    /// Check the translations in PO file for common problems.
+    Check(CheckOpts),
+
+    /// Ask an LLM engine to review the existing translations in PO file.
+    Review(ReviewOpts),
+
+    /// Step through the translated and fuzzy messages in PO file, and review them interactively.
+    Tui(TuiOpts),
+
+    /// Export the messages to translate from PO file to XLIFF for CAT tools, or to CSV/TSV for
+    /// spreadsheets.
+    Export(ExportOpts),
+
+    /// Import the translations in XLIFF or CSV/TSV file back into PO file.
+    Import(ImportOpts),
+}
+
+#[derive(Debug, Args)]
+pub struct TranslateOpts {
+    /// PO file to translate.
     #[clap(short, long = "po")]
-    pub po_file_path: String,
+    pub po_file_path: Option<String>,
 
-    /// Target languange. Please use the short code defined in ISO-639-1.
-    #[clap(short, long, default_value = "en")]
-    pub target_lang: Language,
+    /// Output file. By default, the PO file is updated inplace.
+    #[clap(short, long = "output")]
+    pub output_file_path: Option<String>,
+
+    /// Write a JSON report of the run to this file, with the outcome of each message.
+    #[clap(long = "report")]
+    pub report_file_path: Option<String>,
+
+    /// File format: po, json or ftl. By default, it is decided by the file extension. Formats
+    /// other than PO need a separate output file for the translations.
+    #[clap(long)]
+    pub format: Option<CatalogFormat>,
 
-    #[clap(short, long, env = "POTR_ENGINE", default_value = "openai")]
-    pub engine: TranslatorEngine,
+    /// Target languange. Please use the short code defined in ISO-639-1. [default: en]
+    #[clap(short, long)]
+    pub target_lang: Option<Language>,
+
+    #[clap(flatten)]
+    pub engine: EngineOpts,
+
+    /// Skip translation, only generate po file.
+    #[clap(long, visible_alias = "st")]
+    pub skip_translation: bool,
+
+    /// Mark translated messages as fuzzy.
+    #[clap(long = "fuzzy")]
+    pub as_fuzzy: bool,
+
+    /// Don't check the translations for refusals, unchanged text or wrong language.
+    #[clap(long)]
+    pub no_validate: bool,
+
+    /// Ask OpenAI models to reply in JSON with the translation, notes and confidence.
+    #[clap(long)]
+    pub json_output: bool,
+
+    /// Write the notes of the model into the comments of the messages. Requires --json-output.
+    #[clap(long, requires = "json_output")]
+    pub store_notes: bool,
+
+    /// Back-translate the new translations to estimate their quality, and mark the ones with low
+    /// similarity as fuzzy.
+    #[clap(long)]
+    pub back_translate: bool,
+
+    /// Similarity score from 0 to 1, below which translations are marked as fuzzy. Implies
+    /// --back-translate. [default: 0.5]
+    #[clap(long)]
+    pub quality_threshold: Option<f64>,
+
+    /// Only list the messages to translate and estimate the cost, without calling the engine or
+    /// writing the output file.
+    #[clap(long)]
+    pub dry_run: bool,
+
+    #[clap(flatten)]
+    pub filter: MessageFilterOpts,
+
+    #[clap(flatten)]
+    pub budget: BudgetOpts,
+}
+
+/// Options of the translation engine, shared by the commands that call it.
+#[derive(Debug, Args)]
+pub struct EngineOpts {
+    /// Translator engine. [default: openai]
+    #[clap(short, long, env = "POTR_ENGINE")]
+    pub engine: Option<TranslatorEngine>,
 
     #[clap(short = 'k', long)]
     pub api_key: Option<String>,
@@ -33,14 +191,42 @@ pub struct Opts {
 
     #[clap(short, long, env = "POTR_MODEL")]
     pub model: Option<String>,
+}
+
+/// Options for the message processing tools that don't need a translation engine, e.g. clear and
+/// clone.
+#[derive(Debug, Args)]
+pub struct ProcessOpts {
+    /// PO file to process.
+    #[clap(short, long = "po")]
+    pub po_file_path: Option<String>,
 
+    /// Output file. By default, the PO file is updated inplace.
     #[clap(short, long = "output")]
     pub output_file_path: Option<String>,
 
-    /// Skip translation, only generate po file.
-    #[clap(long, visible_alias = "st")]
-    pub skip_translation: bool,
+    /// Write a JSON report of the run to this file, with the outcome of each message.
+    #[clap(long = "report")]
+    pub report_file_path: Option<String>,
+
+    /// File format: po, json or ftl. By default, it is decided by the file extension.
+    #[clap(long)]
+    pub format: Option<CatalogFormat>,
 
+    /// Mark processed messages as fuzzy.
+    #[clap(long = "fuzzy")]
+    pub as_fuzzy: bool,
+
+    /// Only list the messages to process, without writing the output file.
+    #[clap(long)]
+    pub dry_run: bool,
+
+    #[clap(flatten)]
+    pub filter: MessageFilterOpts,
+}
+
+#[derive(Debug, Args)]
+pub struct MessageFilterOpts {
     /// Process translated message. By default, translated messages are skipped.
     #[clap(long, visible_alias = "pt")]
     pub process_translated: bool,
@@ -58,8 +244,8 @@ pub struct Opts {
     pub process_fuzzy_only: bool,
 
     /// Limit the number of messages to translate.
-    #[clap(short, long, default_value = "0")]
-    pub limit: i32,
+    #[clap(short, long)]
+    pub limit: Option<i32>,
 
     /// Source file path regex to translate. By default, all files are translated.
     #[clap(long)]
@@ -70,93 +256,518 @@ pub struct Opts {
     pub include_message: Option<String>,
 
     /// Regex to exclude messages to translate. By default, all messages are translated.
-    #[clap(long = "include")]
+    #[clap(long = "exclude")]
     pub exclude_message: Option<String>,
 
-    /// Mark translated messages as fuzzy.
-    #[clap(long = "fuzzy")]
-    pub as_fuzzy: bool,
+    /// Filter expression to select messages, e.g. 'source =~ "src/guide" && len(msgid) < 500'.
+    #[clap(long)]
+    pub filter: Option<String>,
+}
 
-    /// Print verbose logs.
-    #[clap(short, long)]
-    pub verbose: bool,
+#[derive(Debug, Args)]
+pub struct BudgetOpts {
+    /// Stop translating when the total characters of the source text would exceed this limit.
+    #[clap(long)]
+    pub max_characters: Option<usize>,
+
+    /// Stop translating when the total prompt tokens would exceed this limit.
+    #[clap(long)]
+    pub max_prompt_tokens: Option<usize>,
+
+    /// Stop translating when the total completion tokens would exceed this limit.
+    #[clap(long)]
+    pub max_completion_tokens: Option<usize>,
+
+    /// Stop translating when the total estimated cost in USD would exceed this limit.
+    #[clap(long)]
+    pub max_cost: Option<f64>,
 }
 
-impl Opts {
-    pub fn to_translator_config(&self) -> Result<TranslatorConfig> {
-        let api_key = match &self.api_key {
-            Some(key) => key.clone(),
-            None => match self.engine {
-                TranslatorEngine::OpenAI => match std::env::var("POTR_API_KEY_OPENAI") {
-                    Ok(key) => key,
-                    Err(_) => {
-                        anyhow::bail!(
-                            "OpenAI API key is not specified, please specify it via \"-k\" option or POTR_API_KEY_OPENAI environment variable."
-                        );
-                    }
-                },
-                TranslatorEngine::AzureOpenAI => match std::env::var("POTR_API_KEY_AZURE_OPENAI") {
-                    Ok(key) => key,
-                    Err(_) => {
-                        anyhow::bail!(
-                            "Azure OpenAI service API key is not specified, please specify it via \"-k\" option or POTR_API_KEY_AZURE_OPENAI environment variable."
-                        );
-                    }
-                },
-                TranslatorEngine::DeepL => match std::env::var("POTR_API_KEY_DEEPL") {
-                    Ok(key) => key,
-                    Err(_) => {
-                        anyhow::bail!(
-                            "DeepL API key is not specified, please specify it via \"-k\" option or POTR_API_KEY_DEEPL environment variable."
-                        );
-                    }
-                },
-                _ => "".to_string(),
-            },
+impl TranslateOpts {
+    /// Settings from command line, which take precedence over the ones in config file.
+    pub fn to_profile_config(&self) -> ProfileConfig {
+        let mut profile = ProfileConfig {
+            po_file_path: self.po_file_path.clone(),
+            output_file_path: self.output_file_path.clone(),
+            report_file_path: self.report_file_path.clone(),
+            format: self.format,
+            target_lang: self.target_lang,
+            skip_translation: self.skip_translation.then_some(true),
+            as_fuzzy: self.as_fuzzy.then_some(true),
+            validate: self.no_validate.then_some(false),
+            json_output: self.json_output.then_some(true),
+            store_notes: self.store_notes.then_some(true),
+            ..Default::default()
         };
+        self.engine.apply_to(&mut profile);
+        self.filter.apply_to(&mut profile);
+        profile.budget = self.budget.to_budget();
+        if self.back_translate || self.quality_threshold.is_some() {
+            profile.back_translation = Some(BackTranslationConfig {
+                enabled: Some(true),
+                threshold: self.quality_threshold,
+                ..Default::default()
+            });
+        }
+        profile
+    }
 
-        let config = TranslatorConfig {
-            engine: self.engine.clone(),
-            target_lang: self.target_lang,
-            model: self.model.clone(),
-            api_base: self.api_base.clone(),
-            api_key,
-            api_version: self.api_version.clone(),
-            api_deployment_id: self.api_deployment_id.clone(),
-            extra_params: Default::default(),
+    /// Built-in defaults of translate command, used when not set in command line or config file.
+    pub fn default_profile_config() -> ProfileConfig {
+        ProfileConfig {
+            engine: Some(TranslatorEngine::OpenAI),
+            target_lang: Some(Language::English),
+            ..Default::default()
+        }
+    }
+}
+
+pub fn get_api_key_from_env(engine: TranslatorEngine) -> Result<String> {
+    let api_key = match engine {
+        TranslatorEngine::OpenAI => match std::env::var("POTR_API_KEY_OPENAI") {
+            Ok(key) => key,
+            Err(_) => {
+                anyhow::bail!(
+                    "OpenAI API key is not specified, please specify it via \"-k\" option or POTR_API_KEY_OPENAI environment variable."
+                );
+            }
+        },
+        TranslatorEngine::AzureOpenAI => match std::env::var("POTR_API_KEY_AZURE_OPENAI") {
+            Ok(key) => key,
+            Err(_) => {
+                anyhow::bail!(
+                    "Azure OpenAI service API key is not specified, please specify it via \"-k\" option or POTR_API_KEY_AZURE_OPENAI environment variable."
+                );
+            }
+        },
+        TranslatorEngine::DeepL => match std::env::var("POTR_API_KEY_DEEPL") {
+            Ok(key) => key,
+            Err(_) => {
+                anyhow::bail!(
+                    "DeepL API key is not specified, please specify it via \"-k\" option or POTR_API_KEY_DEEPL environment variable."
+                );
+            }
+        },
+        _ => "".to_string(),
+    };
+
+    Ok(api_key)
+}
+
+impl EngineOpts {
+    pub fn apply_to(&self, profile: &mut ProfileConfig) {
+        profile.engine = self.engine;
+        profile.model = self.model.clone();
+        profile.api_base = self.api_base.clone();
+        profile.api_version = self.api_version.clone();
+        profile.api_deployment_id = self.api_deployment_id.clone();
+    }
+
+    pub fn get_api_key(&self, engine: TranslatorEngine) -> Result<String> {
+        match &self.api_key {
+            Some(key) => Ok(key.clone()),
+            None => get_api_key_from_env(engine),
+        }
+    }
+}
+
+impl ProcessOpts {
+    /// Settings from command line, which take precedence over the ones in config file. The engine
+    /// is always decided by the command.
+    pub fn to_profile_config(&self, engine: TranslatorEngine) -> ProfileConfig {
+        let mut profile = ProfileConfig {
+            po_file_path: self.po_file_path.clone(),
+            output_file_path: self.output_file_path.clone(),
+            report_file_path: self.report_file_path.clone(),
+            format: self.format,
+            engine: Some(engine),
+            as_fuzzy: self.as_fuzzy.then_some(true),
+            ..Default::default()
         };
+        self.filter.apply_to(&mut profile);
+        profile
+    }
+}
 
-        Ok(config)
+impl MessageFilterOpts {
+    pub fn apply_to(&self, profile: &mut ProfileConfig) {
+        profile.process_translated = self.process_translated.then_some(true);
+        profile.process_code_blocks = self.process_code_blocks.then_some(true);
+        profile.process_fuzzy_only = self.process_fuzzy_only.then_some(true);
+        profile.skip_text = self.skip_text.then_some(true);
+        profile.limit = self.limit;
+        profile.source = self.source.clone();
+        profile.include = self.include_message.clone();
+        profile.exclude = self.exclude_message.clone();
+        profile.filter = self.filter.clone();
     }
+}
 
-    pub fn to_potr_config(&self) -> PotrConfig {
-        let output_file_path = match &self.output_file_path {
-            Some(path) => path.clone(),
-            None => self.po_file_path.clone(),
+impl BudgetOpts {
+    pub fn to_budget(&self) -> Option<Budget> {
+        let budget = Budget {
+            max_characters: self.max_characters,
+            max_prompt_tokens: self.max_prompt_tokens,
+            max_completion_tokens: self.max_completion_tokens,
+            max_cost: self.max_cost,
         };
+        (budget != Budget::default()).then_some(budget)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct MergeOpts {
+    /// PO file with existing translations.
+    #[clap(short, long = "po")]
+    pub po_file_path: String,
+
+    /// POT template file with the latest messages.
+    #[clap(long = "pot")]
+    pub pot_file_path: String,
+
+    /// Output file. By default, the PO file is updated inplace.
+    #[clap(short, long = "output")]
+    pub output_file_path: Option<String>,
+
+    /// Do not use fuzzy matching when an exact match is not found.
+    #[clap(long, visible_alias = "nfm")]
+    pub no_fuzzy_matching: bool,
+
+    /// Do not keep the previous msgid (#|) of fuzzy messages.
+    #[clap(long)]
+    pub no_previous: bool,
+
+    /// Filter expression to select the template messages to look for fuzzy matches. By default,
+    /// all messages are matched.
+    #[clap(long)]
+    pub filter: Option<MessageFilter>,
+}
 
-        PotrConfig {
+impl MergeOpts {
+    pub fn to_merge_config(&self) -> MergeConfig {
+        MergeConfig {
             po_file_path: self.po_file_path.clone(),
-            output_file_path: output_file_path,
-            skip_translation: self.skip_translation,
-            skip_translated: !self.process_translated,
-            skip_code_blocks: !self.process_code_blocks,
-            skip_text: self.skip_text,
-            skip_non_fuzzy: !self.process_fuzzy_only,
-            source_regex: match &self.source {
-                Some(s) => Some(Regex::new(s).unwrap()),
-                None => None,
-            },
-            include_message_regex: match &self.include_message {
-                Some(s) => Some(Regex::new(s).unwrap()),
-                None => None,
+            pot_file_path: self.pot_file_path.clone(),
+            output_file_path: match &self.output_file_path {
+                Some(path) => path.clone(),
+                None => self.po_file_path.clone(),
             },
-            exclude_message_regex: match &self.exclude_message {
-                Some(s) => Some(Regex::new(s).unwrap()),
-                None => None,
+            fuzzy_matching: !self.no_fuzzy_matching,
+            keep_previous: !self.no_previous,
+            filter: self.filter.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct CompileOpts {
+    /// PO file to compile.
+    #[clap(short, long = "po")]
+    pub po_file_path: String,
+
+    /// Output MO file. By default, it is the PO file path with ".mo" extension.
+    #[clap(short, long = "output")]
+    pub output_file_path: Option<String>,
+
+    /// Include fuzzy messages. By default, fuzzy messages are skipped.
+    #[clap(long, visible_alias = "use-fuzzy")]
+    pub include_fuzzy: bool,
+
+    /// Filter expression to select messages to compile. By default, all messages are compiled.
+    #[clap(long)]
+    pub filter: Option<MessageFilter>,
+}
+
+impl CompileOpts {
+    pub fn to_compile_config(&self) -> CompileConfig {
+        CompileConfig {
+            po_file_path: self.po_file_path.clone(),
+            output_file_path: match &self.output_file_path {
+                Some(path) => path.clone(),
+                None => Path::new(&self.po_file_path)
+                    .with_extension("mo")
+                    .to_string_lossy()
+                    .to_string(),
             },
-            message_limit: self.limit,
-            as_fuzzy: self.as_fuzzy,
+            include_fuzzy: self.include_fuzzy,
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct StatsOpts {
+    /// PO file to check.
+    #[clap(short, long = "po")]
+    pub po_file_path: String,
+
+    /// Output format: table, json or csv.
+    #[clap(short, long, default_value = "table")]
+    pub format: StatsFormat,
+
+    /// Fail if the percentage of translated messages is lower than this, e.g. 95.
+    #[clap(long)]
+    pub threshold: Option<f64>,
+
+    /// Filter expression to select messages to count. By default, all messages are counted.
+    #[clap(long)]
+    pub filter: Option<MessageFilter>,
+}
+
+impl StatsOpts {
+    pub fn to_stats_config(&self) -> StatsConfig {
+        StatsConfig {
+            po_file_path: self.po_file_path.clone(),
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct CheckOpts {
+    /// PO file to check.
+    #[clap(short, long = "po")]
+    pub po_file_path: String,
+
+    /// Output file for --mark-fuzzy. By default, the PO file is updated inplace.
+    #[clap(short, long = "output")]
+    pub output_file_path: Option<String>,
+
+    /// Language of the translations. By default, it is read from the "Language" header of the
+    /// PO file.
+    #[clap(short, long)]
+    pub target_lang: Option<Language>,
+
+    /// Output format: text or json.
+    #[clap(short, long, default_value = "text")]
+    pub format: CheckFormat,
+
+    /// Mark the messages with problems as fuzzy.
+    #[clap(long)]
+    pub mark_fuzzy: bool,
+
+    /// Filter expression to select messages to check. By default, all translated messages are
+    /// checked.
+    #[clap(long)]
+    pub filter: Option<MessageFilter>,
+}
+
+impl CheckOpts {
+    pub fn to_check_config(&self) -> CheckConfig {
+        CheckConfig {
+            output_file_path: self
+                .output_file_path
+                .clone()
+                .unwrap_or_else(|| self.po_file_path.clone()),
+            po_file_path: self.po_file_path.clone(),
+            target_lang: self.target_lang,
+            mark_fuzzy: self.mark_fuzzy,
+            filter: self.filter.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ReviewOpts {
+    /// PO file to review.
+    #[clap(short, long = "po")]
+    pub po_file_path: Option<String>,
+
+    /// Output file. By default, the PO file is updated inplace.
+    #[clap(short, long = "output")]
+    pub output_file_path: Option<String>,
+
+    /// Write the suggested corrections to this JSON file, instead of the PO file.
+    #[clap(long = "review-file")]
+    pub review_file_path: Option<String>,
+
+    /// Target languange. Please use the short code defined in ISO-639-1. [default: en]
+    #[clap(short, long)]
+    pub target_lang: Option<Language>,
+
+    #[clap(flatten)]
+    pub engine: EngineOpts,
+
+    /// Limit the number of messages to review.
+    #[clap(short, long)]
+    pub limit: Option<i32>,
+
+    /// Filter expression to select messages, e.g. 'source =~ "src/guide" && len(msgid) < 500'.
+    #[clap(long)]
+    pub filter: Option<String>,
+}
+
+impl ReviewOpts {
+    /// Settings from command line, which take precedence over the ones in config file.
+    pub fn to_profile_config(&self) -> ProfileConfig {
+        let mut profile = ProfileConfig {
+            po_file_path: self.po_file_path.clone(),
+            output_file_path: self.output_file_path.clone(),
+            target_lang: self.target_lang,
+            limit: self.limit,
+            filter: self.filter.clone(),
+            ..Default::default()
+        };
+        self.engine.apply_to(&mut profile);
+        profile
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct TuiOpts {
+    /// PO file to review.
+    #[clap(short, long = "po")]
+    pub po_file_path: Option<String>,
+
+    /// Output file. By default, the PO file is updated inplace.
+    #[clap(short, long = "output")]
+    pub output_file_path: Option<String>,
+
+    /// Target languange. Please use the short code defined in ISO-639-1. [default: en]
+    #[clap(short, long)]
+    pub target_lang: Option<Language>,
+
+    #[clap(flatten)]
+    pub engine: EngineOpts,
+
+    /// Other engines that can be picked to regenerate the suggestion. Their API keys are read from
+    /// the environment variables.
+    #[clap(long = "alt-engine")]
+    pub alt_engines: Vec<TranslatorEngine>,
+
+    /// Only review fuzzy messages. By default, translated messages are also reviewed.
+    #[clap(long)]
+    pub fuzzy_only: bool,
+
+    /// Filter expression to select messages, e.g. 'source =~ "src/guide" && len(msgid) < 500'.
+    #[clap(long)]
+    pub filter: Option<String>,
+}
+
+impl TuiOpts {
+    /// Settings from command line, which take precedence over the ones in config file.
+    pub fn to_profile_config(&self) -> ProfileConfig {
+        let mut profile = ProfileConfig {
+            po_file_path: self.po_file_path.clone(),
+            output_file_path: self.output_file_path.clone(),
+            target_lang: self.target_lang,
+            process_fuzzy_only: self.fuzzy_only.then_some(true),
+            filter: self.filter.clone(),
+            ..Default::default()
+        };
+        self.engine.apply_to(&mut profile);
+        profile
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ExportOpts {
+    /// PO file to export.
+    #[clap(short, long = "po")]
+    pub po_file_path: Option<String>,
+
+    /// Output file.
+    #[clap(short, long = "output")]
+    pub output_file_path: String,
+
+    /// File format. By default, it is decided by the file extension, and XLIFF is used for
+    /// unknown extensions.
+    #[clap(short, long, value_enum)]
+    pub format: Option<ExchangeFormat>,
+
+    /// XLIFF version: 1.2 or 2.0.
+    #[clap(long = "xliff-version", default_value = "1.2")]
+    pub version: XliffVersion,
+
+    /// Source languange, i.e. the language of msgid.
+    #[clap(short, long, default_value = "en")]
+    pub source_lang: Language,
+
+    /// Target languange. Please use the short code defined in ISO-639-1. [default: en]
+    #[clap(short, long)]
+    pub target_lang: Option<Language>,
+
+    #[clap(flatten)]
+    pub filter: MessageFilterOpts,
+}
+
+impl ExportOpts {
+    /// Settings from command line, which take precedence over the ones in config file.
+    pub fn to_profile_config(&self) -> ProfileConfig {
+        let mut profile = ProfileConfig {
+            po_file_path: self.po_file_path.clone(),
+            target_lang: self.target_lang,
+            ..Default::default()
+        };
+        self.filter.apply_to(&mut profile);
+        profile
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ImportOpts {
+    /// PO file to import into.
+    #[clap(short, long = "po")]
+    pub po_file_path: Option<String>,
+
+    /// XLIFF, CSV or TSV file with the translations. Both XLIFF 1.2 and 2.0 are supported.
+    #[clap(
+        short,
+        long = "input",
+        visible_short_alias = 'x',
+        visible_alias = "xliff"
+    )]
+    pub input_file_path: String,
+
+    /// File format. By default, it is decided by the file extension, and XLIFF is used for
+    /// unknown extensions.
+    #[clap(short, long, value_enum)]
+    pub format: Option<ExchangeFormat>,
+
+    /// Output file. By default, the PO file is updated inplace.
+    #[clap(short, long = "output")]
+    pub output_file_path: Option<String>,
+}
+
+impl ImportOpts {
+    /// Settings from command line, which take precedence over the ones in config file.
+    pub fn to_profile_config(&self) -> ProfileConfig {
+        ProfileConfig {
+            po_file_path: self.po_file_path.clone(),
+            output_file_path: self.output_file_path.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Format of the files exchanged with translators and reviewers outside of potr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExchangeFormat {
+    Xliff,
+    Csv,
+    Tsv,
+}
+
+impl ExchangeFormat {
+    pub fn from_path(path: &str) -> ExchangeFormat {
+        match Path::new(path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .as_deref()
+        {
+            Some("csv") => ExchangeFormat::Csv,
+            Some("tsv") => ExchangeFormat::Tsv,
+            _ => ExchangeFormat::Xliff,
+        }
+    }
+
+    pub fn to_spreadsheet_format(self) -> Option<SpreadsheetFormat> {
+        match self {
+            ExchangeFormat::Xliff => None,
+            ExchangeFormat::Csv => Some(SpreadsheetFormat::Csv),
+            ExchangeFormat::Tsv => Some(SpreadsheetFormat::Tsv),
         }
     }
 }
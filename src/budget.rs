@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+/// Spending limits of a translation run. Limits that are not set are not checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Budget {
+    /// Max total characters of the source text.
+    pub max_characters: Option<usize>,
+
+    /// Max total prompt (input) tokens.
+    pub max_prompt_tokens: Option<usize>,
+
+    /// Max total completion (output) tokens.
+    pub max_completion_tokens: Option<usize>,
+
+    /// Max total estimated cost in USD, calculated with the price table.
+    pub max_cost: Option<f64>,
+}
+
+impl Budget {
+    /// Merge 2 budgets. Limits in self take precedence, and the rest are taken from `base`.
+    pub fn or(self, base: Budget) -> Budget {
+        Budget {
+            max_characters: self.max_characters.or(base.max_characters),
+            max_prompt_tokens: self.max_prompt_tokens.or(base.max_prompt_tokens),
+            max_completion_tokens: self.max_completion_tokens.or(base.max_completion_tokens),
+            max_cost: self.max_cost.or(base.max_cost),
+        }
+    }
+}
+
+/// Usage of one or more translation requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Usage {
+    pub characters: usize,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+
+    /// Cost in USD. `None` if the price of the engine or model is unknown.
+    pub cost: Option<f64>,
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.characters += other.characters;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cost = match (self.cost, other.cost) {
+            (Some(a), Some(b)) => Some(a + b),
+            _ => None,
+        };
+    }
+}
+
+/// Tracks the spending of a run against the budget.
+#[derive(Debug, Clone)]
+pub struct BudgetTracker {
+    pub budget: Budget,
+    pub spent: Usage,
+}
+
+impl BudgetTracker {
+    pub fn new(budget: Budget) -> BudgetTracker {
+        BudgetTracker {
+            budget,
+            spent: Usage {
+                cost: Some(0.0),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Check if the next request can be sent without exceeding the budget. On failure, the
+    /// exceeded limit is returned.
+    pub fn check(&self, next: &Usage) -> Result<(), String> {
+        let exceeds = |limit: Option<usize>, spent: usize, next: usize| {
+            limit.is_some_and(|limit| spent + next > limit)
+        };
+
+        if exceeds(
+            self.budget.max_characters,
+            self.spent.characters,
+            next.characters,
+        ) {
+            return Err(format!(
+                "MaxCharacters = {}, Spent = {}, Next = {}",
+                self.budget.max_characters.unwrap(),
+                self.spent.characters,
+                next.characters
+            ));
+        }
+
+        if exceeds(
+            self.budget.max_prompt_tokens,
+            self.spent.prompt_tokens,
+            next.prompt_tokens,
+        ) {
+            return Err(format!(
+                "MaxPromptTokens = {}, Spent = {}, Next = {}",
+                self.budget.max_prompt_tokens.unwrap(),
+                self.spent.prompt_tokens,
+                next.prompt_tokens
+            ));
+        }
+
+        if exceeds(
+            self.budget.max_completion_tokens,
+            self.spent.completion_tokens,
+            next.completion_tokens,
+        ) {
+            return Err(format!(
+                "MaxCompletionTokens = {}, Spent = {}, Next = {}",
+                self.budget.max_completion_tokens.unwrap(),
+                self.spent.completion_tokens,
+                next.completion_tokens
+            ));
+        }
+
+        if let Some(max_cost) = self.budget.max_cost {
+            match (self.spent.cost, next.cost) {
+                (Some(spent), Some(next)) if spent + next > max_cost => {
+                    return Err(format!(
+                        "MaxCost = {}, Spent = {:.4}, Next = {:.4}",
+                        max_cost, spent, next
+                    ));
+                }
+                (Some(_), Some(_)) => {}
+                _ => return Err("MaxCost is set, but the price is unknown".to_string()),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn add(&mut self, usage: Usage) {
+        self.spent += usage;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn budget_should_be_checked_before_spending() {
+        let mut tracker = BudgetTracker::new(Budget {
+            max_characters: Some(100),
+            max_cost: Some(1.0),
+            ..Default::default()
+        });
+
+        let usage = Usage {
+            characters: 40,
+            prompt_tokens: 10,
+            completion_tokens: 10,
+            cost: Some(0.4),
+        };
+        assert!(tracker.check(&usage).is_ok());
+        tracker.add(usage);
+        assert!(tracker.check(&usage).is_ok());
+        tracker.add(usage);
+
+        // Both characters and cost would be exceeded, and characters are checked first.
+        assert_eq!(
+            tracker.check(&usage),
+            Err("MaxCharacters = 100, Spent = 80, Next = 40".to_string())
+        );
+
+        let unknown_price = Usage {
+            cost: None,
+            ..Default::default()
+        };
+        assert!(tracker.check(&unknown_price).is_err());
+        assert!(BudgetTracker::new(Budget::default())
+            .check(&unknown_price)
+            .is_ok());
+    }
+}
@@ -0,0 +1,500 @@
+use crate::{
+    dry_run::preview,
+    error::PotrError,
+    filter::MessageFilter,
+    formats,
+    po_writer::{display_width, message_key},
+    translators::Language,
+};
+use anyhow::Result;
+use polib::{
+    catalog::Catalog,
+    message::{MessageMutView, MessageView},
+};
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{self, Write},
+    path::Path,
+    str::FromStr,
+};
+use strum_macros::EnumString;
+
+#[derive(Debug, Clone)]
+pub struct CheckConfig {
+    pub po_file_path: String,
+    pub output_file_path: String,
+
+    /// Language of the translations. By default, it is read from the "Language" header of the
+    /// PO file.
+    pub target_lang: Option<Language>,
+
+    /// Mark the messages with problems as fuzzy, and write them to the output file.
+    pub mark_fuzzy: bool,
+
+    /// Only check the messages matching the filter.
+    pub filter: Option<MessageFilter>,
+
+    /// Allowed range of the display width ratio between msgstr and msgid.
+    pub min_length_ratio: f64,
+    pub max_length_ratio: f64,
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        Self {
+            po_file_path: Default::default(),
+            output_file_path: Default::default(),
+            target_lang: None,
+            mark_fuzzy: false,
+            filter: None,
+            min_length_ratio: 0.3,
+            max_length_ratio: 3.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+pub enum CheckFormat {
+    #[strum(serialize = "text")]
+    Text,
+    #[strum(serialize = "json")]
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckKind {
+    Placeholder,
+    Markdown,
+    Brackets,
+    Whitespace,
+    Untranslated,
+    Script,
+    Length,
+    Duplicate,
+}
+
+impl fmt::Display for CheckKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            CheckKind::Placeholder => "placeholder",
+            CheckKind::Markdown => "markdown",
+            CheckKind::Brackets => "brackets",
+            CheckKind::Whitespace => "whitespace",
+            CheckKind::Untranslated => "untranslated",
+            CheckKind::Script => "script",
+            CheckKind::Length => "length",
+            CheckKind::Duplicate => "duplicate",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// A problem found in the translation of a message.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckIssue {
+    pub msgid: String,
+
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub msgctxt: String,
+
+    pub source: String,
+    pub kind: CheckKind,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CheckReport {
+    pub checked_count: usize,
+    pub marked_fuzzy_count: usize,
+    pub issues: Vec<CheckIssue>,
+}
+
+/// Checks the existing translations in PO files for common problems.
+pub struct PoChecker {
+    pub config: CheckConfig,
+    placeholder_regex: Regex,
+    link_regex: Regex,
+}
+
+impl PoChecker {
+    pub fn new(config: CheckConfig) -> PoChecker {
+        PoChecker {
+            config,
+            // Printf style (%s, %1$d, %(name)s) and brace style ({0}, {name}, {{name}}) placeholders.
+            placeholder_regex: Regex::new(
+                r"%(\d+\$)?[-+ #0]*\d*(\.\d+)?[sdifuxXeEgGcp]|%\(\w+\)[sdif]|\{\{?[\w.\-]*\}?\}",
+            )
+            .unwrap(),
+            link_regex: Regex::new(r"\]\(([^)\s]+)[^)]*\)").unwrap(),
+        }
+    }
+
+    pub fn run(&self) -> Result<CheckReport, PotrError> {
+        tracing::info!("Loading po file: {}", self.config.po_file_path);
+        let mut po_file = formats::load_po_file(&self.config.po_file_path)?;
+
+        let target_lang = self.config.target_lang.or_else(|| {
+            // Language header can have region, e.g. "zh_CN" or "pt-BR".
+            let language = po_file.metadata.language.split(['_', '-', '@']).next()?;
+            Language::from_str(language).ok()
+        });
+
+        let mut report = self.check(&po_file, target_lang);
+        tracing::info!(
+            "Check completed! Checked = {}, Issues = {}",
+            report.checked_count,
+            report.issues.len()
+        );
+
+        if self.config.mark_fuzzy && !report.issues.is_empty() {
+            let keys: HashSet<String> = po_file
+                .messages()
+                .filter(|m| report.issues.iter().any(|i| is_issue_of(i, *m)))
+                .map(message_key)
+                .collect();
+            for mut message in po_file.messages_mut() {
+                if !message.is_fuzzy() && keys.contains(&message_key(&message)) {
+                    message.flags_mut().add_flag("fuzzy");
+                    report.marked_fuzzy_count += 1;
+                }
+            }
+
+            tracing::info!("Write to output: {:?}", self.config.output_file_path);
+            polib::po_file::write(&po_file, Path::new(&self.config.output_file_path))?;
+        }
+
+        Ok(report)
+    }
+
+    pub fn check(&self, catalog: &Catalog, target_lang: Option<Language>) -> CheckReport {
+        let mut report = CheckReport::default();
+        let mut msgids_by_msgstr: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for message in catalog.messages() {
+            if !self.is_selected(message) {
+                continue;
+            }
+            report.checked_count += 1;
+
+            let mut problems = vec![];
+            for (source, translation) in translation_pairs(message) {
+                self.check_translation(source, translation, target_lang, &mut problems);
+            }
+
+            // The same problem can be found in multiple plural forms, but only needs reporting once.
+            problems.sort_by_key(|(kind, _)| *kind);
+            problems.dedup_by_key(|(kind, _)| *kind);
+            report
+                .issues
+                .extend(problems.into_iter().map(|(kind, detail)| CheckIssue {
+                    msgid: message.msgid().to_string(),
+                    msgctxt: message.msgctxt().to_string(),
+                    source: message.source().to_string(),
+                    kind,
+                    detail,
+                }));
+
+            if let Ok(msgstr) = message.msgstr() {
+                let msgids = msgids_by_msgstr.entry(msgstr).or_default();
+                if !msgids.contains(&message.msgid()) {
+                    msgids.push(message.msgid());
+                }
+            }
+        }
+
+        for message in catalog.messages().filter(|m| self.is_selected(*m)) {
+            let msgids = message
+                .msgstr()
+                .ok()
+                .and_then(|msgstr| msgids_by_msgstr.get(msgstr));
+            let others: Vec<&str> = match msgids {
+                Some(msgids) => msgids
+                    .iter()
+                    .filter(|msgid| **msgid != message.msgid())
+                    .copied()
+                    .collect(),
+                _ => continue,
+            };
+            if !others.is_empty() {
+                report.issues.push(CheckIssue {
+                    msgid: message.msgid().to_string(),
+                    msgctxt: message.msgctxt().to_string(),
+                    source: message.source().to_string(),
+                    kind: CheckKind::Duplicate,
+                    detail: format!("Same msgstr is used by: {:?}", others),
+                });
+            }
+        }
+
+        report
+    }
+
+    fn is_selected(&self, message: &dyn MessageView) -> bool {
+        message.is_translated()
+            && self
+                .config
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.is_match(message))
+    }
+
+    fn check_translation(
+        &self,
+        source: &str,
+        translation: &str,
+        target_lang: Option<Language>,
+        problems: &mut Vec<(CheckKind, String)>,
+    ) {
+        let placeholders = |text| -> Vec<String> {
+            let mut placeholders: Vec<String> = self
+                .placeholder_regex
+                .find_iter(text)
+                .map(|m| m.as_str().to_string())
+                .collect();
+            placeholders.sort();
+            placeholders
+        };
+        let (expected, actual) = (placeholders(source), placeholders(translation));
+        if expected != actual {
+            problems.push((
+                CheckKind::Placeholder,
+                format!("Placeholders differ: {:?} vs {:?}", expected, actual),
+            ));
+        }
+
+        let (expected, actual) = (
+            self.markdown_structure(source),
+            self.markdown_structure(translation),
+        );
+        if expected != actual {
+            problems.push((
+                CheckKind::Markdown,
+                format!("Markdown structure differs: {} vs {}", expected, actual),
+            ));
+        }
+
+        if is_balanced(source) && !is_balanced(translation) {
+            problems.push((
+                CheckKind::Brackets,
+                "Brackets or quotes are unbalanced".to_string(),
+            ));
+        }
+
+        let leading = |text: &str| text[..text.len() - text.trim_start().len()].to_string();
+        let trailing = |text: &str| text[text.trim_end().len()..].to_string();
+        if leading(source) != leading(translation) || trailing(source) != trailing(translation) {
+            problems.push((
+                CheckKind::Whitespace,
+                format!(
+                    "Leading or trailing whitespace differs: ({:?}, {:?}) vs ({:?}, {:?})",
+                    leading(source),
+                    trailing(source),
+                    leading(translation),
+                    trailing(translation)
+                ),
+            ));
+        }
+
+        // Code blocks are usually kept as they are, so they are not expected to be translated.
+        if source.starts_with("```") {
+            return;
+        }
+
+        let word_count = source
+            .split_whitespace()
+            .filter(|w| w.chars().any(char::is_alphabetic))
+            .count();
+        if source == translation && word_count >= 2 {
+            problems.push((
+                CheckKind::Untranslated,
+                "msgstr is the same as msgid".to_string(),
+            ));
+        }
+
+        if let Some(script) = target_lang.and_then(Script::of_language) {
+            let text = self.placeholder_regex.replace_all(translation, "");
+            let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+            if !letters.is_empty() && !letters.iter().any(|c| script.contains(*c)) {
+                problems.push((
+                    CheckKind::Script,
+                    format!("No {:?} characters found in msgstr", script),
+                ));
+            }
+        }
+
+        let source_width = display_width(source);
+        if source_width >= 20 {
+            let ratio = display_width(translation) as f64 / source_width as f64;
+            if ratio < self.config.min_length_ratio || ratio > self.config.max_length_ratio {
+                problems.push((
+                    CheckKind::Length,
+                    format!("Length ratio {:.2} is out of range", ratio),
+                ));
+            }
+        }
+    }
+
+    fn markdown_structure(&self, text: &str) -> String {
+        let mut headings = vec![];
+        let mut fence_count = 0;
+        for line in text.lines() {
+            let line = line.trim_start();
+            if line.starts_with("```") {
+                fence_count += 1;
+            }
+            let level = line.chars().take_while(|c| *c == '#').count();
+            if level > 0 && line[level..].starts_with(' ') {
+                headings.push(level);
+            }
+        }
+
+        let code_span_count = text.matches('`').count();
+        let links: Vec<&str> = self
+            .link_regex
+            .captures_iter(text)
+            .map(|c| c.get(1).unwrap().as_str())
+            .collect();
+        format!(
+            "(headings: {:?}, fences: {}, backticks: {}, links: {:?})",
+            headings, fence_count, code_span_count, links
+        )
+    }
+}
+
+impl CheckReport {
+    pub fn format(&self, format: CheckFormat) -> Result<String, PotrError> {
+        match format {
+            CheckFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            CheckFormat::Text => Ok(self.to_text()?),
+        }
+    }
+
+    fn to_text(&self) -> Result<String> {
+        let mut output = String::new();
+        for issue in &self.issues {
+            writeln!(
+                output,
+                "[{}] {}: {}",
+                issue.kind, issue.source, issue.detail
+            )?;
+            writeln!(output, "  msgid: {}", preview(&issue.msgid))?;
+        }
+        writeln!(
+            output,
+            "Checked messages: {}, issues: {}, marked as fuzzy: {}",
+            self.checked_count,
+            self.issues.len(),
+            self.marked_fuzzy_count
+        )?;
+        Ok(output)
+    }
+}
+
+fn is_issue_of(issue: &CheckIssue, message: &dyn MessageView) -> bool {
+    issue.msgid == message.msgid() && issue.msgctxt == message.msgctxt()
+}
+
+/// Source and translation pairs of a message. For plural messages, the first form is the
+/// translation of msgid, and the other forms are the translations of msgid_plural.
+fn translation_pairs(message: &dyn MessageView) -> Vec<(&str, &str)> {
+    match (message.msgid_plural(), message.msgstr_plural()) {
+        (Ok(msgid_plural), Ok(msgstr_plural)) => msgstr_plural
+            .iter()
+            .enumerate()
+            .map(|(i, msgstr)| {
+                let source = if i == 0 {
+                    message.msgid()
+                } else {
+                    msgid_plural
+                };
+                (source, msgstr.as_str())
+            })
+            .collect(),
+        _ => vec![(message.msgid(), message.msgstr().unwrap_or_default())],
+    }
+}
+
+fn is_balanced(text: &str) -> bool {
+    let mut stack = vec![];
+    for c in text.chars() {
+        match c {
+            '(' | '（' => stack.push(')'),
+            '[' | '【' => stack.push(']'),
+            '{' => stack.push('}'),
+            '「' => stack.push('」'),
+            ')' | '）' if stack.pop() != Some(')') => return false,
+            ']' | '】' if stack.pop() != Some(']') => return false,
+            '}' if stack.pop() != Some('}') => return false,
+            '」' if stack.pop() != Some('」') => return false,
+            _ => {}
+        }
+    }
+
+    stack.is_empty()
+        && text.matches('"').count().is_multiple_of(2)
+        && text.matches('“').count() == text.matches('”').count()
+}
+
+/// Writing system of a language, detected by the Unicode block of the characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    Chinese,
+    Japanese,
+    Korean,
+}
+
+impl Script {
+    pub(crate) fn of_language(language: Language) -> Option<Script> {
+        use Language::*;
+        let script = match language {
+            Chinese => Script::Chinese,
+            Japanese => Script::Japanese,
+            Korean => Script::Korean,
+            Russian | Ukrainian | Bulgarian | Belarusian | Macedonian | Kazakh | Kirghiz
+            | Mongolian | Tajik => Script::Cyrillic,
+            Greek => Script::Greek,
+            Arabic | Persian | Urdu | Pushto => Script::Arabic,
+            Hebrew | Yiddish => Script::Hebrew,
+            Hindi | Marathi | Nepali | Sanskrit => Script::Devanagari,
+            Thai => Script::Thai,
+            English | German | French | Spanish | Italian | Portuguese | Dutch | Polish | Czech
+            | Slovak | Swedish | Danish | Norwegian | NorwegianBokmal | NorwegianNynorsk
+            | Finnish | Hungarian | Romanian | Turkish | Indonesian | Malay | Vietnamese
+            | Croatian | Slovenian | Estonian | Latvian | Lithuanian | Catalan | Galician
+            | Basque | Irish | Welsh | Icelandic | Afrikaans | Swahili | Tagalog | Albanian => {
+                Script::Latin
+            }
+            _ => return None,
+        };
+        Some(script)
+    }
+
+    pub(crate) fn contains(&self, c: char) -> bool {
+        let is_han = matches!(c as u32, 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF);
+        match self {
+            Script::Latin => {
+                c.is_ascii_alphabetic() || matches!(c as u32, 0xC0..=0x24F | 0x1E00..=0x1EFF)
+            }
+            Script::Cyrillic => matches!(c as u32, 0x400..=0x52F),
+            Script::Greek => matches!(c as u32, 0x370..=0x3FF | 0x1F00..=0x1FFF),
+            Script::Arabic => matches!(c as u32, 0x600..=0x6FF | 0x750..=0x77F),
+            Script::Hebrew => matches!(c as u32, 0x590..=0x5FF),
+            Script::Devanagari => matches!(c as u32, 0x900..=0x97F),
+            Script::Thai => matches!(c as u32, 0xE00..=0xE7F),
+            Script::Chinese => is_han,
+            Script::Japanese => is_han || matches!(c as u32, 0x3040..=0x30FF),
+            Script::Korean => {
+                is_han || matches!(c as u32, 0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF)
+            }
+        }
+    }
+}
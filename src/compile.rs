@@ -0,0 +1,350 @@
+use crate::error::PotrError;
+use crate::filter::MessageFilter;
+use crate::formats;
+use polib::{
+    catalog::Catalog,
+    message::{Message, MessageView},
+    metadata::CatalogMetadata,
+};
+
+const MO_MAGIC: u32 = 0x950412de;
+const MO_HEADER_SIZE: usize = 28;
+
+#[derive(Debug, Clone, Default)]
+pub struct CompileConfig {
+    pub po_file_path: String,
+    pub output_file_path: String,
+    pub include_fuzzy: bool,
+    pub filter: Option<MessageFilter>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompileStats {
+    pub compiled_count: usize,
+    pub fuzzy_skipped_count: usize,
+    pub untranslated_skipped_count: usize,
+    pub filtered_count: usize,
+}
+
+/// Compiles PO files into GNU MO files, the same way as `msgfmt` does.
+pub struct MoCompiler {
+    pub config: CompileConfig,
+}
+
+/// A single string pair in the MO file. Both strings are already encoded in MO format, e.g.
+/// "msgctxt\x04msgid\0msgid_plural" and "msgstr[0]\0msgstr[1]".
+struct MoEntry {
+    original: Vec<u8>,
+    translation: Vec<u8>,
+}
+
+impl MoCompiler {
+    pub fn new(config: CompileConfig) -> MoCompiler {
+        MoCompiler { config }
+    }
+
+    pub fn run(&self) -> Result<CompileStats, PotrError> {
+        tracing::info!("Loading po file: {}", self.config.po_file_path);
+        let po_file = formats::load_po_file(&self.config.po_file_path)?;
+
+        let (data, stats) = self.compile(&po_file);
+        tracing::info!(
+            "Compile completed! Compiled = {}, FuzzySkipped = {}, UntranslatedSkipped = {}, Filtered = {}",
+            stats.compiled_count,
+            stats.fuzzy_skipped_count,
+            stats.untranslated_skipped_count,
+            stats.filtered_count
+        );
+
+        tracing::info!("Write to output: {:?}", self.config.output_file_path);
+        std::fs::write(&self.config.output_file_path, data)?;
+        Ok(stats)
+    }
+
+    pub fn compile(&self, catalog: &Catalog) -> (Vec<u8>, CompileStats) {
+        let mut stats = CompileStats::default();
+        let mut entries = vec![MoEntry {
+            original: vec![],
+            translation: catalog.metadata.export_for_po().into_bytes(),
+        }];
+
+        for message in catalog.messages() {
+            if !message.is_translated() {
+                stats.untranslated_skipped_count += 1;
+                continue;
+            }
+
+            if message.is_fuzzy() && !self.config.include_fuzzy {
+                stats.fuzzy_skipped_count += 1;
+                continue;
+            }
+
+            if let Some(filter) = &self.config.filter {
+                if !filter.is_match(message) {
+                    stats.filtered_count += 1;
+                    continue;
+                }
+            }
+
+            stats.compiled_count += 1;
+            entries.push(MoEntry {
+                original: encode_original(message),
+                translation: encode_translation(message),
+            });
+        }
+
+        // Original strings must be sorted for binary search in the gettext runtime.
+        entries.sort_by(|a, b| a.original.cmp(&b.original));
+
+        (write_mo(&entries), stats)
+    }
+}
+
+fn encode_original(message: &dyn MessageView) -> Vec<u8> {
+    let mut original = Vec::new();
+    if !message.msgctxt().is_empty() {
+        original.extend_from_slice(message.msgctxt().as_bytes());
+        original.push(4);
+    }
+    original.extend_from_slice(message.msgid().as_bytes());
+    if let Ok(msgid_plural) = message.msgid_plural() {
+        original.push(0);
+        original.extend_from_slice(msgid_plural.as_bytes());
+    }
+    original
+}
+
+fn encode_translation(message: &dyn MessageView) -> Vec<u8> {
+    match (message.msgstr(), message.msgstr_plural()) {
+        (Ok(msgstr), _) => msgstr.as_bytes().to_vec(),
+        (_, Ok(msgstr_plural)) => msgstr_plural.join("\0").into_bytes(),
+        _ => vec![],
+    }
+}
+
+fn write_mo(entries: &[MoEntry]) -> Vec<u8> {
+    let count = entries.len();
+    let hash_size = hash_table_size(count);
+    let original_table_offset = MO_HEADER_SIZE;
+    let translation_table_offset = original_table_offset + 8 * count;
+    let hash_table_offset = translation_table_offset + 8 * count;
+    let strings_offset = hash_table_offset + 4 * hash_size;
+
+    let mut data = Vec::new();
+    for value in [
+        MO_MAGIC,
+        0,
+        count as u32,
+        original_table_offset as u32,
+        translation_table_offset as u32,
+        hash_size as u32,
+        hash_table_offset as u32,
+    ] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut original_table = Vec::new();
+    let mut translation_table = Vec::new();
+    let mut string_data = Vec::new();
+    for entry in entries {
+        append_string(
+            &mut original_table,
+            &mut string_data,
+            strings_offset,
+            &entry.original,
+        );
+    }
+    for entry in entries {
+        append_string(
+            &mut translation_table,
+            &mut string_data,
+            strings_offset,
+            &entry.translation,
+        );
+    }
+    data.extend_from_slice(&original_table);
+    data.extend_from_slice(&translation_table);
+
+    let mut hash_table = vec![0u32; hash_size];
+    for (index, entry) in entries.iter().enumerate() {
+        let hash = hash_string(hash_key(&entry.original));
+        let mut slot = hash as usize % hash_size;
+        let increment = 1 + hash as usize % (hash_size - 2);
+        while hash_table[slot] != 0 {
+            slot = (slot + increment) % hash_size;
+        }
+        hash_table[slot] = index as u32 + 1;
+    }
+    for value in hash_table {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    data.extend_from_slice(&string_data);
+    data
+}
+
+fn append_string(table: &mut Vec<u8>, string_data: &mut Vec<u8>, strings_offset: usize, s: &[u8]) {
+    table.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    table.extend_from_slice(&((strings_offset + string_data.len()) as u32).to_le_bytes());
+    string_data.extend_from_slice(s);
+    string_data.push(0);
+}
+
+/// Only the part before msgid_plural is hashed.
+fn hash_key(original: &[u8]) -> &[u8] {
+    match original.iter().position(|&b| b == 0) {
+        Some(end) => &original[..end],
+        None => original,
+    }
+}
+
+/// Hash table size is the next prime after 4/3 of the string count, which is the same as the
+/// classic msgfmt implementation.
+fn hash_table_size(count: usize) -> usize {
+    let mut size = (count * 4 / 3).max(3);
+    while !is_prime(size) {
+        size += 1;
+    }
+    size
+}
+
+fn is_prime(n: usize) -> bool {
+    n >= 2
+        && (2..)
+            .take_while(|d| d * d <= n)
+            .all(|d| !n.is_multiple_of(d))
+}
+
+/// The hashpjw function used by gettext.
+fn hash_string(s: &[u8]) -> u32 {
+    let mut hash: u32 = 0;
+    for &b in s {
+        hash = (hash << 4).wrapping_add(b as u32);
+        let g = hash & 0xf000_0000;
+        if g != 0 {
+            hash ^= g >> 24;
+            hash ^= g;
+        }
+    }
+    hash
+}
+
+/// Parse a MO file back to a catalog. Every message is also looked up through the hash table to
+/// make sure the file can be used by the gettext runtime.
+pub fn parse_mo(data: &[u8]) -> Result<Catalog, PotrError> {
+    let read_u32 = |offset: usize, little_endian: bool| -> Result<u32, PotrError> {
+        let bytes: [u8; 4] = data
+            .get(offset..offset + 4)
+            .ok_or_else(|| {
+                PotrError::parse(format!("Unexpected end of MO file at offset {}", offset))
+            })?
+            .try_into()
+            .unwrap();
+        Ok(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+
+    let little_endian = match read_u32(0, true)? {
+        MO_MAGIC => true,
+        magic if magic.swap_bytes() == MO_MAGIC => false,
+        magic => {
+            return Err(PotrError::parse(format!(
+                "Invalid MO file magic number: {:#x}",
+                magic
+            )))
+        }
+    };
+    let field = |index: usize| read_u32(4 * index, little_endian).map(|v| v as usize);
+    let (count, original_table_offset, translation_table_offset) =
+        (field(2)?, field(3)?, field(4)?);
+    let (hash_size, hash_table_offset) = (field(5)?, field(6)?);
+
+    let read_string = |table_offset: usize, index: usize| -> Result<&[u8], PotrError> {
+        let length = read_u32(table_offset + 8 * index, little_endian)? as usize;
+        let offset = read_u32(table_offset + 8 * index + 4, little_endian)? as usize;
+        data.get(offset..offset + length).ok_or_else(|| {
+            PotrError::parse(format!("Invalid string offset in MO file: {}", offset))
+        })
+    };
+
+    let mut catalog = Catalog::new(CatalogMetadata::new());
+    for index in 0..count {
+        let original = read_string(original_table_offset, index)?;
+        let translation = read_utf8(read_string(translation_table_offset, index)?)?;
+
+        if hash_size > 0 {
+            let hash = hash_string(hash_key(original));
+            let mut slot = hash as usize % hash_size;
+            let increment = 1 + hash as usize % (hash_size - 2);
+            loop {
+                match read_u32(hash_table_offset + 4 * slot, little_endian)? as usize {
+                    0 => {
+                        return Err(PotrError::parse(format!(
+                            "Message is not found in MO hash table: Index = {}",
+                            index
+                        )))
+                    }
+                    found if found == index + 1 => break,
+                    _ => slot = (slot + increment) % hash_size,
+                }
+            }
+        }
+
+        if original.is_empty() {
+            catalog.metadata =
+                CatalogMetadata::parse(&translation).map_err(|e| PotrError::Parse(Box::new(e)))?;
+            continue;
+        }
+
+        let original = read_utf8(original)?;
+        let (msgctxt, original) = match original.split_once('\u{0004}') {
+            Some((msgctxt, original)) => (msgctxt.to_string(), original.to_string()),
+            None => (String::new(), original),
+        };
+
+        let message = match original.split_once('\0') {
+            Some((msgid, msgid_plural)) => Message::build_plural()
+                .with_msgctxt(msgctxt)
+                .with_msgid(msgid.to_string())
+                .with_msgid_plural(msgid_plural.to_string())
+                .with_msgstr_plural(translation.split('\0').map(|s| s.to_string()).collect())
+                .done(),
+            None => Message::build_singular()
+                .with_msgctxt(msgctxt)
+                .with_msgid(original)
+                .with_msgstr(translation)
+                .done(),
+        };
+        catalog.append_or_update(message);
+    }
+
+    Ok(catalog)
+}
+
+fn read_utf8(bytes: &[u8]) -> Result<String, PotrError> {
+    String::from_utf8(bytes.to_vec()).map_err(|e| PotrError::Parse(Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn hash_string_should_match_hashpjw() {
+        assert_eq!(hash_string(b""), 0);
+        assert_eq!(hash_string(b"a"), 0x61);
+        assert_eq!(hash_string(b"ab"), 0x672);
+        assert_eq!(hash_string(b"abcdefghi"), 0x9abaa69);
+    }
+
+    #[test]
+    fn hash_table_size_should_be_prime() {
+        assert_eq!(hash_table_size(0), 3);
+        assert_eq!(hash_table_size(3), 5);
+        assert_eq!(hash_table_size(10), 13);
+    }
+}
@@ -0,0 +1,690 @@
+use crate::error::PotrError;
+use crate::{
+    Budget, CatalogFormat, Language, MessageFilter, PostProcessConfig, PotrConfig, Price,
+    PriceTable, QualityConfig, ReplaceRule, ReviewConfig, SpreadsheetConfig, TranslationRule,
+    TranslatorConfig, TranslatorEngine, TuiConfig, ValidationConfig, XliffConfig,
+};
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Default name of the project configuration file.
+pub const CONFIG_FILE_NAME: &str = "potr.toml";
+
+/// Project configuration file (potr.toml), which holds the default settings and named profiles.
+///
+/// ```toml
+/// [defaults]
+/// engine = "openai"
+/// model = "gpt-4"
+///
+/// [profiles.docs-de]
+/// po = "po/de.po"
+/// target_lang = "de"
+/// source = "src/guide"
+///
+/// [profiles.docs-de.extra_params]
+/// temperature = "0.2"
+///
+/// [[profiles.docs-de.rules]]
+/// source = "src/api/**"
+/// engine = "deepl"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub defaults: ProfileConfig,
+
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileConfig>,
+}
+
+/// Settings of a single profile. All settings are optional, so profiles, config file defaults and
+/// command line options can be layered on top of each other.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    #[serde(rename = "po")]
+    pub po_file_path: Option<String>,
+
+    #[serde(rename = "output")]
+    pub output_file_path: Option<String>,
+
+    /// Format of the files, e.g. "json". By default, it is decided by the file extension.
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    pub format: Option<CatalogFormat>,
+
+    /// JSON file to write the run report to.
+    #[serde(rename = "report")]
+    pub report_file_path: Option<String>,
+
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    pub target_lang: Option<Language>,
+
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    pub engine: Option<TranslatorEngine>,
+
+    pub model: Option<String>,
+    pub api_base: Option<String>,
+    pub api_version: Option<String>,
+    pub api_deployment_id: Option<String>,
+    pub skip_translation: Option<bool>,
+    pub process_translated: Option<bool>,
+    pub process_code_blocks: Option<bool>,
+    pub process_fuzzy_only: Option<bool>,
+    pub skip_text: Option<bool>,
+    pub limit: Option<i32>,
+    pub source: Option<String>,
+    pub include: Option<String>,
+    pub exclude: Option<String>,
+
+    /// Filter expression, see `MessageFilter`.
+    pub filter: Option<String>,
+
+    #[serde(rename = "fuzzy")]
+    pub as_fuzzy: Option<bool>,
+
+    #[serde(default)]
+    pub extra_params: HashMap<String, String>,
+
+    pub prompt: Option<String>,
+
+    #[serde(default)]
+    pub glossary: BTreeMap<String, String>,
+
+    /// Per message rules. Rules in a profile are evaluated before the ones in defaults.
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+
+    /// Prices used by cost estimation and budget, keyed by model or engine name.
+    #[serde(default)]
+    pub prices: HashMap<String, Price>,
+
+    /// Spending limits of a translation run.
+    pub budget: Option<Budget>,
+
+    /// Post-processing of the translator output, see `PostProcessConfig`.
+    pub normalize_whitespace: Option<bool>,
+    pub strip_wrappers: Option<bool>,
+
+    /// Regex replacements applied to the translations. Replacements in defaults are applied
+    /// before the ones in a profile.
+    #[serde(default)]
+    pub replace: Vec<ReplaceRuleConfig>,
+
+    /// Validation of the translator output, see `ValidationConfig`.
+    pub validate: Option<bool>,
+    pub validation_retries: Option<u32>,
+
+    /// Ask OpenAI models to reply in JSON, and write their notes into the comments.
+    pub json_output: Option<bool>,
+    pub store_notes: Option<bool>,
+
+    /// Quality estimation by back-translation.
+    pub back_translation: Option<BackTranslationConfig>,
+}
+
+/// Settings of a translation rule, see `TranslationRule`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    /// Source file glob, e.g. "src/api/**".
+    pub source: Option<String>,
+
+    /// Regex to match msgctxt.
+    pub msgctxt: Option<String>,
+
+    /// Filter expression, see `MessageFilter`.
+    pub filter: Option<String>,
+
+    #[serde(default)]
+    pub skip: bool,
+
+    pub process_translated: Option<bool>,
+    pub process_code_blocks: Option<bool>,
+    pub skip_text: Option<bool>,
+
+    #[serde(rename = "fuzzy")]
+    pub as_fuzzy: Option<bool>,
+
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    pub engine: Option<TranslatorEngine>,
+
+    pub model: Option<String>,
+    pub prompt: Option<String>,
+
+    #[serde(default)]
+    pub glossary: BTreeMap<String, String>,
+}
+
+/// Settings of a replacement rule, see `ReplaceRule`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplaceRuleConfig {
+    pub pattern: String,
+    pub replacement: String,
+
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    pub target_lang: Option<Language>,
+}
+
+/// Settings of the quality estimation, see `QualityConfig`. Having this section enables it,
+/// unless `enabled` is false.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackTranslationConfig {
+    pub enabled: Option<bool>,
+
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    pub engine: Option<TranslatorEngine>,
+
+    pub model: Option<String>,
+
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    pub source_lang: Option<Language>,
+
+    pub threshold: Option<f64>,
+}
+
+impl BackTranslationConfig {
+    /// Merge 2 configs. Settings in self take precedence, and the rest are taken from `base`.
+    pub fn or(self, base: BackTranslationConfig) -> BackTranslationConfig {
+        BackTranslationConfig {
+            enabled: self.enabled.or(base.enabled),
+            engine: self.engine.or(base.engine),
+            model: self.model.or(base.model),
+            source_lang: self.source_lang.or(base.source_lang),
+            threshold: self.threshold.or(base.threshold),
+        }
+    }
+
+    pub fn to_quality_config(&self) -> QualityConfig {
+        let default_config = QualityConfig::default();
+        QualityConfig {
+            enabled: self.enabled.unwrap_or(true),
+            engine: self.engine,
+            api_key: None,
+            model: self.model.clone(),
+            source_lang: self.source_lang.unwrap_or(default_config.source_lang),
+            threshold: self.threshold.unwrap_or(default_config.threshold),
+        }
+    }
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<ConfigFile, PotrError> {
+        tracing::info!("Loading config file: {}", path.display());
+        let content = std::fs::read_to_string(path)?;
+        let mut config_file = Self::parse(&content).map_err(|e| {
+            tracing::error!("Failed to parse config file {}: {}", path.display(), e);
+            e
+        })?;
+
+        // Files in config file are relative to the directory of the config file, so the same
+        // profile works no matter which sub directory potr is launched from.
+        if let Some(base_dir) = path.parent() {
+            config_file.defaults.resolve_paths(base_dir);
+            for profile in config_file.profiles.values_mut() {
+                profile.resolve_paths(base_dir);
+            }
+        }
+
+        Ok(config_file)
+    }
+
+    pub fn parse(content: &str) -> Result<ConfigFile, PotrError> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Find the config file from the given directory upward to the root directory.
+    pub fn discover(start_dir: &Path) -> Option<PathBuf> {
+        start_dir
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Get the settings of a profile, with the defaults applied. If no profile is specified, only
+    /// the defaults are returned.
+    pub fn profile(&self, name: Option<&str>) -> Result<ProfileConfig, PotrError> {
+        match name {
+            Some(name) => match self.profiles.get(name) {
+                Some(profile) => Ok(profile.clone().or(self.defaults.clone())),
+                None => Err(PotrError::config(format!(
+                    "Profile is not found in config file: {}",
+                    name
+                ))),
+            },
+            None => Ok(self.defaults.clone()),
+        }
+    }
+}
+
+impl ProfileConfig {
+    /// Merge 2 profiles. Settings in self take precedence, and the rest are taken from `base`.
+    pub fn or(self, base: ProfileConfig) -> ProfileConfig {
+        let mut extra_params = base.extra_params;
+        extra_params.extend(self.extra_params);
+
+        let mut glossary = base.glossary;
+        glossary.extend(self.glossary);
+
+        let mut rules = self.rules;
+        rules.extend(base.rules);
+
+        let mut prices = base.prices;
+        prices.extend(self.prices);
+
+        let mut replace = base.replace;
+        replace.extend(self.replace);
+
+        ProfileConfig {
+            po_file_path: self.po_file_path.or(base.po_file_path),
+            output_file_path: self.output_file_path.or(base.output_file_path),
+            format: self.format.or(base.format),
+            report_file_path: self.report_file_path.or(base.report_file_path),
+            target_lang: self.target_lang.or(base.target_lang),
+            engine: self.engine.or(base.engine),
+            model: self.model.or(base.model),
+            api_base: self.api_base.or(base.api_base),
+            api_version: self.api_version.or(base.api_version),
+            api_deployment_id: self.api_deployment_id.or(base.api_deployment_id),
+            skip_translation: self.skip_translation.or(base.skip_translation),
+            process_translated: self.process_translated.or(base.process_translated),
+            process_code_blocks: self.process_code_blocks.or(base.process_code_blocks),
+            process_fuzzy_only: self.process_fuzzy_only.or(base.process_fuzzy_only),
+            skip_text: self.skip_text.or(base.skip_text),
+            limit: self.limit.or(base.limit),
+            source: self.source.or(base.source),
+            include: self.include.or(base.include),
+            exclude: self.exclude.or(base.exclude),
+            filter: self.filter.or(base.filter),
+            as_fuzzy: self.as_fuzzy.or(base.as_fuzzy),
+            extra_params,
+            prompt: self.prompt.or(base.prompt),
+            glossary,
+            rules,
+            prices,
+            budget: match (self.budget, base.budget) {
+                (Some(budget), Some(base)) => Some(budget.or(base)),
+                (budget, base) => budget.or(base),
+            },
+            normalize_whitespace: self.normalize_whitespace.or(base.normalize_whitespace),
+            strip_wrappers: self.strip_wrappers.or(base.strip_wrappers),
+            replace,
+            validate: self.validate.or(base.validate),
+            validation_retries: self.validation_retries.or(base.validation_retries),
+            json_output: self.json_output.or(base.json_output),
+            store_notes: self.store_notes.or(base.store_notes),
+            back_translation: match (self.back_translation, base.back_translation) {
+                (Some(config), Some(base)) => Some(config.or(base)),
+                (config, base) => config.or(base),
+            },
+        }
+    }
+
+    fn resolve_paths(&mut self, base_dir: &Path) {
+        for path in [
+            &mut self.po_file_path,
+            &mut self.output_file_path,
+            &mut self.report_file_path,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = base_dir.join(&path).to_string_lossy().to_string();
+        }
+    }
+
+    pub fn to_potr_config(&self) -> Result<PotrConfig, PotrError> {
+        let po_file_path = match &self.po_file_path {
+            Some(path) => path.clone(),
+            None => {
+                return Err(PotrError::config(format!(
+                "PO file is not specified, please specify it via \"-p\" option or \"po\" in {}.",
+                CONFIG_FILE_NAME
+            )))
+            }
+        };
+
+        let default_config = PotrConfig::default();
+        Ok(PotrConfig {
+            output_file_path: self
+                .output_file_path
+                .clone()
+                .unwrap_or_else(|| po_file_path.clone()),
+            po_file_path,
+            format: self.format,
+            skip_translation: self
+                .skip_translation
+                .unwrap_or(default_config.skip_translation),
+            skip_translated: self
+                .process_translated
+                .map(|v| !v)
+                .unwrap_or(default_config.skip_translated),
+            skip_code_blocks: self
+                .process_code_blocks
+                .map(|v| !v)
+                .unwrap_or(default_config.skip_code_blocks),
+            skip_text: self.skip_text.unwrap_or(default_config.skip_text),
+            skip_non_fuzzy: self
+                .process_fuzzy_only
+                .unwrap_or(default_config.skip_non_fuzzy),
+            source_regex: self.source.as_deref().map(Regex::new).transpose()?,
+            include_message_regex: self.include.as_deref().map(Regex::new).transpose()?,
+            exclude_message_regex: self.exclude.as_deref().map(Regex::new).transpose()?,
+            message_limit: self.limit.unwrap_or(default_config.message_limit),
+            as_fuzzy: self.as_fuzzy.unwrap_or(default_config.as_fuzzy),
+            filter: self
+                .filter
+                .as_deref()
+                .map(MessageFilter::parse)
+                .transpose()?,
+            rules: self
+                .rules
+                .iter()
+                .map(|rule| rule.to_translation_rule())
+                .collect::<Result<_, PotrError>>()?,
+            budget: self.budget.unwrap_or_default(),
+            prices: PriceTable::default().with_prices(self.prices.clone()),
+            report_file_path: self.report_file_path.clone(),
+            post_process: self.to_post_process_config()?,
+            validation: ValidationConfig {
+                enabled: self.validate.unwrap_or(default_config.validation.enabled),
+                max_retries: self
+                    .validation_retries
+                    .unwrap_or(default_config.validation.max_retries),
+            },
+            store_notes: self.store_notes.unwrap_or(default_config.store_notes),
+            quality: self
+                .back_translation
+                .as_ref()
+                .map(|config| config.to_quality_config())
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn to_review_config(&self) -> Result<ReviewConfig, PotrError> {
+        let potr_config = self.to_potr_config()?;
+        Ok(ReviewConfig {
+            po_file_path: potr_config.po_file_path,
+            output_file_path: potr_config.output_file_path,
+            review_file_path: None,
+            filter: potr_config.filter,
+            message_limit: potr_config.message_limit.max(0) as usize,
+        })
+    }
+
+    pub fn to_tui_config(&self) -> Result<TuiConfig, PotrError> {
+        let potr_config = self.to_potr_config()?;
+        Ok(TuiConfig {
+            po_file_path: potr_config.po_file_path,
+            output_file_path: potr_config.output_file_path,
+            filter: potr_config.filter,
+            fuzzy_only: potr_config.skip_non_fuzzy,
+        })
+    }
+
+    pub fn to_xliff_config(&self) -> Result<XliffConfig, PotrError> {
+        let potr_config = self.to_potr_config()?;
+        Ok(XliffConfig {
+            po_file_path: potr_config.po_file_path,
+            output_file_path: potr_config.output_file_path,
+            target_lang: self.target_lang.unwrap_or(Language::English),
+            ..Default::default()
+        })
+    }
+
+    pub fn to_spreadsheet_config(&self) -> Result<SpreadsheetConfig, PotrError> {
+        let potr_config = self.to_potr_config()?;
+        Ok(SpreadsheetConfig {
+            po_file_path: potr_config.po_file_path,
+            output_file_path: potr_config.output_file_path,
+            ..Default::default()
+        })
+    }
+
+    pub fn to_post_process_config(&self) -> Result<PostProcessConfig, PotrError> {
+        let default_config = PostProcessConfig::default();
+        Ok(PostProcessConfig {
+            normalize_whitespace: self
+                .normalize_whitespace
+                .unwrap_or(default_config.normalize_whitespace),
+            strip_wrappers: self.strip_wrappers.unwrap_or(default_config.strip_wrappers),
+            replace_rules: self
+                .replace
+                .iter()
+                .map(|rule| {
+                    Ok(ReplaceRule {
+                        pattern: Regex::new(&rule.pattern)?,
+                        replacement: rule.replacement.clone(),
+                        target_lang: rule.target_lang,
+                    })
+                })
+                .collect::<Result<_, PotrError>>()?,
+        })
+    }
+
+    pub fn to_translator_config(&self, api_key: String) -> TranslatorConfig {
+        let default_config = TranslatorConfig::default();
+        TranslatorConfig {
+            engine: self.engine.unwrap_or(default_config.engine),
+            target_lang: self.target_lang.unwrap_or(default_config.target_lang),
+            api_key,
+            model: self.model.clone(),
+            api_base: self.api_base.clone(),
+            api_version: self.api_version.clone(),
+            api_deployment_id: self.api_deployment_id.clone(),
+            extra_params: self.extra_params.clone(),
+            prompt: self.prompt.clone(),
+            glossary: self.glossary.clone(),
+            json_output: self.json_output.unwrap_or(default_config.json_output),
+        }
+    }
+}
+
+impl RuleConfig {
+    pub fn to_translation_rule(&self) -> Result<TranslationRule, PotrError> {
+        Ok(TranslationRule {
+            source: self
+                .source
+                .as_deref()
+                .map(TranslationRule::source_from_glob)
+                .transpose()?,
+            msgctxt: self.msgctxt.as_deref().map(Regex::new).transpose()?,
+            filter: self
+                .filter
+                .as_deref()
+                .map(MessageFilter::parse)
+                .transpose()?,
+            skip: self.skip,
+            skip_translated: self.process_translated.map(|v| !v),
+            skip_code_blocks: self.process_code_blocks.map(|v| !v),
+            skip_text: self.skip_text,
+            as_fuzzy: self.as_fuzzy,
+            engine: self.engine,
+            api_key: None,
+            model: self.model.clone(),
+            prompt: self.prompt.clone(),
+            glossary: self.glossary.clone(),
+        })
+    }
+}
+
+fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| T::from_str(&s).map_err(de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const CONFIG: &str = r#"
+[defaults]
+engine = "openai"
+model = "gpt-4"
+include = "^That"
+
+[defaults.extra_params]
+temperature = "0.2"
+
+[profiles.docs-de]
+po = "po/de.po"
+target_lang = "de"
+source = "src/guide"
+fuzzy = true
+
+[profiles.docs-de.extra_params]
+top_p = "0.9"
+
+[[profiles.docs-de.rules]]
+source = "src/api/**"
+engine = "deepl"
+fuzzy = false
+
+[[profiles.docs-de.rules]]
+msgctxt = "^button$"
+filter = "!translated"
+skip = true
+
+[[defaults.rules]]
+source = "src/*.md"
+process_code_blocks = true
+
+[[defaults.replace]]
+pattern = "\\s+([，。！？])"
+replacement = "$1"
+target_lang = "zh"
+
+[profiles.ui-ja]
+po = "po/ja.po"
+target_lang = "ja"
+engine = "deepl"
+strip_wrappers = false
+
+[[profiles.ui-ja.replace]]
+pattern = "！"
+replacement = "!"
+"#;
+
+    #[test]
+    fn profile_should_be_merged_with_defaults() {
+        let config_file = ConfigFile::parse(CONFIG).unwrap();
+        let profile = config_file.profile(Some("docs-de")).unwrap();
+
+        let potr_config = profile.to_potr_config().unwrap();
+        assert_eq!(potr_config.po_file_path, "po/de.po");
+        assert_eq!(potr_config.output_file_path, "po/de.po");
+        assert_eq!(potr_config.source_regex.unwrap().as_str(), "src/guide");
+        assert_eq!(potr_config.include_message_regex.unwrap().as_str(), "^That");
+        assert!(potr_config.as_fuzzy);
+        assert!(potr_config.skip_translated);
+
+        let translator_config = profile.to_translator_config(String::new());
+        assert_eq!(translator_config.engine, TranslatorEngine::OpenAI);
+        assert_eq!(translator_config.target_lang, Language::German);
+        assert_eq!(translator_config.model.as_deref(), Some("gpt-4"));
+        assert_eq!(translator_config.extra_params.len(), 2);
+    }
+
+    #[test]
+    fn profile_settings_should_override_defaults() {
+        let config_file = ConfigFile::parse(CONFIG).unwrap();
+        let profile = config_file.profile(Some("ui-ja")).unwrap();
+
+        let translator_config = profile.to_translator_config(String::new());
+        assert_eq!(translator_config.engine, TranslatorEngine::DeepL);
+        assert_eq!(translator_config.target_lang, Language::Japanese);
+    }
+
+    #[test]
+    fn command_line_settings_should_override_profile() {
+        let config_file = ConfigFile::parse(CONFIG).unwrap();
+        let cli = ProfileConfig {
+            target_lang: Some(Language::French),
+            ..Default::default()
+        };
+        let profile = cli.or(config_file.profile(Some("docs-de")).unwrap());
+
+        let translator_config = profile.to_translator_config(String::new());
+        assert_eq!(translator_config.target_lang, Language::French);
+        assert_eq!(translator_config.model.as_deref(), Some("gpt-4"));
+    }
+
+    #[test]
+    fn profile_rules_should_be_evaluated_before_defaults() {
+        let config_file = ConfigFile::parse(CONFIG).unwrap();
+        let potr_config = config_file
+            .profile(Some("docs-de"))
+            .unwrap()
+            .to_potr_config()
+            .unwrap();
+
+        assert_eq!(potr_config.rules.len(), 3);
+        assert_eq!(
+            potr_config.rules[0].source.as_ref().unwrap().as_str(),
+            "^src/api/.*$"
+        );
+        assert_eq!(potr_config.rules[0].engine, Some(TranslatorEngine::DeepL));
+        assert_eq!(potr_config.rules[0].as_fuzzy, Some(false));
+        assert_eq!(
+            potr_config.rules[1].msgctxt.as_ref().unwrap().as_str(),
+            "^button$"
+        );
+        assert_eq!(
+            potr_config.rules[1].filter.as_ref().unwrap().to_string(),
+            "!translated"
+        );
+        assert!(potr_config.rules[1].skip);
+        assert_eq!(potr_config.rules[2].skip_code_blocks, Some(false));
+    }
+
+    #[test]
+    fn replace_rules_in_defaults_should_be_applied_first() {
+        let config_file = ConfigFile::parse(CONFIG).unwrap();
+        let post_process = config_file
+            .profile(Some("ui-ja"))
+            .unwrap()
+            .to_post_process_config()
+            .unwrap();
+
+        assert!(post_process.normalize_whitespace);
+        assert!(!post_process.strip_wrappers);
+        assert_eq!(post_process.replace_rules.len(), 2);
+        assert_eq!(
+            post_process.replace_rules[0].pattern.as_str(),
+            "\\s+([，。！？])"
+        );
+        assert_eq!(
+            post_process.replace_rules[0].target_lang,
+            Some(Language::Chinese)
+        );
+        assert_eq!(post_process.replace_rules[1].target_lang, None);
+    }
+
+    #[test]
+    fn unknown_profile_should_fail() {
+        let config_file = ConfigFile::parse(CONFIG).unwrap();
+        assert!(config_file.profile(Some("not-exist")).is_err());
+    }
+
+    #[test]
+    fn unknown_setting_should_fail() {
+        assert!(ConfigFile::parse("[defaults]\nengin = \"openai\"\n").is_err());
+        assert!(ConfigFile::parse("[defaults]\nengine = \"unknown\"\n").is_err());
+    }
+}
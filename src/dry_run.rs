@@ -0,0 +1,388 @@
+use crate::{
+    budget::{BudgetTracker, Usage},
+    error::PotrError,
+    potr::{Potr, SkipReason},
+    translators::{self, TranslatorConfig, TranslatorEngine},
+};
+use anyhow::Result;
+use serde::Deserialize;
+use std::{collections::HashMap, fmt};
+
+/// Tokens used by the chat message framing, e.g. role and separators, for each request.
+const TOKENS_PER_REQUEST: usize = 12;
+
+/// Price of a translation engine or model, in USD.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Price {
+    /// Price per 1K input tokens.
+    #[serde(default)]
+    pub input: f64,
+
+    /// Price per 1K output tokens.
+    #[serde(default)]
+    pub output: f64,
+
+    /// Price per 1M characters.
+    #[serde(default)]
+    pub characters: f64,
+}
+
+/// Prices keyed by model name for LLM based engines, e.g. "gpt-4", or by engine name for the
+/// others, e.g. "deepl".
+#[derive(Debug, Clone)]
+pub struct PriceTable {
+    pub prices: HashMap<String, Price>,
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        let llm = |input, output| Price {
+            input,
+            output,
+            characters: 0.0,
+        };
+
+        let prices = HashMap::from([
+            ("gpt-3.5-turbo".to_string(), llm(0.0015, 0.002)),
+            ("gpt-3.5-turbo-16k".to_string(), llm(0.003, 0.004)),
+            ("gpt-4".to_string(), llm(0.03, 0.06)),
+            ("gpt-4-32k".to_string(), llm(0.06, 0.12)),
+            (
+                "deepl".to_string(),
+                Price {
+                    characters: 25.0,
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        PriceTable { prices }
+    }
+}
+
+impl Price {
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        self.input * usage.prompt_tokens as f64 / 1000.0
+            + self.output * usage.completion_tokens as f64 / 1000.0
+            + self.characters * usage.characters as f64 / 1_000_000.0
+    }
+}
+
+impl PriceTable {
+    /// Add or override prices, e.g. the ones from config file.
+    pub fn with_prices(mut self, prices: HashMap<String, Price>) -> PriceTable {
+        self.prices.extend(prices);
+        self
+    }
+
+    pub fn get(&self, engine: TranslatorEngine, model: Option<&str>) -> Option<Price> {
+        match engine {
+            // Local engines are free.
+            TranslatorEngine::Clear | TranslatorEngine::Clone | TranslatorEngine::Mock => {
+                Some(Price::default())
+            }
+            TranslatorEngine::DeepL => self.prices.get("deepl").copied(),
+            TranslatorEngine::OpenAI | TranslatorEngine::AzureOpenAI => self
+                .prices
+                .get(model.unwrap_or(translators::DEFAULT_MODEL))
+                .copied(),
+            TranslatorEngine::Custom(name) => self.prices.get(name).copied(),
+        }
+    }
+
+    /// Cost of the usage with the engine and model in translator config. `None` if the price is
+    /// unknown.
+    pub fn cost(&self, config: &TranslatorConfig, usage: &Usage) -> Option<f64> {
+        self.get(config.engine, config.model.as_deref())
+            .map(|price| price.cost(usage))
+    }
+}
+
+/// Estimated usage of a single engine and model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageEstimate {
+    pub engine: TranslatorEngine,
+    pub model: Option<String>,
+    pub message_count: usize,
+    pub usage: Usage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedMessage {
+    pub msgid: String,
+    pub source: String,
+    pub engine: TranslatorEngine,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedMessage {
+    pub msgid: String,
+    pub source: String,
+    pub reason: SkipReason,
+}
+
+/// Result of a dry run: the messages that would be translated, and the estimated cost.
+#[derive(Debug, Clone, Default)]
+pub struct DryRunReport {
+    pub selected: Vec<SelectedMessage>,
+    pub skipped: Vec<SkippedMessage>,
+    pub estimates: Vec<UsageEstimate>,
+}
+
+impl DryRunReport {
+    pub fn total_cost(&self) -> Option<f64> {
+        self.estimates.iter().map(|e| e.usage.cost).sum()
+    }
+}
+
+impl Potr {
+    /// Run the message selection without translating anything. No translator is created and the
+    /// output file is not written.
+    pub fn dry_run(&self) -> Result<DryRunReport, PotrError> {
+        let po_file = self.load_catalog()?;
+
+        let mut report = DryRunReport::default();
+        let mut budget = BudgetTracker::new(self.config.budget);
+        let mut budget_exceeded = false;
+        let back_config = self
+            .config
+            .quality
+            .to_translator_config(&self.translator_config);
+        for message in po_file.messages() {
+            let rule_index = self.find_rule(message);
+            let rule = rule_index.map(|index| &self.config.rules[index]);
+            let translator_config = rule
+                .and_then(|r| r.to_translator_config(&self.translator_config))
+                .unwrap_or_else(|| self.translator_config.clone());
+            let mut usage =
+                estimate_usage(&translator_config, message.msgid(), &self.config.prices);
+
+            let limit_reached = self.config.message_limit > 0
+                && report.selected.len() >= self.config.message_limit as usize;
+            let skip_reason = match self.skip_reason(message, rule_index) {
+                None if limit_reached => Some(SkipReason::Limit),
+                None if budget_exceeded || budget.check(&usage).is_err() => {
+                    budget_exceeded = true;
+                    Some(SkipReason::Budget)
+                }
+                reason => reason,
+            };
+
+            if let Some(reason) = skip_reason {
+                report.skipped.push(SkippedMessage {
+                    msgid: message.msgid().to_string(),
+                    source: message.source().to_string(),
+                    reason,
+                });
+                continue;
+            }
+
+            let model = model_name(&translator_config);
+            report.selected.push(SelectedMessage {
+                msgid: message.msgid().to_string(),
+                source: message.source().to_string(),
+                engine: translator_config.engine,
+                model: model.clone(),
+            });
+
+            // Same as the run, the back-translation is counted in the usage of the message. The
+            // translation is assumed to be about the same length as msgid. Retries of the
+            // translations rejected by the validation can't be predicted, so they are not counted.
+            if self
+                .config
+                .quality
+                .should_estimate(translator_config.engine)
+            {
+                usage += estimate_usage(&back_config, message.msgid(), &self.config.prices);
+            }
+
+            budget.add(usage);
+            match report
+                .estimates
+                .iter_mut()
+                .find(|e| e.engine == translator_config.engine && e.model == model)
+            {
+                Some(estimate) => {
+                    estimate.message_count += 1;
+                    estimate.usage += usage;
+                }
+                None => report.estimates.push(UsageEstimate {
+                    engine: translator_config.engine,
+                    model,
+                    message_count: 1,
+                    usage,
+                }),
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Model used by LLM based engines. Other engines don't have models.
+pub(crate) fn model_name(config: &TranslatorConfig) -> Option<String> {
+    match config.engine {
+        TranslatorEngine::OpenAI | TranslatorEngine::AzureOpenAI => Some(
+            config
+                .model
+                .clone()
+                .unwrap_or_else(|| translators::DEFAULT_MODEL.to_string()),
+        ),
+        _ => None,
+    }
+}
+
+/// Estimate the usage of translating the text with the engine in translator config.
+pub(crate) fn estimate_usage(config: &TranslatorConfig, text: &str, prices: &PriceTable) -> Usage {
+    let mut usage = Usage {
+        characters: text.chars().count(),
+        ..Default::default()
+    };
+
+    if model_name(config).is_some() {
+        let text_tokens = estimate_tokens(text);
+        usage.prompt_tokens = estimate_tokens(&translators::system_prompt(config))
+            + estimate_tokens(translators::TEXT_REQUEST_MESSAGE)
+            + text_tokens
+            + TOKENS_PER_REQUEST;
+
+        // Translation is assumed to be about the same length as the source text.
+        usage.completion_tokens = text_tokens;
+    }
+
+    usage.cost = prices.cost(config, &usage);
+    usage
+}
+
+/// Rough token count for GPT models: about 4 characters per token for latin text, and about 1
+/// token per character for the others, e.g. CJK.
+pub fn estimate_tokens(text: &str) -> usize {
+    let ascii_count = text.chars().filter(|c| c.is_ascii()).count();
+    let other_count = text.chars().count() - ascii_count;
+
+    ascii_count.div_ceil(4) + other_count
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Selected messages: {}", self.selected.len())?;
+        for message in &self.selected {
+            let engine = match &message.model {
+                Some(model) => format!("{}/{}", message.engine, model),
+                None => message.engine.to_string(),
+            };
+            writeln!(
+                f,
+                "  [{}] {}: {}",
+                engine,
+                message.source,
+                preview(&message.msgid)
+            )?;
+        }
+
+        writeln!(f, "Skipped messages: {}", self.skipped.len())?;
+        for message in &self.skipped {
+            writeln!(
+                f,
+                "  [{}] {}: {}",
+                message.reason,
+                message.source,
+                preview(&message.msgid)
+            )?;
+        }
+
+        writeln!(f, "Estimated usage:")?;
+        for estimate in &self.estimates {
+            writeln!(
+                f,
+                "  {}{}: Messages = {}, Characters = {}, PromptTokens = {}, CompletionTokens = {}, Cost = {}",
+                estimate.engine,
+                estimate
+                    .model
+                    .as_ref()
+                    .map(|m| format!("/{}", m))
+                    .unwrap_or_default(),
+                estimate.message_count,
+                estimate.usage.characters,
+                estimate.usage.prompt_tokens,
+                estimate.usage.completion_tokens,
+                format_cost(estimate.usage.cost)
+            )?;
+        }
+        writeln!(
+            f,
+            "Estimated total cost: {}",
+            format_cost(self.total_cost())
+        )
+    }
+}
+
+fn format_cost(cost: Option<f64>) -> String {
+    match cost {
+        Some(cost) => format!("${:.4}", cost),
+        None => "unknown (price not found)".to_string(),
+    }
+}
+
+/// First line of the message, truncated for display.
+pub(crate) fn preview(msgid: &str) -> String {
+    const MAX_CHARS: usize = 60;
+    let line = msgid.lines().next().unwrap_or_default();
+    if line.chars().count() > MAX_CHARS || msgid.contains('\n') {
+        format!("{}...", line.chars().take(MAX_CHARS).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn tokens_should_be_estimated_by_characters() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("This is a pen!"), 4);
+        assert_eq!(estimate_tokens("这是一支笔"), 5);
+    }
+
+    #[test]
+    fn price_should_be_found_by_model_or_engine() {
+        let prices = PriceTable::default().with_prices(HashMap::from([(
+            "my-model".to_string(),
+            Price {
+                input: 1.0,
+                ..Default::default()
+            },
+        )]));
+
+        assert_eq!(
+            prices.get(TranslatorEngine::OpenAI, None).unwrap().input,
+            0.0015
+        );
+        assert_eq!(
+            prices
+                .get(TranslatorEngine::AzureOpenAI, Some("my-model"))
+                .unwrap()
+                .input,
+            1.0
+        );
+        assert_eq!(
+            prices
+                .get(TranslatorEngine::DeepL, None)
+                .unwrap()
+                .characters,
+            25.0
+        );
+        assert_eq!(
+            prices.get(TranslatorEngine::Clone, None),
+            Some(Price::default())
+        );
+        assert!(prices
+            .get(TranslatorEngine::OpenAI, Some("unknown"))
+            .is_none());
+    }
+}
@@ -0,0 +1,189 @@
+use crate::translators::TranslateError;
+use std::fmt;
+
+/// Source error of `PotrError`, e.g. the error of the parser.
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Errors of `Potr` and the other tools of the crate, grouped by what the caller can do about
+/// them.
+///
+/// The original errors are kept as the sources, so they can be downcast for the details. The
+/// other errors are kept in `Other`, with their original error chain.
+#[derive(Debug)]
+pub enum PotrError {
+    /// Error of the translation engine, which stopped the run.
+    Translate(TranslateError),
+
+    /// File can't be read or written.
+    Io(std::io::Error),
+
+    /// File can't be parsed, e.g. a malformed PO or JSON file.
+    Parse(BoxedError),
+
+    /// Invalid config file or settings, e.g. a bad regex.
+    Config(BoxedError),
+
+    /// Completion of the PO file is below the threshold of `potr stats`.
+    BelowThreshold { completion: f64, threshold: f64 },
+
+    /// Other errors, e.g. issues found by `potr check`.
+    Other(BoxedError),
+}
+
+impl PotrError {
+    /// Invalid config with the message, e.g. an unknown profile.
+    pub fn config(message: impl Into<String>) -> PotrError {
+        PotrError::Config(message.into().into())
+    }
+
+    /// Parse error with the message, e.g. a malformed line.
+    pub fn parse(message: impl Into<String>) -> PotrError {
+        PotrError::Parse(message.into().into())
+    }
+}
+
+impl fmt::Display for PotrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PotrError::Translate(e) => write!(f, "{}", e),
+            PotrError::Io(e) => write!(f, "IO error: {}", e),
+            PotrError::Parse(e) => write!(f, "Parse error: {}", e),
+            PotrError::Config(e) => write!(f, "Invalid config: {}", e),
+            PotrError::BelowThreshold {
+                completion,
+                threshold,
+            } => write!(
+                f,
+                "Completion {:.2}% is below the threshold {:.2}%.",
+                completion, threshold
+            ),
+            PotrError::Other(e) => {
+                write!(f, "{}", e)?;
+                let mut source = std::error::Error::source(e.as_ref());
+                while let Some(e) = source {
+                    write!(f, ": {}", e)?;
+                    source = e.source();
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for PotrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PotrError::Translate(e) => Some(e),
+            PotrError::Io(e) => Some(e),
+            PotrError::Parse(e) | PotrError::Config(e) => Some(e.as_ref()),
+
+            PotrError::BelowThreshold { .. } => None,
+
+            // The chain of the error is already in the message.
+            PotrError::Other(_) => None,
+        }
+    }
+}
+
+impl From<TranslateError> for PotrError {
+    fn from(e: TranslateError) -> Self {
+        PotrError::Translate(e)
+    }
+}
+
+impl From<std::io::Error> for PotrError {
+    fn from(e: std::io::Error) -> Self {
+        PotrError::Io(e)
+    }
+}
+
+impl From<polib::po_file::POParseError> for PotrError {
+    fn from(e: polib::po_file::POParseError) -> Self {
+        PotrError::Parse(Box::new(e))
+    }
+}
+
+impl From<serde_json::Error> for PotrError {
+    fn from(e: serde_json::Error) -> Self {
+        PotrError::Parse(Box::new(e))
+    }
+}
+
+impl From<roxmltree::Error> for PotrError {
+    fn from(e: roxmltree::Error) -> Self {
+        PotrError::Parse(Box::new(e))
+    }
+}
+
+impl From<csv::Error> for PotrError {
+    fn from(e: csv::Error) -> Self {
+        PotrError::Parse(Box::new(e))
+    }
+}
+
+impl From<toml::de::Error> for PotrError {
+    fn from(e: toml::de::Error) -> Self {
+        PotrError::Config(Box::new(e))
+    }
+}
+
+impl From<regex::Error> for PotrError {
+    fn from(e: regex::Error) -> Self {
+        PotrError::Config(Box::new(e))
+    }
+}
+
+/// Errors are passed around as `anyhow::Error` inside the crate, so they are grouped by the type
+/// of the error they are created from.
+impl From<anyhow::Error> for PotrError {
+    fn from(e: anyhow::Error) -> Self {
+        downcast::<PotrError>(e)
+            .or_else(downcast::<TranslateError>)
+            .or_else(downcast::<std::io::Error>)
+            .or_else(downcast::<polib::po_file::POParseError>)
+            .or_else(downcast::<serde_json::Error>)
+            .or_else(downcast::<roxmltree::Error>)
+            .or_else(downcast::<csv::Error>)
+            .or_else(downcast::<toml::de::Error>)
+            .or_else(downcast::<regex::Error>)
+            .unwrap_or_else(|e| PotrError::Other(e.into()))
+    }
+}
+
+fn downcast<E>(e: anyhow::Error) -> Result<PotrError, anyhow::Error>
+where
+    E: fmt::Display + fmt::Debug + Send + Sync + 'static,
+    PotrError: From<E>,
+{
+    e.downcast::<E>().map(PotrError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_should_be_grouped_by_type() {
+        let e: PotrError = anyhow::Error::from(TranslateError::Auth("Bad key".to_string())).into();
+        assert!(matches!(e, PotrError::Translate(TranslateError::Auth(_))));
+
+        let e: PotrError =
+            anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound)).into();
+        assert!(matches!(e, PotrError::Io(_)));
+
+        let e: PotrError =
+            anyhow::Error::from(toml::from_str::<toml::Value>("=").unwrap_err()).into();
+        assert!(matches!(e, PotrError::Config(_)));
+        assert!(std::error::Error::source(&e)
+            .unwrap()
+            .is::<toml::de::Error>());
+
+        let e: PotrError = anyhow::anyhow!("Something else").into();
+        assert!(matches!(e, PotrError::Other(_)));
+
+        let e: PotrError = anyhow::anyhow!("Something else")
+            .context("Failed to run")
+            .into();
+        assert_eq!(e.to_string(), "Failed to run: Something else");
+    }
+}
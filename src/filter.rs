@@ -0,0 +1,676 @@
+use crate::error::PotrError;
+use anyhow::Result;
+use polib::message::MessageView;
+use regex::Regex;
+use std::{fmt, str::FromStr};
+
+/// Message filter expression, e.g.
+/// `translated && !fuzzy && source =~ "src/guide" && len(msgid) < 500 && !flag("no-wrap")`.
+///
+/// Supported fields:
+/// - `msgid`, `msgid_plural`, `msgctxt`, `comments`: String.
+/// - `msgstr`: String. For plural messages, it is the first plural form.
+/// - `source`: List of source file paths, without line numbers.
+/// - `line`: List of source line numbers.
+/// - `source_ref`: List of source references as they are in the PO file, e.g. "src/SUMMARY.md:3".
+/// - `flags`: List of flags.
+/// - `translated`, `fuzzy`, `plural`, `code_block`: Bool.
+///
+/// Supported operators: `&&`, `||`, `!`, `==`, `!=`, `<`, `<=`, `>`, `>=`, `=~` (regex match) and
+/// `!~` (regex not match). When a list is compared, the comparison is true if any item matches.
+///
+/// Supported functions: `len(s)`, `flag(name)`, `contains(s, sub)`, `starts_with(s, prefix)` and
+/// `ends_with(s, suffix)`.
+#[derive(Debug, Clone)]
+pub struct MessageFilter {
+    expr: Expr,
+    text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueType {
+    Bool,
+    Number,
+    String,
+    StringList,
+    NumberList,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Bool(bool),
+    Number(f64),
+    String(String),
+    StringList(Vec<String>),
+    NumberList(Vec<f64>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Msgid,
+    MsgidPlural,
+    Msgstr,
+    Msgctxt,
+    Comments,
+    Source,
+    Line,
+    SourceRef,
+    Flags,
+    Translated,
+    Fuzzy,
+    Plural,
+    CodeBlock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Len,
+    Flag,
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Field(Field),
+    Call(Function, Vec<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(CompareOp, Box<Expr>, Box<Expr>),
+    Match(Box<Expr>, Regex, bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    String(String),
+    Number(f64),
+    LParen,
+    RParen,
+    Comma,
+    And,
+    Or,
+    Not,
+    Compare(CompareOp),
+    Match,
+    NotMatch,
+}
+
+impl MessageFilter {
+    pub fn parse(text: &str) -> Result<MessageFilter, PotrError> {
+        Self::parse_expr(text).map_err(|e| PotrError::config(e.to_string()))
+    }
+
+    fn parse_expr(text: &str) -> Result<MessageFilter> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let (expr, value_type) = parser.parse_or()?;
+        if let Some((_, token)) = parser.tokens.get(parser.pos) {
+            anyhow::bail!("Unexpected token in filter: {:?}", token);
+        }
+        if value_type != ValueType::Bool {
+            anyhow::bail!("Filter must be a bool expression, but got {:?}", value_type);
+        }
+
+        Ok(MessageFilter {
+            expr,
+            text: text.to_string(),
+        })
+    }
+
+    /// Filter matching a field with the regex, e.g. `source =~ "src/guide"`, for the options
+    /// that are shorthands of a filter.
+    pub(crate) fn regex_match(field: &str, regex: &Regex, negated: bool) -> MessageFilter {
+        let pattern = regex.as_str().replace('\\', "\\\\").replace('"', "\\\"");
+        let op = if negated { "!~" } else { "=~" };
+        MessageFilter::parse(&format!("{} {} \"{}\"", field, op, pattern))
+            .expect("Regex filter should be valid")
+    }
+
+    pub fn is_match(&self, message: &dyn MessageView) -> bool {
+        self.expr.eval(message) == Value::Bool(true)
+    }
+}
+
+impl FromStr for MessageFilter {
+    type Err = PotrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MessageFilter::parse(s)
+    }
+}
+
+impl fmt::Display for MessageFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Source references of a message as (path, line) pairs, e.g. "src/SUMMARY.md:3".
+pub(crate) fn message_source_refs(message: &dyn MessageView) -> Vec<(&str, Option<u32>)> {
+    message
+        .source()
+        .split_whitespace()
+        .map(|s| match s.rsplit_once(':') {
+            Some((path, line)) if line.parse::<u32>().is_ok() => (path, line.parse().ok()),
+            _ => (s, None),
+        })
+        .collect()
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        let field = match name {
+            "msgid" => Field::Msgid,
+            "msgid_plural" => Field::MsgidPlural,
+            "msgstr" => Field::Msgstr,
+            "msgctxt" => Field::Msgctxt,
+            "comments" => Field::Comments,
+            "source" => Field::Source,
+            "line" => Field::Line,
+            "source_ref" => Field::SourceRef,
+            "flags" => Field::Flags,
+            "translated" => Field::Translated,
+            "fuzzy" => Field::Fuzzy,
+            "plural" => Field::Plural,
+            "code_block" => Field::CodeBlock,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    fn value_type(&self) -> ValueType {
+        match self {
+            Field::Msgid
+            | Field::MsgidPlural
+            | Field::Msgstr
+            | Field::Msgctxt
+            | Field::Comments => ValueType::String,
+            Field::Source | Field::SourceRef | Field::Flags => ValueType::StringList,
+            Field::Line => ValueType::NumberList,
+            Field::Translated | Field::Fuzzy | Field::Plural | Field::CodeBlock => ValueType::Bool,
+        }
+    }
+
+    fn value(&self, message: &dyn MessageView) -> Value {
+        match self {
+            Field::Msgid => Value::String(message.msgid().to_string()),
+            Field::MsgidPlural => {
+                Value::String(message.msgid_plural().unwrap_or_default().to_string())
+            }
+            Field::Msgstr => Value::String(match (message.msgstr(), message.msgstr_plural()) {
+                (Ok(msgstr), _) => msgstr.to_string(),
+                (_, Ok(msgstr_plural)) => msgstr_plural.first().cloned().unwrap_or_default(),
+                _ => String::new(),
+            }),
+            Field::Msgctxt => Value::String(message.msgctxt().to_string()),
+            Field::Comments => Value::String(message.comments().to_string()),
+            Field::Source => Value::StringList(
+                message_source_refs(message)
+                    .into_iter()
+                    .map(|(path, _)| path.to_string())
+                    .collect(),
+            ),
+            Field::Line => Value::NumberList(
+                message_source_refs(message)
+                    .into_iter()
+                    .filter_map(|(_, line)| line.map(|l| l as f64))
+                    .collect(),
+            ),
+            Field::SourceRef => Value::StringList(
+                message
+                    .source()
+                    .split_whitespace()
+                    .map(|s| s.to_string())
+                    .collect(),
+            ),
+            Field::Flags => Value::StringList(message.flags().iter().cloned().collect()),
+            Field::Translated => Value::Bool(message.is_translated()),
+            Field::Fuzzy => Value::Bool(message.is_fuzzy()),
+            Field::Plural => Value::Bool(message.is_plural()),
+            Field::CodeBlock => Value::Bool(message.msgid().starts_with("```")),
+        }
+    }
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        let function = match name {
+            "len" => Function::Len,
+            "flag" => Function::Flag,
+            "contains" => Function::Contains,
+            "starts_with" => Function::StartsWith,
+            "ends_with" => Function::EndsWith,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    fn check_args(&self, arg_types: &[ValueType]) -> Result<ValueType> {
+        let valid = match self {
+            Function::Len => {
+                arg_types.len() == 1
+                    && matches!(
+                        arg_types[0],
+                        ValueType::String | ValueType::StringList | ValueType::NumberList
+                    )
+            }
+            Function::Flag => arg_types == [ValueType::String],
+            Function::Contains | Function::StartsWith | Function::EndsWith => {
+                arg_types.len() == 2
+                    && matches!(arg_types[0], ValueType::String | ValueType::StringList)
+                    && arg_types[1] == ValueType::String
+            }
+        };
+        if !valid {
+            anyhow::bail!("Invalid arguments for function {:?}: {:?}", self, arg_types);
+        }
+
+        Ok(match self {
+            Function::Len => ValueType::Number,
+            _ => ValueType::Bool,
+        })
+    }
+
+    fn call(&self, args: Vec<Value>, message: &dyn MessageView) -> Value {
+        let string_test = |f: fn(&str, &str) -> bool| match (&args[0], &args[1]) {
+            (Value::String(s), Value::String(p)) => Value::Bool(f(s, p)),
+            (Value::StringList(l), Value::String(p)) => Value::Bool(l.iter().any(|s| f(s, p))),
+            _ => Value::Bool(false),
+        };
+
+        match self {
+            Function::Len => Value::Number(match &args[0] {
+                Value::String(s) => s.chars().count() as f64,
+                Value::StringList(l) => l.len() as f64,
+                Value::NumberList(l) => l.len() as f64,
+                _ => 0.0,
+            }),
+            Function::Flag => match &args[0] {
+                Value::String(flag) => Value::Bool(message.flags().contains(flag)),
+                _ => Value::Bool(false),
+            },
+            Function::Contains => string_test(|s, p| s.contains(p)),
+            Function::StartsWith => string_test(|s, p| s.starts_with(p)),
+            Function::EndsWith => string_test(|s, p| s.ends_with(p)),
+        }
+    }
+}
+
+impl CompareOp {
+    fn compare<T: PartialOrd>(&self, a: T, b: T) -> bool {
+        match self {
+            CompareOp::Eq => a == b,
+            CompareOp::Ne => a != b,
+            CompareOp::Lt => a < b,
+            CompareOp::Le => a <= b,
+            CompareOp::Gt => a > b,
+            CompareOp::Ge => a >= b,
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, message: &dyn MessageView) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Field(field) => field.value(message),
+            Expr::Call(function, args) => {
+                function.call(args.iter().map(|a| a.eval(message)).collect(), message)
+            }
+            Expr::Not(expr) => Value::Bool(expr.eval(message) != Value::Bool(true)),
+            Expr::And(a, b) => Value::Bool(
+                a.eval(message) == Value::Bool(true) && b.eval(message) == Value::Bool(true),
+            ),
+            Expr::Or(a, b) => Value::Bool(
+                a.eval(message) == Value::Bool(true) || b.eval(message) == Value::Bool(true),
+            ),
+            Expr::Compare(op, a, b) => Value::Bool(match (a.eval(message), b.eval(message)) {
+                (Value::Bool(a), Value::Bool(b)) => op.compare(a, b),
+                (Value::Number(a), Value::Number(b)) => op.compare(a, b),
+                (Value::String(a), Value::String(b)) => op.compare(a, b),
+                (Value::StringList(l), Value::String(b)) => {
+                    l.into_iter().any(|a| op.compare(a, b.clone()))
+                }
+                (Value::NumberList(l), Value::Number(b)) => l.into_iter().any(|a| op.compare(a, b)),
+                _ => false,
+            }),
+            Expr::Match(expr, regex, negated) => {
+                let matched = match expr.eval(message) {
+                    Value::String(s) => regex.is_match(&s),
+                    Value::StringList(l) => l.iter().any(|s| regex.is_match(s)),
+                    _ => false,
+                };
+                Value::Bool(matched != *negated)
+            }
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let start = pos;
+        let c = chars[pos];
+        let next = chars.get(pos + 1).copied();
+        let token = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            ('(', _) => Token::LParen,
+            (')', _) => Token::RParen,
+            (',', _) => Token::Comma,
+            ('&', Some('&')) => Token::And,
+            ('|', Some('|')) => Token::Or,
+            ('=', Some('=')) => Token::Compare(CompareOp::Eq),
+            ('=', Some('~')) => Token::Match,
+            ('!', Some('=')) => Token::Compare(CompareOp::Ne),
+            ('!', Some('~')) => Token::NotMatch,
+            ('!', _) => Token::Not,
+            ('<', Some('=')) => Token::Compare(CompareOp::Le),
+            ('<', _) => Token::Compare(CompareOp::Lt),
+            ('>', Some('=')) => Token::Compare(CompareOp::Ge),
+            ('>', _) => Token::Compare(CompareOp::Gt),
+            ('"', _) => {
+                let mut s = String::new();
+                pos += 1;
+                loop {
+                    match chars.get(pos) {
+                        None => anyhow::bail!("Unterminated string in filter at {}", start),
+                        Some('"') => break,
+                        Some('\\') => {
+                            pos += 1;
+                            match chars.get(pos) {
+                                Some('n') => s.push('\n'),
+                                Some('t') => s.push('\t'),
+                                Some(c @ ('"' | '\\')) => s.push(*c),
+                                // Keep other escapes as they are, so regexes like "\d+" work.
+                                Some(c) => {
+                                    s.push('\\');
+                                    s.push(*c);
+                                }
+                                None => anyhow::bail!("Unterminated string in filter at {}", start),
+                            }
+                        }
+                        Some(c) => s.push(*c),
+                    }
+                    pos += 1;
+                }
+                Token::String(s)
+            }
+            (c, _) if c.is_ascii_digit() => {
+                while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                    pos += 1;
+                }
+                let number: String = chars[start..pos].iter().collect();
+                tokens.push((start, Token::Number(number.parse()?)));
+                continue;
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                    pos += 1;
+                }
+                tokens.push((start, Token::Ident(chars[start..pos].iter().collect())));
+                continue;
+            }
+            (c, _) => anyhow::bail!("Unexpected character in filter at {}: {}", start, c),
+        };
+
+        pos += match token {
+            Token::LParen | Token::RParen | Token::Comma | Token::String(_) => 1,
+            Token::Not | Token::Compare(CompareOp::Lt) | Token::Compare(CompareOp::Gt) => 1,
+            _ => 2,
+        };
+        tokens.push((start, token));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        match self.tokens.get(self.pos) {
+            Some((_, token)) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => anyhow::bail!("Unexpected end of filter"),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        let token = self.next()?;
+        if token != expected {
+            anyhow::bail!("Expected {:?} in filter, but got {:?}", expected, token);
+        }
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<(Expr, ValueType)> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = (
+                Expr::Or(Box::new(expect_bool(left)?), Box::new(expect_bool(right)?)),
+                ValueType::Bool,
+            );
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<(Expr, ValueType)> {
+        let mut left = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            left = (
+                Expr::And(Box::new(expect_bool(left)?), Box::new(expect_bool(right)?)),
+                ValueType::Bool,
+            );
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<(Expr, ValueType)> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            let expr = self.parse_unary()?;
+            return Ok((Expr::Not(Box::new(expect_bool(expr)?)), ValueType::Bool));
+        }
+
+        self.parse_compare()
+    }
+
+    fn parse_compare(&mut self) -> Result<(Expr, ValueType)> {
+        let (left, left_type) = self.parse_primary()?;
+        match self.peek().cloned() {
+            Some(Token::Compare(op)) => {
+                self.pos += 1;
+                let (right, right_type) = self.parse_primary()?;
+                let valid = match (left_type, right_type) {
+                    (ValueType::Number | ValueType::NumberList, ValueType::Number) => true,
+                    (ValueType::String | ValueType::StringList, ValueType::String)
+                    | (ValueType::Bool, ValueType::Bool) => {
+                        matches!(op, CompareOp::Eq | CompareOp::Ne)
+                    }
+                    _ => false,
+                };
+                if !valid {
+                    anyhow::bail!(
+                        "Cannot compare {:?} with {:?} using {:?} in filter",
+                        left_type,
+                        right_type,
+                        op
+                    );
+                }
+                Ok((
+                    Expr::Compare(op, Box::new(left), Box::new(right)),
+                    ValueType::Bool,
+                ))
+            }
+            Some(token @ (Token::Match | Token::NotMatch)) => {
+                self.pos += 1;
+                if !matches!(left_type, ValueType::String | ValueType::StringList) {
+                    anyhow::bail!("Cannot match {:?} with regex in filter", left_type);
+                }
+                let pattern = match self.next()? {
+                    Token::String(pattern) => pattern,
+                    token => anyhow::bail!("Expected regex string in filter, but got {:?}", token),
+                };
+                Ok((
+                    Expr::Match(
+                        Box::new(left),
+                        Regex::new(&pattern)?,
+                        token == Token::NotMatch,
+                    ),
+                    ValueType::Bool,
+                ))
+            }
+            _ => Ok((left, left_type)),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<(Expr, ValueType)> {
+        match self.next()? {
+            Token::LParen => {
+                let expr = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Token::String(s) => Ok((Expr::Literal(Value::String(s)), ValueType::String)),
+            Token::Number(n) => Ok((Expr::Literal(Value::Number(n)), ValueType::Number)),
+            Token::Ident(name) if name == "true" || name == "false" => {
+                Ok((Expr::Literal(Value::Bool(name == "true")), ValueType::Bool))
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::LParen) => {
+                let function = Function::from_name(&name)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown function in filter: {}", name))?;
+                self.pos += 1;
+
+                let mut args = Vec::new();
+                let mut arg_types = Vec::new();
+                if self.peek() != Some(&Token::RParen) {
+                    loop {
+                        let (arg, arg_type) = self.parse_or()?;
+                        args.push(arg);
+                        arg_types.push(arg_type);
+                        if self.peek() != Some(&Token::Comma) {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                self.expect(Token::RParen)?;
+
+                let value_type = function.check_args(&arg_types)?;
+                Ok((Expr::Call(function, args), value_type))
+            }
+            Token::Ident(name) => {
+                let field = Field::from_name(&name)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown field in filter: {}", name))?;
+                Ok((Expr::Field(field), field.value_type()))
+            }
+            token => anyhow::bail!("Unexpected token in filter: {:?}", token),
+        }
+    }
+}
+
+fn expect_bool((expr, value_type): (Expr, ValueType)) -> Result<Expr> {
+    if value_type != ValueType::Bool {
+        anyhow::bail!(
+            "Expected bool expression in filter, but got {:?}",
+            value_type
+        );
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+
+    fn message() -> Message {
+        let mut flags = polib::message::MessageFlags::new();
+        flags.add_flag("no-wrap");
+        Message::build_singular()
+            .with_source("src/guide/Pen.md:3 src/SUMMARY.md:10".to_string())
+            .with_flags(flags)
+            .with_msgctxt("button".to_string())
+            .with_msgid("This is a pen!".to_string())
+            .with_msgstr("Das ist ein Stift!".to_string())
+            .done()
+    }
+
+    fn is_match(filter: &str) -> bool {
+        MessageFilter::parse(filter).unwrap().is_match(&message())
+    }
+
+    #[test]
+    fn filter_should_match_fields() {
+        assert!(is_match("translated && !fuzzy"));
+        assert!(is_match("source =~ \"src/guide\""));
+        assert!(is_match("source == \"src/SUMMARY.md\" && line > 5"));
+        assert!(is_match("source_ref =~ \"SUMMARY\\.md:10$\""));
+        assert!(is_match("msgctxt == \"button\" && msgstr =~ \"^Das\""));
+        assert!(is_match("len(msgid) < 500 && flag(\"no-wrap\")"));
+        assert!(is_match(
+            "!(code_block || plural) && starts_with(msgid, \"This\")"
+        ));
+        assert!(!is_match("translated && !flag(\"no-wrap\")"));
+        assert!(!is_match("source !~ \"\\.md$\""));
+        assert!(!is_match("line >= 11 || contains(comments, \"TODO\")"));
+    }
+
+    #[test]
+    fn regex_match_should_keep_the_pattern() {
+        let regex = Regex::new(r#"^This\s"?is"#).unwrap();
+        let filter = MessageFilter::regex_match("msgid", &regex, false);
+        assert_eq!(filter.to_string(), r#"msgid =~ "^This\\s\"?is""#);
+        assert!(filter.is_match(&message()));
+        assert!(!MessageFilter::regex_match("msgid", &regex, true).is_match(&message()));
+    }
+
+    #[test]
+    fn invalid_filter_should_fail_to_parse() {
+        for filter in [
+            "",
+            "msgid",
+            "translated &&",
+            "unknown",
+            "len(translated) > 1",
+            "msgid > \"a\"",
+            "fuzzy =~ \"a\"",
+            "msgid =~ \"(\"",
+            "\"unterminated",
+        ] {
+            assert!(MessageFilter::parse(filter).is_err(), "{}", filter);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use super::{check_translation_path, translations_by_key, CatalogBackend, CatalogFormat};
+use crate::error::PotrError;
+use polib::{catalog::Catalog, message::Message, metadata::CatalogMetadata};
+use regex::Regex;
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+/// Indent of the multiline patterns written into the translation file.
+const INDENT: &str = "    ";
+
+/// Fluent (.ftl) files. The value of a message or term is keyed by its id, e.g. "hello" or
+/// "-brand", and its attributes by "id.attribute".
+///
+/// Patterns are translated as text, so placeables and select expressions are sent to the engine
+/// as they are.
+pub struct FluentBackend {}
+
+impl CatalogBackend for FluentBackend {
+    fn format(&self) -> CatalogFormat {
+        CatalogFormat::Fluent
+    }
+
+    fn load(&self, source_path: &Path, translation_path: &Path) -> Result<Catalog, PotrError> {
+        check_translation_path(self.format(), source_path, translation_path)?;
+
+        let mut translations = HashMap::new();
+        if translation_path.exists() {
+            for item in parse(&std::fs::read_to_string(translation_path)?)? {
+                if let FtlItem::Entry(entry) = item {
+                    translations.extend(entry.patterns());
+                }
+            }
+        }
+
+        let source_file = source_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for item in parse(&std::fs::read_to_string(source_path)?)? {
+            let entry = match item {
+                FtlItem::Entry(entry) => entry,
+                FtlItem::Comment(_) => continue,
+            };
+
+            for (key, text) in entry.patterns() {
+                let msgstr = translations.get(&key).cloned().unwrap_or_default();
+                catalog.append_or_update(
+                    Message::build_singular()
+                        .with_comments(entry.comment.clone())
+                        .with_source(format!("{}:{}", source_file, entry.line))
+                        .with_msgctxt(key)
+                        .with_msgid(text)
+                        .with_msgstr(msgstr)
+                        .done(),
+                );
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    /// Write the translated entries in the order of the source file. Entries without any
+    /// translation are left out, so the app can fall back to the source language.
+    fn write(
+        &self,
+        catalog: &Catalog,
+        source_path: &Path,
+        translation_path: &Path,
+    ) -> Result<(), PotrError> {
+        let translations = translations_by_key(catalog);
+
+        let mut ftl = String::new();
+        for item in parse(&std::fs::read_to_string(source_path)?)? {
+            match item {
+                FtlItem::Comment(comment) => writeln!(ftl, "{}\n", comment).unwrap(),
+                FtlItem::Entry(entry) => {
+                    let value = translations.get(&entry.id);
+                    let attributes: Vec<(&String, &String)> = entry
+                        .attributes
+                        .iter()
+                        .filter_map(|(name, _)| {
+                            translations
+                                .get(&format!("{}.{}", entry.id, name))
+                                .map(|text| (name, text))
+                        })
+                        .collect();
+
+                    // Terms must have a value, while messages can have only attributes.
+                    if value.is_none() && (attributes.is_empty() || entry.id.starts_with('-')) {
+                        continue;
+                    }
+
+                    for line in entry.comment.lines() {
+                        if line.is_empty() {
+                            writeln!(ftl, "#").unwrap();
+                        } else {
+                            writeln!(ftl, "# {}", line).unwrap();
+                        }
+                    }
+                    match value {
+                        Some(value) => {
+                            write_pattern(&mut ftl, &format!("{} =", entry.id), value, INDENT)
+                        }
+                        None => writeln!(ftl, "{} =", entry.id).unwrap(),
+                    }
+                    for (name, text) in attributes {
+                        write_pattern(
+                            &mut ftl,
+                            &format!("{}.{} =", INDENT, name),
+                            text,
+                            &INDENT.repeat(2),
+                        );
+                    }
+                    writeln!(ftl).unwrap();
+                }
+            }
+        }
+
+        std::fs::write(translation_path, ftl)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FtlItem {
+    /// Comment lines that are not attached to an entry, e.g. group comments.
+    Comment(String),
+
+    Entry(FtlEntry),
+}
+
+/// A message or a term.
+#[derive(Debug, Clone, PartialEq)]
+struct FtlEntry {
+    id: String,
+    line: usize,
+
+    /// Text of the comment lines right before the entry.
+    comment: String,
+
+    value: Option<String>,
+    attributes: Vec<(String, String)>,
+}
+
+impl FtlEntry {
+    /// Value and attributes, keyed in the same way as msgctxt.
+    fn patterns(&self) -> Vec<(String, String)> {
+        let mut patterns = vec![];
+        if let Some(value) = &self.value {
+            patterns.push((self.id.clone(), value.clone()));
+        }
+        for (name, text) in &self.attributes {
+            patterns.push((format!("{}.{}", self.id, name), text.clone()));
+        }
+        patterns
+    }
+}
+
+fn parse(ftl: &str) -> Result<Vec<FtlItem>, PotrError> {
+    let entry_regex = Regex::new(r"^(-?[a-zA-Z][a-zA-Z0-9_-]*) *=(.*)$").unwrap();
+    let attribute_regex = Regex::new(r"^\s+\.([a-zA-Z][a-zA-Z0-9_-]*) *=(.*)$").unwrap();
+
+    let lines: Vec<&str> = ftl.lines().collect();
+    let mut items = vec![];
+    let mut comment_lines: Vec<&str> = vec![];
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        index += 1;
+
+        // Only "#" comments right before an entry are attached to it. The ones followed by a
+        // blank line, and "##" or "###" comments, are standalone.
+        if line.starts_with('#') && !line.starts_with("##") {
+            comment_lines.push(line);
+            continue;
+        }
+        if line.starts_with('#') || line.trim().is_empty() {
+            if !comment_lines.is_empty() {
+                items.push(FtlItem::Comment(comment_lines.join("\n")));
+                comment_lines.clear();
+            }
+            if line.starts_with('#') {
+                items.push(FtlItem::Comment(line.to_string()));
+            }
+            continue;
+        }
+
+        let line_number = index;
+        let captures = match entry_regex.captures(line) {
+            Some(captures) => captures,
+            None => {
+                return Err(PotrError::parse(format!(
+                    "Invalid Fluent entry at line {}: {}",
+                    line_number, line
+                )))
+            }
+        };
+
+        // Indented lines, and the blank lines between them, continue the entry.
+        let mut block = vec![];
+        while index < lines.len() {
+            let next = lines[index];
+            let continues = if next.trim().is_empty() {
+                lines[index..]
+                    .iter()
+                    .find(|line| !line.trim().is_empty())
+                    .is_some_and(|line| line.starts_with(' '))
+            } else {
+                next.starts_with(' ')
+            };
+            if !continues {
+                break;
+            }
+            block.push(next);
+            index += 1;
+        }
+
+        let value_end = block
+            .iter()
+            .position(|line| attribute_regex.is_match(line))
+            .unwrap_or(block.len());
+        let mut attributes = vec![];
+        let mut attribute_start = value_end;
+        while attribute_start < block.len() {
+            let captures = attribute_regex.captures(block[attribute_start]).unwrap();
+            let attribute_end = block[attribute_start + 1..]
+                .iter()
+                .position(|line| attribute_regex.is_match(line))
+                .map_or(block.len(), |position| attribute_start + 1 + position);
+            attributes.push((
+                captures[1].to_string(),
+                pattern(&captures[2], &block[attribute_start + 1..attribute_end]),
+            ));
+            attribute_start = attribute_end;
+        }
+
+        let value = pattern(&captures[2], &block[..value_end]);
+        let comment = comment_lines
+            .drain(..)
+            .map(|line| line.trim_start_matches('#').strip_prefix(' ').unwrap_or(""))
+            .collect::<Vec<_>>()
+            .join("\n");
+        items.push(FtlItem::Entry(FtlEntry {
+            id: captures[1].to_string(),
+            line: line_number,
+            comment,
+            value: (!value.is_empty()).then_some(value),
+            attributes,
+        }));
+    }
+
+    if !comment_lines.is_empty() {
+        items.push(FtlItem::Comment(comment_lines.join("\n")));
+    }
+    Ok(items)
+}
+
+/// Text of a pattern, from the inline part after "=" and the indented lines after it. The
+/// common indent of the lines is removed.
+fn pattern(inline: &str, lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    let mut text_lines = vec![];
+    if !inline.trim().is_empty() {
+        text_lines.push(inline.trim());
+    }
+    text_lines.extend(
+        lines
+            .iter()
+            .map(|line| line.get(indent..).unwrap_or("").trim_end()),
+    );
+    text_lines.join("\n").trim().to_string()
+}
+
+fn write_pattern(ftl: &mut String, prefix: &str, text: &str, indent: &str) {
+    if !text.contains('\n') {
+        writeln!(ftl, "{} {}", prefix, text).unwrap();
+        return;
+    }
+
+    writeln!(ftl, "{}", prefix).unwrap();
+    for line in text.lines() {
+        if line.is_empty() {
+            writeln!(ftl).unwrap();
+        } else {
+            writeln!(ftl, "{}{}", indent, line).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn entries_should_be_parsed_with_comments_and_attributes() {
+        let ftl = "### Resource comment\n\n# Shown on the home page.\nhello = Hello, { $name }!\n\n-brand = Potr\nlogin-input = Predefined value\n    .placeholder = email@example.com\n    .aria-label =\n        Login\n        input value\n\nemails =\n    { $count ->\n        [one] One email\n       *[other] { $count } emails\n    }\n";
+        let items = parse(ftl).unwrap();
+        assert_eq!(items.len(), 5);
+        assert_eq!(
+            items[0],
+            FtlItem::Comment("### Resource comment".to_string())
+        );
+        assert_eq!(
+            items[1],
+            FtlItem::Entry(FtlEntry {
+                id: "hello".to_string(),
+                line: 4,
+                comment: "Shown on the home page.".to_string(),
+                value: Some("Hello, { $name }!".to_string()),
+                attributes: vec![],
+            })
+        );
+
+        let FtlItem::Entry(login_input) = &items[3] else {
+            panic!("Entry is expected: {:?}", items[3]);
+        };
+        assert_eq!(
+            login_input.patterns(),
+            vec![
+                ("login-input".to_string(), "Predefined value".to_string()),
+                (
+                    "login-input.placeholder".to_string(),
+                    "email@example.com".to_string()
+                ),
+                (
+                    "login-input.aria-label".to_string(),
+                    "Login\ninput value".to_string()
+                ),
+            ]
+        );
+
+        let FtlItem::Entry(emails) = &items[4] else {
+            panic!("Entry is expected: {:?}", items[4]);
+        };
+        assert_eq!(
+            emails.value.as_deref(),
+            Some("{ $count ->\n    [one] One email\n   *[other] { $count } emails\n}")
+        );
+    }
+
+    #[test]
+    fn unindented_text_should_fail() {
+        assert!(parse("hello = Hello\nworld\n").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use super::{check_translation_path, translations_by_key, CatalogBackend, CatalogFormat};
+use crate::error::PotrError;
+use polib::{catalog::Catalog, message::Message, metadata::CatalogMetadata};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, path::Path};
+
+/// JSON i18n files, either flat (`{"home.title": "Home"}`) or nested
+/// (`{"home": {"title": "Home"}}`). The keys of nested objects are joined with dots.
+///
+/// Only string values are translated. Other values, e.g. numbers, are copied as they are.
+pub struct JsonBackend {}
+
+impl CatalogBackend for JsonBackend {
+    fn format(&self) -> CatalogFormat {
+        CatalogFormat::Json
+    }
+
+    fn load(&self, source_path: &Path, translation_path: &Path) -> Result<Catalog, PotrError> {
+        check_translation_path(self.format(), source_path, translation_path)?;
+
+        let translations: HashMap<String, String> = if translation_path.exists() {
+            flatten(&read_json(translation_path)?).into_iter().collect()
+        } else {
+            HashMap::new()
+        };
+
+        let source_file = source_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for (key, text) in flatten(&read_json(source_path)?) {
+            // Nothing to translate, and empty msgid is reserved for the PO header.
+            if text.is_empty() {
+                continue;
+            }
+
+            let msgstr = translations.get(&key).cloned().unwrap_or_default();
+            catalog.append_or_update(
+                Message::build_singular()
+                    .with_source(source_file.clone())
+                    .with_msgctxt(key)
+                    .with_msgid(text)
+                    .with_msgstr(msgstr)
+                    .done(),
+            );
+        }
+
+        Ok(catalog)
+    }
+
+    fn write(
+        &self,
+        catalog: &Catalog,
+        source_path: &Path,
+        translation_path: &Path,
+    ) -> Result<(), PotrError> {
+        let translations = translations_by_key(catalog);
+        let output = translate_value(&read_json(source_path)?, None, &translations)
+            .unwrap_or_else(|| Value::Object(Map::new()));
+
+        let mut json = serde_json::to_string_pretty(&output)?;
+        json.push('\n');
+        std::fs::write(translation_path, json)?;
+        Ok(())
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value, PotrError> {
+    let value: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    if !value.is_object() {
+        return Err(PotrError::parse(format!(
+            "JSON i18n file must be an object: {}",
+            path.display()
+        )));
+    }
+    Ok(value)
+}
+
+fn child_key(key: Option<&str>, name: &str) -> String {
+    match key {
+        Some(key) => format!("{}.{}", key, name),
+        None => name.to_string(),
+    }
+}
+
+/// All string values with their keys, in the order of the file.
+fn flatten(value: &Value) -> Vec<(String, String)> {
+    fn visit(value: &Value, key: Option<&str>, entries: &mut Vec<(String, String)>) {
+        match value {
+            Value::Object(map) => {
+                for (name, child) in map {
+                    visit(child, Some(&child_key(key, name)), entries);
+                }
+            }
+            Value::String(text) => {
+                if let Some(key) = key {
+                    entries.push((key.to_string(), text.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut entries = vec![];
+    visit(value, None, &mut entries);
+    entries
+}
+
+/// Replace the strings in the source with their translations. Untranslated strings and the
+/// objects left empty are removed, so the app can fall back to the source language.
+fn translate_value(
+    value: &Value,
+    key: Option<&str>,
+    translations: &HashMap<String, String>,
+) -> Option<Value> {
+    match value {
+        Value::Object(map) => {
+            let translated: Map<String, Value> = map
+                .iter()
+                .filter_map(|(name, child)| {
+                    translate_value(child, Some(&child_key(key, name)), translations)
+                        .map(|child| (name.clone(), child))
+                })
+                .collect();
+            (!translated.is_empty()).then_some(Value::Object(translated))
+        }
+        Value::String(text) if text.is_empty() => Some(value.clone()),
+        Value::String(_) => key
+            .and_then(|key| translations.get(key))
+            .map(|msgstr| Value::String(msgstr.clone())),
+        _ => Some(value.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn nested_keys_should_be_joined_with_dots() {
+        let value = json!({
+            "title": "Pens",
+            "home": { "welcome": "Welcome!", "count": 3, "empty": "" },
+        });
+        assert_eq!(
+            flatten(&value),
+            vec![
+                ("title".to_string(), "Pens".to_string()),
+                ("home.welcome".to_string(), "Welcome!".to_string()),
+                ("home.empty".to_string(), "".to_string()),
+            ]
+        );
+
+        let translations = HashMap::from([("home.welcome".to_string(), "欢迎！".to_string())]);
+        assert_eq!(
+            translate_value(&value, None, &translations),
+            Some(json!({ "home": { "welcome": "欢迎！", "count": 3, "empty": "" } }))
+        );
+    }
+}
@@ -0,0 +1,105 @@
+mod fluent;
+mod json;
+mod po;
+
+use crate::error::PotrError;
+use polib::catalog::Catalog;
+use std::{collections::HashMap, path::Path};
+use strum_macros::{Display, EnumString};
+
+/// Localization file formats that can be translated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+pub enum CatalogFormat {
+    #[strum(serialize = "po")]
+    Po,
+    #[strum(serialize = "json")]
+    Json,
+    #[strum(serialize = "ftl")]
+    Fluent,
+}
+
+impl CatalogFormat {
+    /// Format of the file by its extension. PO is used for unknown extensions, e.g. ".pot".
+    pub fn from_path(path: &str) -> CatalogFormat {
+        match Path::new(path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .as_deref()
+        {
+            Some("json") => CatalogFormat::Json,
+            Some("ftl") => CatalogFormat::Fluent,
+            _ => CatalogFormat::Po,
+        }
+    }
+}
+
+/// Backend of a localization file format.
+///
+/// Messages of all formats are loaded into a PO catalog, so the filters, engines and checks work
+/// the same way on all of them. Formats other than PO keep the source text and the translations
+/// in separate files, so their keys are stored as msgctxt, and the source file is used as the
+/// template when writing the translations.
+pub trait CatalogBackend {
+    fn format(&self) -> CatalogFormat;
+
+    /// Load the messages, along with the existing translations in the translation file if any.
+    fn load(&self, source_path: &Path, translation_path: &Path) -> Result<Catalog, PotrError>;
+
+    fn write(
+        &self,
+        catalog: &Catalog,
+        source_path: &Path,
+        translation_path: &Path,
+    ) -> Result<(), PotrError>;
+}
+
+pub fn create_backend(format: CatalogFormat) -> Box<dyn CatalogBackend> {
+    match format {
+        CatalogFormat::Po => Box::new(po::PoBackend {}),
+        CatalogFormat::Json => Box::new(json::JsonBackend {}),
+        CatalogFormat::Fluent => Box::new(fluent::FluentBackend {}),
+    }
+}
+
+/// Load a PO file for the commands that only work on PO files, e.g. stats or check. Files of
+/// the other formats are rejected, instead of failing to parse them as PO.
+pub(crate) fn load_po_file(path: &str) -> Result<Catalog, PotrError> {
+    let format = CatalogFormat::from_path(path);
+    if format != CatalogFormat::Po {
+        return Err(PotrError::config(format!(
+            "Only PO files are supported by this command, but got a {} file: {}",
+            format, path
+        )));
+    }
+
+    po::PoBackend {}.load(Path::new(path), Path::new(path))
+}
+
+/// Translations of the formats with separate files can't be written into the source file.
+fn check_translation_path(
+    format: CatalogFormat,
+    source_path: &Path,
+    translation_path: &Path,
+) -> Result<(), PotrError> {
+    if source_path == translation_path {
+        return Err(PotrError::config(format!(
+            "Translations of {} files are written into a separate file, please specify it via \"-o\" option.",
+            format
+        )));
+    }
+    Ok(())
+}
+
+/// Translations keyed by msgctxt. Untranslated messages are left out.
+fn translations_by_key(catalog: &Catalog) -> HashMap<String, String> {
+    catalog
+        .messages()
+        .filter(|message| message.is_translated())
+        .filter_map(|message| {
+            message
+                .msgstr()
+                .ok()
+                .map(|msgstr| (message.msgctxt().to_string(), msgstr.to_string()))
+        })
+        .collect()
+}
@@ -0,0 +1,29 @@
+use super::{CatalogBackend, CatalogFormat};
+use crate::error::PotrError;
+use polib::catalog::Catalog;
+use std::path::Path;
+
+/// GNU gettext PO files, which hold both the source text and the translations.
+pub struct PoBackend {}
+
+impl CatalogBackend for PoBackend {
+    fn format(&self) -> CatalogFormat {
+        CatalogFormat::Po
+    }
+
+    fn load(&self, source_path: &Path, _translation_path: &Path) -> Result<Catalog, PotrError> {
+        // The parser reports a missing file as a parse error, so check it first.
+        std::fs::metadata(source_path)?;
+        Ok(polib::po_file::parse(source_path)?)
+    }
+
+    fn write(
+        &self,
+        catalog: &Catalog,
+        _source_path: &Path,
+        translation_path: &Path,
+    ) -> Result<(), PotrError> {
+        polib::po_file::write(catalog, translation_path)?;
+        Ok(())
+    }
+}
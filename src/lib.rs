@@ -1,5 +1,47 @@
+mod budget;
+mod check;
+mod compile;
+mod config_file;
+mod dry_run;
+mod error;
+mod filter;
+mod formats;
+mod merge;
+mod observer;
+mod po_writer;
+mod post_process;
 mod potr;
+mod quality;
+mod report;
+mod review;
+mod rules;
+mod spreadsheet;
+mod stats;
 mod translators;
+mod tui;
+mod validate;
+mod xliff;
 
+pub use budget::*;
+pub use check::*;
+pub use compile::*;
+pub use config_file::*;
+pub use dry_run::*;
+pub use error::*;
+pub use filter::*;
+pub use formats::*;
+pub use merge::*;
+pub use observer::*;
+pub use po_writer::*;
+pub use post_process::*;
 pub use potr::*;
+pub use quality::*;
+pub use report::*;
+pub use review::*;
+pub use rules::*;
+pub use spreadsheet::*;
+pub use stats::*;
 pub use translators::*;
+pub use tui::*;
+pub use validate::*;
+pub use xliff::*;
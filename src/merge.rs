@@ -0,0 +1,382 @@
+use crate::error::PotrError;
+use crate::filter::MessageFilter;
+use crate::formats;
+use crate::po_writer::{message_key, parse_obsolete, PoFileExtras, PoWriter, PreviousMessage};
+use anyhow::Result;
+use polib::{
+    catalog::Catalog,
+    message::{Message, MessageBuilder, MessageFlags, MessageMutView, MessageView},
+    metadata::CatalogMetadata,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+#[derive(Debug, Clone)]
+pub struct MergeConfig {
+    pub po_file_path: String,
+    pub pot_file_path: String,
+    pub output_file_path: String,
+    pub fuzzy_matching: bool,
+    pub fuzzy_threshold: f64,
+    pub keep_previous: bool,
+
+    /// Only look for fuzzy matches of the template messages matching the filter, e.g. to leave
+    /// short messages untranslated instead. Exact matches are always used.
+    pub filter: Option<MessageFilter>,
+}
+
+impl Default for MergeConfig {
+    fn default() -> Self {
+        Self {
+            po_file_path: Default::default(),
+            pot_file_path: Default::default(),
+            output_file_path: Default::default(),
+            fuzzy_matching: true,
+            // Same threshold as the one used by msgmerge.
+            fuzzy_threshold: 0.6,
+            keep_previous: true,
+            filter: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    pub matched_count: usize,
+    pub fuzzy_count: usize,
+    pub untranslated_count: usize,
+    pub obsolete_count: usize,
+}
+
+pub struct MergeResult {
+    pub catalog: Catalog,
+    pub extras: PoFileExtras,
+    pub stats: MergeStats,
+}
+
+/// Updates a PO file from a POT template, the same way as `msgmerge` does.
+pub struct PoMerger {
+    pub config: MergeConfig,
+}
+
+impl PoMerger {
+    pub fn new(config: MergeConfig) -> PoMerger {
+        PoMerger { config }
+    }
+
+    pub fn run(&self) -> Result<MergeStats, PotrError> {
+        tracing::info!(
+            "Merging po file: Po = {}, Pot = {}",
+            self.config.po_file_path,
+            self.config.pot_file_path
+        );
+        let po_file = formats::load_po_file(&self.config.po_file_path)?;
+        let pot_file = formats::load_po_file(&self.config.pot_file_path)?;
+
+        // Obsolete entries are dropped by the PO parser, so they are read from the file content.
+        let po_extras = PoFileExtras {
+            obsolete: parse_obsolete(&std::fs::read_to_string(&self.config.po_file_path)?),
+            ..Default::default()
+        };
+
+        let result = self.merge(&po_file, &po_extras, &pot_file);
+        tracing::info!(
+            "Merge completed! Matched = {}, Fuzzy = {}, Untranslated = {}, Obsolete = {}",
+            result.stats.matched_count,
+            result.stats.fuzzy_count,
+            result.stats.untranslated_count,
+            result.stats.obsolete_count
+        );
+
+        tracing::info!("Write to output: {:?}", self.config.output_file_path);
+        PoWriter::default().write(
+            &result.catalog,
+            &result.extras,
+            Path::new(&self.config.output_file_path),
+        )?;
+
+        Ok(result.stats)
+    }
+
+    /// Merge the PO file into the template. Obsolete entries of the PO file are in `po_extras`,
+    /// which are brought back if the template has them again, and kept as obsolete otherwise.
+    pub fn merge(
+        &self,
+        po_file: &Catalog,
+        po_extras: &PoFileExtras,
+        pot_file: &Catalog,
+    ) -> MergeResult {
+        let mut metadata = clone_metadata(&po_file.metadata);
+        metadata.pot_creation_date = pot_file.metadata.pot_creation_date.clone();
+
+        let mut result = MergeResult {
+            catalog: Catalog::new(metadata),
+            extras: PoFileExtras::default(),
+            stats: MergeStats::default(),
+        };
+
+        let nplurals = po_file.metadata.plural_rules.nplurals;
+        let candidates: Vec<&dyn MessageView> = po_file
+            .messages()
+            .filter(|m| m.is_translated() && !m.msgid().is_empty())
+            .collect();
+
+        let obsolete: HashMap<String, &Message> = po_extras
+            .obsolete
+            .iter()
+            .map(|message| (message_key(message), message))
+            .collect();
+
+        let mut used_keys = HashSet::new();
+        for template in pot_file.messages() {
+            let exact_match = find_exact_match(po_file, template).or_else(|| {
+                obsolete
+                    .get(&message_key(template))
+                    .map(|message| *message as &dyn MessageView)
+            });
+            let (message, previous) = match exact_match {
+                Some(definition) => {
+                    if definition.is_translated() {
+                        result.stats.matched_count += 1;
+                    } else {
+                        result.stats.untranslated_count += 1;
+                    }
+                    used_keys.insert(message_key(definition));
+                    (merge_message(template, definition, nplurals), None)
+                }
+                None => match self.find_fuzzy_match(&candidates, template) {
+                    Some(definition) => {
+                        result.stats.fuzzy_count += 1;
+                        used_keys.insert(message_key(definition));
+
+                        let mut message = merge_message(template, definition, nplurals);
+                        message.flags_mut().add_flag("fuzzy");
+                        (message, Some(previous_message(definition)))
+                    }
+                    None => {
+                        result.stats.untranslated_count += 1;
+                        (untranslated_message(template, nplurals), None)
+                    }
+                },
+            };
+
+            if let Some(previous) = previous {
+                if self.config.keep_previous {
+                    result
+                        .extras
+                        .previous
+                        .insert(message_key(&message), previous);
+                }
+            }
+            result.catalog.append_or_update(message);
+        }
+
+        // Same as msgmerge, untranslated messages that are gone from the template are dropped,
+        // and translated ones are kept as obsolete.
+        for definition in po_file.messages() {
+            if definition.is_translated() && used_keys.insert(message_key(definition)) {
+                result.stats.obsolete_count += 1;
+                result.extras.obsolete.push(copy_message(definition));
+            }
+        }
+
+        // Obsolete entries of the PO file are kept after the newly obsoleted ones.
+        for definition in &po_extras.obsolete {
+            if used_keys.insert(message_key(definition)) {
+                result.stats.obsolete_count += 1;
+                result.extras.obsolete.push(copy_message(definition));
+            }
+        }
+
+        result
+    }
+
+    fn find_fuzzy_match<'a>(
+        &self,
+        candidates: &[&'a dyn MessageView],
+        template: &dyn MessageView,
+    ) -> Option<&'a dyn MessageView> {
+        if !self.config.fuzzy_matching
+            || template.msgid().is_empty()
+            || !self
+                .config
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.is_match(template))
+        {
+            return None;
+        }
+
+        let template_msgid: Vec<char> = template.msgid().chars().collect();
+        let mut best_match = None;
+        let mut best_score = self.config.fuzzy_threshold;
+        for candidate in candidates {
+            let candidate_msgid: Vec<char> = candidate.msgid().chars().collect();
+            if similarity_upper_bound(&template_msgid, &candidate_msgid) < best_score {
+                continue;
+            }
+
+            let score = similarity(&template_msgid, &candidate_msgid);
+            if score >= best_score && best_match.is_none() || score > best_score {
+                best_score = score;
+                best_match = Some(*candidate);
+            }
+        }
+
+        best_match
+    }
+}
+
+fn find_exact_match<'a>(
+    po_file: &'a Catalog,
+    template: &dyn MessageView,
+) -> Option<&'a dyn MessageView> {
+    let msgctxt = Some(template.msgctxt()).filter(|s| !s.is_empty());
+    po_file.find_message(msgctxt, template.msgid(), template.msgid_plural().ok())
+}
+
+/// Create the merged message: everything comes from the template, except translations and
+/// translator flags.
+fn merge_message(
+    template: &dyn MessageView,
+    definition: &dyn MessageView,
+    nplurals: usize,
+) -> Message {
+    let mut flags = template.flags().clone();
+    flags.remove_flag("fuzzy");
+    if definition.is_fuzzy() {
+        flags.add_flag("fuzzy");
+    }
+
+    let mut builder = new_message_builder(template, flags);
+    match (template.is_plural(), definition.is_plural()) {
+        (false, false) => builder.with_msgstr(definition.msgstr().unwrap().to_string()),
+        (false, true) => builder.with_msgstr(definition.msgstr_plural().unwrap()[0].clone()),
+        (true, true) => builder.with_msgstr_plural(definition.msgstr_plural().unwrap().clone()),
+        (true, false) => {
+            let mut msgstr_plural = vec![String::new(); nplurals.max(1)];
+            msgstr_plural[0] = definition.msgstr().unwrap().to_string();
+            builder.with_msgstr_plural(msgstr_plural)
+        }
+    };
+
+    builder.done()
+}
+
+fn untranslated_message(template: &dyn MessageView, nplurals: usize) -> Message {
+    let mut flags = template.flags().clone();
+    flags.remove_flag("fuzzy");
+
+    let mut builder = new_message_builder(template, flags);
+    if template.is_plural() {
+        builder.with_msgstr_plural(vec![String::new(); nplurals.max(1)]);
+    }
+
+    builder.done()
+}
+
+fn new_message_builder(template: &dyn MessageView, flags: MessageFlags) -> MessageBuilder {
+    let mut builder = if template.is_plural() {
+        Message::build_plural()
+    } else {
+        Message::build_singular()
+    };
+
+    builder
+        .with_comments(template.comments().to_string())
+        .with_source(template.source().to_string())
+        .with_flags(flags)
+        .with_msgctxt(template.msgctxt().to_string())
+        .with_msgid(template.msgid().to_string());
+
+    if let Ok(msgid_plural) = template.msgid_plural() {
+        builder.with_msgid_plural(msgid_plural.to_string());
+    }
+
+    builder
+}
+
+fn previous_message(definition: &dyn MessageView) -> PreviousMessage {
+    PreviousMessage {
+        msgctxt: Some(definition.msgctxt().to_string()).filter(|s| !s.is_empty()),
+        msgid: definition.msgid().to_string(),
+        msgid_plural: definition.msgid_plural().ok().map(|s| s.to_string()),
+    }
+}
+
+pub(crate) fn copy_message(message: &dyn MessageView) -> Message {
+    let mut builder = new_message_builder(message, message.flags().clone());
+    match (message.msgstr(), message.msgstr_plural()) {
+        (Ok(msgstr), _) => builder.with_msgstr(msgstr.to_string()),
+        (_, Ok(msgstr_plural)) => builder.with_msgstr_plural(msgstr_plural.clone()),
+        _ => &mut builder,
+    };
+    builder.done()
+}
+
+pub(crate) fn clone_metadata(metadata: &CatalogMetadata) -> CatalogMetadata {
+    let mut cloned = CatalogMetadata::new();
+    cloned.project_id_version = metadata.project_id_version.clone();
+    cloned.pot_creation_date = metadata.pot_creation_date.clone();
+    cloned.po_revision_date = metadata.po_revision_date.clone();
+    cloned.last_translator = metadata.last_translator.clone();
+    cloned.language_team = metadata.language_team.clone();
+    cloned.mime_version = metadata.mime_version.clone();
+    cloned.content_type = metadata.content_type.clone();
+    cloned.content_transfer_encoding = metadata.content_transfer_encoding.clone();
+    cloned.language = metadata.language.clone();
+    cloned.plural_rules.nplurals = metadata.plural_rules.nplurals;
+    cloned.plural_rules.expr = metadata.plural_rules.expr.clone();
+    cloned
+}
+
+fn similarity_upper_bound(a: &[char], b: &[char]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    2.0 * a.len().min(b.len()) as f64 / (a.len() + b.len()) as f64
+}
+
+/// Similarity of 2 strings in [0, 1], computed in the same way as gettext's `fstrcmp`:
+/// 2 * (length of longest common subsequence) / (total length).
+pub(crate) fn similarity(a: &[char], b: &[char]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    let mut prev_row = vec![0usize; b.len() + 1];
+    let mut row = vec![0usize; b.len() + 1];
+    for ca in a {
+        for (j, cb) in b.iter().enumerate() {
+            row[j + 1] = if ca == cb {
+                prev_row[j] + 1
+            } else {
+                row[j].max(prev_row[j + 1])
+            };
+        }
+        std::mem::swap(&mut prev_row, &mut row);
+    }
+
+    2.0 * prev_row[b.len()] as f64 / (a.len() + b.len()) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn similarity_should_match_fstrcmp() {
+        assert_eq!(similarity(&chars("abc"), &chars("abc")), 1.0);
+        assert_eq!(similarity(&chars("abc"), &chars("xyz")), 0.0);
+        assert_eq!(similarity(&chars("abcd"), &chars("abce")), 0.75);
+        assert_eq!(similarity(&chars(""), &chars("")), 1.0);
+    }
+}
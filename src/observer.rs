@@ -0,0 +1,36 @@
+use crate::{error::PotrError, potr::SkipReason, report::MessageReport};
+use polib::message::MessageView;
+use serde::Serialize;
+
+/// Progress of a translation run, sent after each message is visited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Progress {
+    /// Number of messages visited so far, including the skipped ones.
+    pub visited_count: usize,
+    pub total_count: usize,
+    pub translated_count: usize,
+    pub skipped_count: usize,
+    pub failed_count: usize,
+}
+
+/// Receives the events of a translation run, e.g. to show the progress in a GUI, or to collect
+/// the results in build tooling without scraping the logs.
+///
+/// All methods do nothing by default, so only the events of interest need to be implemented.
+/// They are called on the task running the translation, so they should return quickly.
+pub trait PotrObserver: Send + Sync {
+    /// The message passed all the filters and is about to be sent to the engine.
+    fn on_message_selected(&self, _message: &dyn MessageView) {}
+
+    fn on_message_skipped(&self, _message: &dyn MessageView, _reason: SkipReason) {}
+
+    /// The message has its new msgstr and flags, and the report has the usage and quality of the
+    /// translation.
+    fn on_message_translated(&self, _message: &dyn MessageView, _report: &MessageReport) {}
+
+    /// The message is left as it was. Errors of the engine are in `PotrError::Translate`, so
+    /// e.g. a bad API key can be told from a rate limit.
+    fn on_message_failed(&self, _message: &dyn MessageView, _error: &PotrError) {}
+
+    fn on_progress(&self, _progress: &Progress) {}
+}
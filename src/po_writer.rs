@@ -0,0 +1,592 @@
+use crate::error::PotrError;
+use polib::{
+    catalog::Catalog,
+    message::{Message, MessageFlags, MessageView},
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Default page width used by msgcat and msgmerge.
+pub const DEFAULT_PAGE_WIDTH: usize = 79;
+
+/// Previous msgctxt/msgid of a fuzzy message, written as `#|` comments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreviousMessage {
+    pub msgctxt: Option<String>,
+    pub msgid: String,
+    pub msgid_plural: Option<String>,
+}
+
+/// Data that polib `Catalog` cannot carry, but we still want to write into the PO file.
+#[derive(Debug, Default)]
+pub struct PoFileExtras {
+    /// Previous message of fuzzy messages, keyed by `message_key`.
+    pub previous: HashMap<String, PreviousMessage>,
+
+    /// Obsolete messages, written as `#~` entries at the end of the file.
+    pub obsolete: Vec<Message>,
+}
+
+/// Generate a unique key of a message in the same way as gettext does.
+pub fn message_key(message: &dyn MessageView) -> String {
+    let mut key = String::new();
+    if !message.msgctxt().is_empty() {
+        key.push_str(message.msgctxt());
+        key.push('\u{0004}');
+    }
+    key.push_str(message.msgid());
+    if let Ok(msgid_plural) = message.msgid_plural() {
+        key.push('\u{0000}');
+        key.push_str(msgid_plural);
+    }
+    key
+}
+
+/// Parse the obsolete (`#~`) entries in the content of a PO file, which are dropped by the PO
+/// parser. Their flags and fields are kept, while the previous msgid (`#~|`) is not.
+pub fn parse_obsolete(content: &str) -> Vec<Message> {
+    let mut messages = vec![];
+    let mut flags = MessageFlags::new();
+    let mut fields: Vec<(String, String)> = vec![];
+    for line in content.lines() {
+        let line = line.trim_end();
+        if line.starts_with("#~|") {
+            continue;
+        }
+
+        if let Some(value) = line.strip_prefix("#~ ").filter(|v| v.starts_with('"')) {
+            if let Some((_, field)) = fields.last_mut() {
+                field.push_str(&unquote(value));
+            }
+            continue;
+        }
+
+        if let Some((keyword, value)) = line.strip_prefix("#~ ").and_then(|l| l.split_once(' ')) {
+            // msgctxt, or msgid without msgctxt, starts a new entry.
+            let starts_entry = keyword == "msgctxt"
+                || keyword == "msgid" && !matches!(fields.as_slice(), [(k, _)] if k == "msgctxt");
+            if starts_entry && !fields.is_empty() {
+                messages.extend(obsolete_message(std::mem::take(&mut flags), &fields));
+                fields.clear();
+            }
+            fields.push((keyword.to_string(), unquote(value)));
+            continue;
+        }
+
+        messages.extend(obsolete_message(std::mem::take(&mut flags), &fields));
+        fields.clear();
+
+        // Flags are written before the fields of the entry.
+        if let Some(line_flags) = line.strip_prefix("#, ") {
+            for flag in line_flags
+                .split(',')
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+            {
+                flags.add_flag(flag);
+            }
+        }
+    }
+    messages.extend(obsolete_message(flags, &fields));
+
+    messages
+}
+
+fn obsolete_message(flags: MessageFlags, fields: &[(String, String)]) -> Option<Message> {
+    let field = |keyword: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == keyword)
+            .map(|(_, v)| v.clone())
+    };
+
+    let msgid = field("msgid")?;
+    let mut builder = match field("msgid_plural") {
+        Some(msgid_plural) => {
+            let mut builder = Message::build_plural();
+            builder.with_msgid_plural(msgid_plural).with_msgstr_plural(
+                fields
+                    .iter()
+                    .filter(|(k, _)| k.starts_with("msgstr["))
+                    .map(|(_, v)| v.clone())
+                    .collect(),
+            );
+            builder
+        }
+        None => {
+            let mut builder = Message::build_singular();
+            builder.with_msgstr(field("msgstr").unwrap_or_default());
+            builder
+        }
+    };
+    builder
+        .with_flags(flags)
+        .with_msgctxt(field("msgctxt").unwrap_or_default())
+        .with_msgid(msgid);
+    Some(builder.done())
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let value = value.strip_prefix('"').unwrap_or(value);
+    let value = value.strip_suffix('"').unwrap_or(value);
+
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('a') => result.push('\u{07}'),
+            Some('b') => result.push('\u{08}'),
+            Some('v') => result.push('\u{0B}'),
+            Some('f') => result.push('\u{0C}'),
+            Some(c) => result.push(c),
+            None => {}
+        }
+    }
+    result
+}
+
+/// PO file writer that produces the same layout and line wrapping as GNU msgcat.
+pub struct PoWriter {
+    page_width: usize,
+}
+
+impl Default for PoWriter {
+    fn default() -> Self {
+        Self::new(DEFAULT_PAGE_WIDTH)
+    }
+}
+
+impl PoWriter {
+    pub fn new(page_width: usize) -> PoWriter {
+        PoWriter { page_width }
+    }
+
+    pub fn write(
+        &self,
+        catalog: &Catalog,
+        extras: &PoFileExtras,
+        path: &Path,
+    ) -> Result<(), PotrError> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(self.format(catalog, extras).as_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn format(&self, catalog: &Catalog, extras: &PoFileExtras) -> String {
+        let mut output = String::new();
+
+        self.format_field(&mut output, "", "msgid", "");
+        self.format_field(&mut output, "", "msgstr", &catalog.metadata.export_for_po());
+
+        for message in catalog.messages() {
+            output.push('\n');
+            self.format_message(
+                &mut output,
+                message,
+                extras.previous.get(&message_key(message)),
+                "",
+            );
+        }
+
+        for message in &extras.obsolete {
+            output.push('\n');
+            self.format_message(&mut output, message, None, "#~ ");
+        }
+
+        output
+    }
+
+    fn format_message(
+        &self,
+        output: &mut String,
+        message: &dyn MessageView,
+        previous: Option<&PreviousMessage>,
+        prefix: &str,
+    ) {
+        // Like msgmerge, extracted comments and references are not kept for obsolete messages.
+        if prefix.is_empty() {
+            if !message.comments().is_empty() {
+                for line in message.comments().split('\n') {
+                    if line.is_empty() {
+                        output.push_str("#.\n");
+                    } else {
+                        output.push_str(&format!("#. {}\n", line));
+                    }
+                }
+            }
+
+            if !message.source().is_empty() {
+                self.format_references(output, message.source());
+            }
+        }
+
+        let flags = ordered_flags(message);
+        if !flags.is_empty() {
+            output.push_str(&format!("#, {}\n", flags.join(", ")));
+        }
+
+        if let Some(previous) = previous {
+            if let Some(msgctxt) = &previous.msgctxt {
+                self.format_field(output, "#| ", "msgctxt", msgctxt);
+            }
+            self.format_field(output, "#| ", "msgid", &previous.msgid);
+            if let Some(msgid_plural) = &previous.msgid_plural {
+                self.format_field(output, "#| ", "msgid_plural", msgid_plural);
+            }
+        }
+
+        if !message.msgctxt().is_empty() {
+            self.format_field(output, prefix, "msgctxt", message.msgctxt());
+        }
+
+        self.format_field(output, prefix, "msgid", message.msgid());
+        match (message.msgid_plural(), message.msgstr_plural()) {
+            (Ok(msgid_plural), Ok(msgstr_plural)) => {
+                self.format_field(output, prefix, "msgid_plural", msgid_plural);
+                for (index, msgstr) in msgstr_plural.iter().enumerate() {
+                    self.format_field(output, prefix, &format!("msgstr[{}]", index), msgstr);
+                }
+            }
+            _ => {
+                self.format_field(output, prefix, "msgstr", message.msgstr().unwrap_or(""));
+            }
+        }
+    }
+
+    fn format_references(&self, output: &mut String, source: &str) {
+        let mut line = String::new();
+        for reference in source.split_whitespace() {
+            if !line.is_empty() && line.len() + 1 + reference.len() > self.page_width {
+                output.push_str(&line);
+                output.push('\n');
+                line.clear();
+            }
+
+            if line.is_empty() {
+                line.push_str("#:");
+            }
+            line.push(' ');
+            line.push_str(reference);
+        }
+
+        if !line.is_empty() {
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+
+    fn format_field(&self, output: &mut String, prefix: &str, keyword: &str, value: &str) {
+        let portions: Vec<&str> = if value.is_empty() {
+            vec![""]
+        } else {
+            value.split_inclusive('\n').collect()
+        };
+
+        let header_width = display_width(prefix) + keyword.len() + 1;
+        if portions.len() == 1 {
+            let escaped = escape(portions[0]);
+            if header_width + display_width(&escaped) + 2 <= self.page_width {
+                output.push_str(&format!("{}{} \"{}\"\n", prefix, keyword, escaped));
+                return;
+            }
+        }
+
+        output.push_str(&format!("{}{} \"\"\n", prefix, keyword));
+        let max_width = self.page_width.saturating_sub(display_width(prefix) + 2);
+        for portion in portions {
+            for line in wrap(portion, max_width) {
+                output.push_str(&format!("{}\"{}\"\n", prefix, line));
+            }
+        }
+    }
+}
+
+/// msgcat always writes the fuzzy flag first.
+fn ordered_flags(message: &dyn MessageView) -> Vec<&str> {
+    let mut flags: Vec<&str> = message
+        .flags()
+        .iter()
+        .map(|flag| flag.as_str())
+        .filter(|flag| *flag != "fuzzy")
+        .collect();
+
+    if message.is_fuzzy() {
+        flags.insert(0, "fuzzy");
+    }
+
+    flags
+}
+
+fn escape(text: &str) -> String {
+    text.chars().map(escape_char).collect()
+}
+
+fn escape_char(c: char) -> String {
+    match c {
+        '\\' => String::from("\\\\"),
+        '"' => String::from("\\\""),
+        '\n' => String::from("\\n"),
+        '\r' => String::from("\\r"),
+        '\t' => String::from("\\t"),
+        '\u{07}' => String::from("\\a"),
+        '\u{08}' => String::from("\\b"),
+        '\u{0B}' => String::from("\\v"),
+        '\u{0C}' => String::from("\\f"),
+        _ => c.to_string(),
+    }
+}
+
+pub(crate) fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+fn char_width(c: char) -> usize {
+    if is_wide(c) {
+        2
+    } else {
+        1
+    }
+}
+
+fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x20000..=0x3FFFD)
+}
+
+/// Simplified version of the Unicode line breaking rules used by gettext: we break after spaces,
+/// and around CJK characters, except before closing or after opening punctuations.
+fn can_break_between(prev: char, next: char) -> bool {
+    const NO_BREAK_BEFORE: &str = ",.!?;:)]}、。，．：；！？）」』】〉》";
+    const NO_BREAK_AFTER: &str = "([{（「『【〈《";
+
+    if NO_BREAK_BEFORE.contains(next) || NO_BREAK_AFTER.contains(prev) {
+        return false;
+    }
+
+    if prev == ' ' {
+        return next != ' ';
+    }
+
+    next != ' ' && (is_wide(prev) || is_wide(next))
+}
+
+/// Wrap a single line (ending with at most one newline) into escaped pieces, so each piece fits
+/// into `max_width` columns whenever there is a break opportunity.
+fn wrap(portion: &str, max_width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    let mut last_break: Option<(usize, usize)> = None;
+    let mut prev: Option<char> = None;
+
+    for c in portion.chars() {
+        if let Some(prev) = prev {
+            if can_break_between(prev, c) {
+                last_break = Some((line.len(), line_width));
+            }
+        }
+        prev = Some(c);
+
+        let escaped = escape_char(c);
+        let width = display_width(&escaped);
+        if line_width + width > max_width {
+            if let Some((break_pos, break_width)) = last_break.take() {
+                if break_pos > 0 {
+                    let rest = line.split_off(break_pos);
+                    lines.push(line);
+                    line = rest;
+                    line_width -= break_width;
+                }
+            }
+        }
+
+        line.push_str(&escaped);
+        line_width += width;
+    }
+
+    lines.push(line);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use pretty_assertions::assert_eq;
+
+    fn format_message(message: &Message) -> String {
+        let mut output = String::new();
+        PoWriter::default().format_message(&mut output, message, None, "");
+        output
+    }
+
+    #[test]
+    fn obsolete_entries_should_be_parsed_back() {
+        let mut extras = PoFileExtras::default();
+        let mut flags = MessageFlags::new();
+        flags.add_flag("fuzzy");
+        extras.obsolete.push(
+            Message::build_singular()
+                .with_flags(flags)
+                .with_msgctxt(String::from("button"))
+                .with_msgid(String::from("Old button"))
+                .with_msgstr(String::from("Alter Knopf"))
+                .done(),
+        );
+        extras.obsolete.push(
+            Message::build_plural()
+                .with_msgid(String::from("One \"pen\""))
+                .with_msgid_plural(String::from(
+                    "Many pens, in a message long enough to be wrapped into multiple lines by the writer",
+                ))
+                .with_msgstr_plural(vec![String::from("Ein Stift"), String::from("Stifte\n")])
+                .done(),
+        );
+
+        let catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        let content = PoWriter::default().format(&catalog, &extras);
+        let content = format!("{}\n#: src/Pen.md:1\nmsgid \"Pen\"\nmsgstr \"\"\n", content);
+        let obsolete = parse_obsolete(&content);
+
+        assert_eq!(obsolete.len(), 2);
+        for (parsed, expected) in obsolete.iter().zip(&extras.obsolete) {
+            assert_eq!(format_message(parsed), format_message(expected));
+        }
+    }
+
+    #[test]
+    fn escaped_chars_in_obsolete_entries_should_be_parsed_back() {
+        let mut extras = PoFileExtras::default();
+        extras.obsolete.push(
+            Message::build_singular()
+                .with_msgid(String::from(
+                    "Escaped \\ \" \n \r \t \u{07} \u{08} \u{0B} \u{0C}",
+                ))
+                .with_msgstr(String::from("\r\n"))
+                .done(),
+        );
+
+        let catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        let content = PoWriter::default().format(&catalog, &extras);
+        let obsolete = parse_obsolete(&content);
+
+        assert_eq!(obsolete.len(), 1);
+        assert_eq!(obsolete[0].msgid(), extras.obsolete[0].msgid());
+        assert_eq!(
+            obsolete[0].msgstr().unwrap(),
+            extras.obsolete[0].msgstr().unwrap()
+        );
+        assert_eq!(
+            PoWriter::default().format(
+                &catalog,
+                &PoFileExtras {
+                    obsolete,
+                    ..Default::default()
+                }
+            ),
+            content
+        );
+    }
+
+    #[test]
+    fn short_message_should_be_written_in_single_line() {
+        let message = Message::build_singular()
+            .with_msgid(String::from("Hello \"world\"!\n"))
+            .with_msgstr(String::from("你好，世界！"))
+            .done();
+
+        assert_eq!(
+            format_message(&message),
+            "msgid \"Hello \\\"world\\\"!\\n\"\nmsgstr \"你好，世界！\"\n"
+        );
+    }
+
+    #[test]
+    fn multi_line_message_should_be_split_at_newlines() {
+        let message = Message::build_singular()
+            .with_msgid(String::from("```bash\n# This is a code block\n```"))
+            .done();
+
+        assert_eq!(
+            format_message(&message),
+            "msgid \"\"\n\"```bash\\n\"\n\"# This is a code block\\n\"\n\"```\"\nmsgstr \"\"\n"
+        );
+    }
+
+    #[test]
+    fn long_message_should_be_wrapped_at_spaces() {
+        let message = Message::build_singular()
+            .with_msgid("word ".repeat(20).trim_end().to_string())
+            .done();
+
+        assert_eq!(
+            format_message(&message),
+            format!(
+                "msgid \"\"\n\"{}\"\n\"{}\"\nmsgstr \"\"\n",
+                "word ".repeat(15),
+                "word ".repeat(5).trim_end()
+            )
+        );
+    }
+
+    #[test]
+    fn long_cjk_message_should_be_wrapped_by_display_width() {
+        let text = "这是一段很长的中文文本。".repeat(4);
+        let lines = wrap(&text, 77);
+        assert_eq!(lines.concat(), text);
+        assert!(lines.iter().all(|line| display_width(line) <= 77));
+        assert!(lines.iter().skip(1).all(|line| !line.starts_with('。')));
+    }
+
+    #[test]
+    fn references_should_be_wrapped_at_page_width() {
+        let mut output = String::new();
+        let source = (1..=8)
+            .map(|i| format!("src/some/long/path.md:{}", i))
+            .collect::<Vec<String>>()
+            .join("\n");
+        PoWriter::default().format_references(&mut output, &source);
+
+        assert!(output
+            .lines()
+            .all(|line| line.len() <= 79 && line.starts_with("#: ")));
+        assert_eq!(output.split_whitespace().filter(|s| *s != "#:").count(), 8);
+    }
+
+    #[test]
+    fn obsolete_message_should_be_prefixed() {
+        let message = Message::build_singular()
+            .with_msgid(String::from("Removed"))
+            .with_msgstr(String::from("Entfernt"))
+            .done();
+        let mut output = String::new();
+        PoWriter::default().format_message(&mut output, &message, None, "#~ ");
+
+        assert_eq!(output, "#~ msgid \"Removed\"\n#~ msgstr \"Entfernt\"\n");
+    }
+}
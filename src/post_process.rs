@@ -0,0 +1,210 @@
+use crate::translators::Language;
+use regex::Regex;
+
+/// Regex replacement applied to the translations, e.g. to fix punctuation of a language.
+#[derive(Debug, Clone)]
+pub struct ReplaceRule {
+    pub pattern: Regex,
+
+    /// Replacement text, which can refer to the capture groups, e.g. "$1".
+    pub replacement: String,
+
+    /// Only apply to this target language. `None` means all languages.
+    pub target_lang: Option<Language>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PostProcessConfig {
+    /// Make the leading and trailing whitespace and newlines the same as msgid.
+    pub normalize_whitespace: bool,
+
+    /// Remove the quotes, preambles (e.g. "Translation:") and code fences that wrap the whole
+    /// translation, but don't exist in msgid.
+    pub strip_wrappers: bool,
+
+    pub replace_rules: Vec<ReplaceRule>,
+}
+
+impl Default for PostProcessConfig {
+    fn default() -> Self {
+        Self {
+            normalize_whitespace: true,
+            strip_wrappers: true,
+            replace_rules: vec![],
+        }
+    }
+}
+
+/// Fixes up the common problems in the output of translators, before it is written to msgstr.
+#[derive(Debug, Clone)]
+pub struct PostProcessor {
+    pub config: PostProcessConfig,
+    pub target_lang: Language,
+    preamble_regex: Regex,
+}
+
+impl PostProcessor {
+    pub fn new(config: PostProcessConfig, target_lang: Language) -> PostProcessor {
+        PostProcessor {
+            config,
+            target_lang,
+            preamble_regex: Regex::new(
+                r"(?i)^\s*(translation|translated text|here is the translation[^:\n]*)\s*:\s*",
+            )
+            .unwrap(),
+        }
+    }
+
+    pub fn process(&self, source: &str, translated: String) -> String {
+        // Empty translations, e.g. from the clear engine, are kept as they are.
+        if translated.trim().is_empty() {
+            return translated;
+        }
+
+        let mut result = translated;
+        if self.config.strip_wrappers {
+            result = self.strip_wrappers(source, result);
+        }
+
+        if self.config.normalize_whitespace {
+            let leading = &source[..source.len() - source.trim_start().len()];
+            let trailing = &source[source.trim_end().len()..];
+            result = format!("{}{}{}", leading, result.trim(), trailing);
+        }
+
+        for rule in &self.config.replace_rules {
+            if rule.target_lang.is_none_or(|lang| lang == self.target_lang) {
+                result = rule
+                    .pattern
+                    .replace_all(&result, rule.replacement.as_str())
+                    .to_string();
+            }
+        }
+
+        result
+    }
+
+    fn strip_wrappers(&self, source: &str, translated: String) -> String {
+        const QUOTES: [(char, char); 5] = [
+            ('"', '"'),
+            ('\'', '\''),
+            ('“', '”'),
+            ('「', '」'),
+            ('«', '»'),
+        ];
+
+        let source = source.trim();
+        let mut result = translated.trim().to_string();
+        loop {
+            let mut stripped = result.clone();
+            if !self.preamble_regex.is_match(source) {
+                stripped = self.preamble_regex.replace(&stripped, "").to_string();
+            }
+
+            if stripped.starts_with("```")
+                && stripped.ends_with("```")
+                && !source.starts_with("```")
+            {
+                let inner = &stripped[3..stripped.len() - 3];
+                // Skip the language of the code fence, e.g. "```text".
+                stripped = match inner.split_once('\n') {
+                    Some((first_line, rest)) if !first_line.contains(' ') => rest.to_string(),
+                    _ => inner.to_string(),
+                };
+            }
+
+            // Quotes are only stripped when they wrap the whole text, e.g. not "A" and "B".
+            for (open, close) in QUOTES {
+                let inner = |text: &str| {
+                    let inner = text.strip_prefix(open)?.strip_suffix(close)?;
+                    (!inner.contains([open, close])).then_some(inner.to_string())
+                };
+                if let (Some(inner), None) = (inner(&stripped), inner(source)) {
+                    stripped = inner;
+                }
+            }
+
+            let stripped = stripped.trim().to_string();
+            if stripped == result || stripped.is_empty() {
+                return result;
+            }
+            result = stripped;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn process(source: &str, translated: &str) -> String {
+        PostProcessor::new(PostProcessConfig::default(), Language::German)
+            .process(source, translated.to_string())
+    }
+
+    #[test]
+    fn wrappers_should_be_stripped() {
+        assert_eq!(
+            process("This is a pen!", "\"Das ist ein Stift!\""),
+            "Das ist ein Stift!"
+        );
+        assert_eq!(
+            process("This is a pen!", "Translation: \"Das ist ein Stift!\""),
+            "Das ist ein Stift!"
+        );
+        assert_eq!(
+            process("This is a pen!", "```text\nDas ist ein Stift!\n```"),
+            "Das ist ein Stift!"
+        );
+
+        // Wrappers that exist in msgid are kept.
+        assert_eq!(process("\"Pen\"", "\"Stift\""), "\"Stift\"");
+        assert_eq!(
+            process("\"Pen\" or \"pencil\"", "\"Stift\" oder \"Bleistift\""),
+            "\"Stift\" oder \"Bleistift\""
+        );
+        assert_eq!(
+            process("```\necho pen\n```", "```\necho Stift\n```"),
+            "```\necho Stift\n```"
+        );
+    }
+
+    #[test]
+    fn whitespace_should_match_msgid() {
+        assert_eq!(
+            process("This is a pen!", "Das ist ein Stift!\n"),
+            "Das ist ein Stift!"
+        );
+        assert_eq!(
+            process(" This is a pen!\n", "Das ist ein Stift!"),
+            " Das ist ein Stift!\n"
+        );
+        assert_eq!(process("This is a pen!", ""), "");
+    }
+
+    #[test]
+    fn replace_rules_should_be_applied_for_target_language() {
+        let config = PostProcessConfig {
+            replace_rules: vec![
+                ReplaceRule {
+                    pattern: Regex::new(r"\s+!").unwrap(),
+                    replacement: "!".to_string(),
+                    target_lang: Some(Language::German),
+                },
+                ReplaceRule {
+                    pattern: Regex::new("Stift").unwrap(),
+                    replacement: "Kugelschreiber".to_string(),
+                    target_lang: Some(Language::French),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let processor = PostProcessor::new(config, Language::German);
+        assert_eq!(
+            processor.process("This is a pen!", "Das ist ein Stift !".to_string()),
+            "Das ist ein Stift!"
+        );
+    }
+}
@@ -1,22 +1,58 @@
-use crate::translators::{self, *};
+use crate::{
+    budget::{Budget, BudgetTracker, Usage},
+    dry_run::{self, PriceTable},
+    error::PotrError,
+    filter::MessageFilter,
+    formats::{self, CatalogFormat},
+    observer::{PotrObserver, Progress},
+    post_process::{PostProcessConfig, PostProcessor},
+    quality::{self, QualityConfig, QualityEstimate},
+    report::{MessageOutcome, MessageReport, RunReport},
+    rules::TranslationRule,
+    translators::{self, *},
+    validate::{ResponseValidator, ValidationConfig},
+};
 use anyhow::Result;
 use polib::{
     catalog::{Catalog, MessageMutProxy},
     message::{MessageMutView, MessageView},
 };
 use regex::Regex;
+use serde::Serialize;
 use std::{
+    fmt,
     path::Path,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
+/// Prefix of the comment lines that hold the notes of the model.
+const NOTES_COMMENT_PREFIX: &str = "potr-notes: ";
+
+/// Prefix of the comment lines that hold the back-translation of a low quality translation.
+const BACK_TRANSLATION_COMMENT_PREFIX: &str = "potr-back-translation: ";
+
+/// Translator of a rule, along with its config for usage estimation.
+type RuleTranslator = (Box<dyn Translator>, TranslatorConfig);
+
+/// Filters a message must match to be selected, each with the reason to skip it otherwise.
+type Selection = Vec<(SkipReason, MessageFilter)>;
+
 #[derive(Debug, Clone)]
 pub struct PotrConfig {
+    /// PO file, or the source file of the other formats.
     pub po_file_path: String,
+
+    /// Output file. For the formats other than PO, the existing translations are also loaded
+    /// from it.
     pub output_file_path: String,
+
+    /// Format of the files. `None` means it is decided by the extension of the source file.
+    pub format: Option<CatalogFormat>,
+
     pub skip_translation: bool,
     pub skip_translated: bool,
     pub skip_code_blocks: bool,
@@ -27,6 +63,25 @@ pub struct PotrConfig {
     pub exclude_message_regex: Option<Regex>,
     pub message_limit: i32,
     pub as_fuzzy: bool,
+    pub filter: Option<MessageFilter>,
+    pub rules: Vec<TranslationRule>,
+    pub budget: Budget,
+    pub prices: PriceTable,
+
+    /// JSON file to write the run report to.
+    pub report_file_path: Option<String>,
+
+    /// Fix-ups applied to the translator output.
+    pub post_process: PostProcessConfig,
+
+    /// Checks of the translator output, e.g. refusals or untranslated text.
+    pub validation: ValidationConfig,
+
+    /// Write the notes of the model into the comments of the message, in JSON output mode.
+    pub store_notes: bool,
+
+    /// Quality estimation by back-translation.
+    pub quality: QualityConfig,
 }
 
 impl Default for PotrConfig {
@@ -34,6 +89,7 @@ impl Default for PotrConfig {
         Self {
             po_file_path: Default::default(),
             output_file_path: Default::default(),
+            format: None,
             skip_translation: false,
             skip_translated: true,
             skip_code_blocks: true,
@@ -44,53 +100,260 @@ impl Default for PotrConfig {
             exclude_message_regex: None,
             message_limit: 0,
             as_fuzzy: false,
+            filter: None,
+            rules: vec![],
+            budget: Budget::default(),
+            prices: PriceTable::default(),
+            report_file_path: None,
+            post_process: PostProcessConfig::default(),
+            validation: ValidationConfig::default(),
+            store_notes: false,
+            quality: QualityConfig::default(),
         }
     }
 }
 
+impl PotrConfig {
+    /// The skip flags and regexes are shorthands of filters, so they are checked in the same way
+    /// as `filter`, in the order of the skip reasons. Settings of the rule override the config.
+    fn selection(&self, rule: Option<&TranslationRule>) -> Selection {
+        let skip_translated = rule
+            .and_then(|r| r.skip_translated)
+            .unwrap_or(self.skip_translated);
+        let skip_code_blocks = rule
+            .and_then(|r| r.skip_code_blocks)
+            .unwrap_or(self.skip_code_blocks);
+        let skip_text = rule.and_then(|r| r.skip_text).unwrap_or(self.skip_text);
+
+        let flags = [
+            (skip_translated, SkipReason::Translated, "!translated"),
+            (self.skip_non_fuzzy, SkipReason::NonFuzzy, "fuzzy"),
+            (skip_code_blocks, SkipReason::CodeBlock, "!code_block"),
+            (skip_text, SkipReason::Text, "code_block"),
+        ];
+        let mut selection: Selection = flags
+            .into_iter()
+            .filter(|(enabled, _, _)| *enabled)
+            .map(|(_, reason, filter)| (reason, MessageFilter::parse(filter).unwrap()))
+            .collect();
+
+        let regexes = [
+            (&self.source_regex, SkipReason::Source, "source_ref", false),
+            (
+                &self.include_message_regex,
+                SkipReason::Include,
+                "msgid",
+                false,
+            ),
+            (
+                &self.exclude_message_regex,
+                SkipReason::Exclude,
+                "msgid",
+                true,
+            ),
+        ];
+        for (regex, reason, field, negated) in regexes {
+            if let Some(regex) = regex {
+                selection.push((reason, MessageFilter::regex_match(field, regex, negated)));
+            }
+        }
+
+        if let Some(filter) = &self.filter {
+            selection.push((SkipReason::Filter, filter.clone()));
+        }
+
+        selection
+    }
+}
+
+/// Why a message is skipped from translation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    Rule,
+    Translated,
+    NonFuzzy,
+    CodeBlock,
+    Text,
+    Source,
+    Include,
+    Exclude,
+    Filter,
+    Limit,
+    Budget,
+    Canceled,
+    Aborted,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            SkipReason::Rule => "skip rule",
+            SkipReason::Translated => "translated",
+            SkipReason::NonFuzzy => "non fuzzy",
+            SkipReason::CodeBlock => "code block",
+            SkipReason::Text => "text",
+            SkipReason::Source => "source not matched",
+            SkipReason::Include => "include not matched",
+            SkipReason::Exclude => "exclude matched",
+            SkipReason::Filter => "filter not matched",
+            SkipReason::Limit => "limit reached",
+            SkipReason::Budget => "budget exceeded",
+            SkipReason::Canceled => "canceled",
+            SkipReason::Aborted => "aborted by fatal error",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
 pub struct Potr {
     pub config: PotrConfig,
     pub translator_config: TranslatorConfig,
     pub is_canceled: Arc<AtomicBool>,
 
-    source_parser_regex: Regex,
+    /// Selection without a rule, followed by the ones of each rule.
+    selections: Vec<Selection>,
+    post_processor: PostProcessor,
+    validator: ResponseValidator,
+    observers: Vec<Arc<dyn PotrObserver>>,
+
+    /// Translator injected by the caller, used instead of creating one from translator config.
+    translator: Option<Box<dyn Translator>>,
 }
 
 impl Potr {
     pub fn new(config: PotrConfig, translator_config: TranslatorConfig) -> Potr {
+        let post_processor =
+            PostProcessor::new(config.post_process.clone(), translator_config.target_lang);
+        let validator =
+            ResponseValidator::new(config.validation.clone(), translator_config.target_lang);
+        let selections = std::iter::once(None)
+            .chain(config.rules.iter().map(Some))
+            .map(|rule| config.selection(rule))
+            .collect();
         Potr {
             config,
             translator_config,
             is_canceled: Arc::new(AtomicBool::new(false)),
-            source_parser_regex: Regex::new(r"[^:]+:\d+").unwrap(),
+            selections,
+            post_processor,
+            validator,
+            observers: vec![],
+            translator: None,
         }
     }
 
+    /// Use the translator instead of creating one from translator config, e.g. a mock in tests or
+    /// an engine of the integration. Translator config is still used for its settings, e.g. the
+    /// target language and the usage estimation, while its engine is taken from the translator.
+    pub fn with_translator(
+        config: PotrConfig,
+        mut translator_config: TranslatorConfig,
+        translator: Box<dyn Translator>,
+    ) -> Potr {
+        translator_config.engine = translator.name();
+        let mut potr = Potr::new(config, translator_config);
+        potr.translator = Some(translator);
+        potr
+    }
+
     pub fn cancel_flag(&self) -> Arc<AtomicBool> {
         self.is_canceled.clone()
     }
 
-    pub async fn run(&self) -> Result<()> {
-        let mut po_file = self.load_po_catelog()?;
-        self.translate(&mut po_file).await?;
+    /// Receive the events of the following runs.
+    pub fn add_observer(&mut self, observer: Arc<dyn PotrObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Translate the file and write the output. When the run is stopped by a fatal engine
+    /// error, e.g. a bad API key, the messages translated so far are still written.
+    pub async fn run(&self) -> Result<(), PotrError> {
+        let start_time = Instant::now();
+        let mut po_file = self.load_catalog()?;
+        let (mut report, fatal_error) = self.translate_with_report(&mut po_file).await?;
         self.write_output_file(po_file)?;
-        Ok(())
+
+        if let Some(report_file_path) = &self.config.report_file_path {
+            report.duration_ms = start_time.elapsed().as_millis() as u64;
+            report.write(Path::new(report_file_path))?;
+        }
+
+        match fatal_error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Translate the messages of a catalog in memory, e.g. one parsed by the caller. The file
+    /// paths in the config are not used, and the report is returned instead of being written.
+    pub async fn translate_catalog(&self, catalog: &mut Catalog) -> Result<RunReport, PotrError> {
+        match self.translate_with_report(catalog).await? {
+            (_, Some(e)) => Err(e.into()),
+            (report, None) => Ok(report),
+        }
+    }
+
+    /// Translate the catalog, and return the report along with the fatal error that stopped the
+    /// run, if any.
+    async fn translate_with_report(
+        &self,
+        catalog: &mut Catalog,
+    ) -> Result<(RunReport, Option<TranslateError>)> {
+        let started_at = SystemTime::now();
+        let start_time = Instant::now();
+        let mut report = RunReport {
+            po_file_path: self.config.po_file_path.clone(),
+            output_file_path: self.config.output_file_path.clone(),
+            engine: self.translator_config.engine,
+            model: dry_run::model_name(&self.translator_config),
+            started_at: started_at.duration_since(UNIX_EPOCH)?.as_secs(),
+            duration_ms: 0,
+            totals: Default::default(),
+            messages: vec![],
+        };
+
+        let fatal_error = self.translate(catalog, &mut report).await?;
+        report.duration_ms = start_time.elapsed().as_millis() as u64;
+        Ok((report, fatal_error))
+    }
+
+    pub(crate) fn load_catalog(&self) -> Result<Catalog, PotrError> {
+        let format = self.catalog_format();
+        tracing::info!("Loading {} file: {}", format, self.config.po_file_path);
+        formats::create_backend(format).load(
+            Path::new(&self.config.po_file_path),
+            Path::new(&self.config.output_file_path),
+        )
     }
 
-    fn load_po_catelog(&self) -> Result<Catalog, anyhow::Error> {
-        tracing::info!("Loading po file: {}", self.config.po_file_path);
-        let po_file = polib::po_file::parse(Path::new(&self.config.po_file_path))?;
-        Ok(po_file)
+    fn catalog_format(&self) -> CatalogFormat {
+        self.config
+            .format
+            .unwrap_or_else(|| CatalogFormat::from_path(&self.config.po_file_path))
     }
 
-    async fn translate(&self, po_file: &mut Catalog) -> Result<()> {
-        let translator = translators::create(self.translator_config.clone())?;
+    async fn translate(
+        &self,
+        po_file: &mut Catalog,
+        report: &mut RunReport,
+    ) -> Result<Option<TranslateError>> {
+        let created_translator;
+        let translator = match &self.translator {
+            Some(translator) => translator.as_ref(),
+            None => {
+                created_translator = translators::create(self.translator_config.clone())?;
+                created_translator.as_ref()
+            }
+        };
+        let rule_translators = self.create_rule_translators()?;
+        let back_translator = self.create_back_translator()?;
         if self.config.skip_translation {
             tracing::info!(
                 "Traslation skipped: TotalMessageCount = {}",
                 po_file.count()
             );
-            return Ok(());
+            return Ok(None);
         }
 
         tracing::info!(
@@ -98,21 +361,120 @@ impl Potr {
             po_file.count()
         );
 
+        let total_count = po_file.count();
         let mut processed_count = 0;
         let mut translated_count = 0;
         let mut failed_count = 0;
+        let mut budget = BudgetTracker::new(self.config.budget);
+        let mut fatal_error = None;
+
+        // Once the run is stopped, e.g. canceled or limit reached, the rest of the messages are
+        // still visited, so they are recorded as skipped in the report.
+        let mut stop_reason = None;
         for mut message in po_file.messages_mut() {
-            if self.is_canceled.load(Ordering::SeqCst) {
-                break;
+            if stop_reason.is_none() && self.is_canceled.load(Ordering::SeqCst) {
+                stop_reason = Some(SkipReason::Canceled);
+            }
+
+            let mut message_report = MessageReport {
+                msgid: message.msgid().to_string(),
+                msgctxt: message.msgctxt().to_string(),
+                source: message.source().to_string(),
+                outcome: MessageOutcome::Translated,
+                engine: None,
+                model: None,
+                duration_ms: None,
+                usage: None,
+                notes: None,
+                confidence: None,
+                quality_score: None,
+                back_translation: None,
+            };
+
+            let rule_index = self.find_rule(&message);
+            let rule = rule_index.map(|index| &self.config.rules[index]);
+            let (message_translator, translator_config) =
+                match rule_index.and_then(|index| rule_translators[index].as_ref()) {
+                    Some((translator, config)) => (translator.as_ref(), config),
+                    None => (translator, &self.translator_config),
+                };
+
+            // Check the budget with the estimated usage before sending the request.
+            let estimated_usage =
+                dry_run::estimate_usage(translator_config, message.msgid(), &self.config.prices);
+            let mut skip_reason = self.skip_reason(&message, rule_index).or(stop_reason);
+            if skip_reason.is_none() {
+                if let Err(e) = budget.check(&estimated_usage) {
+                    tracing::info!("Budget limit reached, stop translating: {}", e);
+                    stop_reason = Some(SkipReason::Budget);
+                    skip_reason = stop_reason;
+                }
+            }
+
+            if let Some(reason) = skip_reason {
+                tracing::debug!(
+                    "Skip message: Reason = {}, Message = {}, Source = {}",
+                    reason,
+                    message.msgid(),
+                    message.source()
+                );
+                self.notify(|observer| observer.on_message_skipped(&message, reason));
+                message_report.outcome = MessageOutcome::Skipped { reason };
+                report.add_message(message_report);
+                self.notify_progress(report, total_count);
+                if stop_reason.is_none() {
+                    processed_count += 1;
+                }
+                continue;
             }
 
-            match self.translate_message(&translator, &mut message).await {
-                Ok(translated) => {
-                    if translated {
-                        translated_count += 1;
+            self.notify(|observer| observer.on_message_selected(&message));
+            message_report.engine = Some(translator_config.engine);
+            message_report.model = dry_run::model_name(translator_config);
+            let start_time = Instant::now();
+            match self
+                .translate_message(message_translator, translator_config, rule, &mut message)
+                .await
+            {
+                Ok(translation) => {
+                    translated_count += 1;
+
+                    let mut usage =
+                        self.actual_usage(translator_config, estimated_usage, translation.usage);
+                    message_report.notes = translation.notes;
+                    message_report.confidence = translation.confidence;
+
+                    if let Some((back_translator, back_config)) =
+                        back_translator.as_ref().filter(|_| {
+                            self.config
+                                .quality
+                                .should_estimate(translator_config.engine)
+                        })
+                    {
+                        match self
+                            .estimate_quality(back_translator.as_ref(), back_config, &mut message)
+                            .await
+                        {
+                            Ok((estimate, back_usage)) => {
+                                usage += back_usage;
+                                message_report.quality_score = Some(estimate.score);
+                                message_report.back_translation = Some(estimate.back_translation);
+                            }
+                            Err(e) => {
+                                tracing::warn!(
+                                    "Failed to estimate translation quality: Error = {}, Message = {}",
+                                    e,
+                                    message.msgid()
+                                );
+                            }
+                        }
                     }
+
+                    budget.add(usage);
+                    message_report.usage = Some(usage);
                 }
                 Err(e) => {
+                    let e = PotrError::from(e);
                     failed_count += 1;
 
                     tracing::error!(
@@ -120,8 +482,27 @@ impl Potr {
                         e,
                         message.msgid()
                     );
+                    self.notify(|observer| observer.on_message_failed(&message, &e));
+                    message_report.outcome = MessageOutcome::Failed {
+                        error: e.to_string(),
+                    };
+
+                    // The other messages will fail in the same way, so stop the run.
+                    if let PotrError::Translate(e) = e {
+                        if e.is_fatal() {
+                            tracing::error!("Fatal error, stop translating: {}", e);
+                            stop_reason = Some(SkipReason::Aborted);
+                            fatal_error = Some(e);
+                        }
+                    }
                 }
             }
+            message_report.duration_ms = Some(start_time.elapsed().as_millis() as u64);
+            if message_report.outcome == MessageOutcome::Translated {
+                self.notify(|observer| observer.on_message_translated(&message, &message_report));
+            }
+            report.add_message(message_report);
+            self.notify_progress(report, total_count);
 
             processed_count += 1;
             if processed_count % 10 == 0 {
@@ -135,7 +516,7 @@ impl Potr {
 
             if self.config.message_limit > 0 && translated_count >= self.config.message_limit {
                 tracing::info!("Message limit reached: {}", translated_count);
-                break;
+                stop_reason = Some(SkipReason::Limit);
             }
         }
 
@@ -145,100 +526,277 @@ impl Potr {
             translated_count,
             failed_count
         );
+        log_usage(&budget.spent);
+        report.totals.usage = budget.spent;
+
+        Ok(fatal_error)
+    }
+
+    fn notify(&self, event: impl Fn(&dyn PotrObserver)) {
+        for observer in &self.observers {
+            event(observer.as_ref());
+        }
+    }
+
+    fn notify_progress(&self, report: &RunReport, total_count: usize) {
+        let progress = Progress {
+            visited_count: report.totals.message_count,
+            total_count,
+            translated_count: report.totals.translated_count,
+            skipped_count: report.totals.skipped_count,
+            failed_count: report.totals.failed_count,
+        };
+        self.notify(|observer| observer.on_progress(&progress));
+    }
+
+    /// Rules that need a different translator get their own one, so engine, model, prompt and
+    /// glossary can be changed per message.
+    fn create_rule_translators(&self) -> Result<Vec<Option<RuleTranslator>>> {
+        self.config
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.to_translator_config(&self.translator_config)
+                    .map(|config| Ok((translators::create(config.clone())?, config)))
+                    .transpose()
+            })
+            .collect()
+    }
+
+    /// Translator used for back-translation, when quality estimation is enabled.
+    fn create_back_translator(&self) -> Result<Option<RuleTranslator>> {
+        if !self.config.quality.enabled {
+            return Ok(None);
+        }
+
+        let config = self
+            .config
+            .quality
+            .to_translator_config(&self.translator_config);
+        Ok(Some((translators::create(config.clone())?, config)))
+    }
 
-        Ok(())
+    pub(crate) fn find_rule(&self, message: &dyn MessageView) -> Option<usize> {
+        self.config
+            .rules
+            .iter()
+            .position(|rule| rule.is_match(message))
     }
 
     async fn translate_message<'a>(
         &self,
-        translator: &Box<dyn Translator>,
+        translator: &dyn Translator,
+        translator_config: &TranslatorConfig,
+        rule: Option<&TranslationRule>,
         message: &mut MessageMutProxy<'a>,
-    ) -> Result<bool> {
-        if !self.should_translate_message(message) {
-            return Ok(false);
+    ) -> Result<Translation> {
+        tracing::debug!("Translating message: {}", message.msgid());
+        let mut translation = translator.translate_detailed(message.msgid()).await?;
+        tracing::debug!("Translation completed: Result = {}\n", translation.text);
+        translation.text = self
+            .post_processor
+            .process(message.msgid(), translation.text);
+
+        if self.validator.should_validate(translator_config.engine) {
+            let mut retry_count = 0;
+            while let Err(e) = self.validator.validate(message.msgid(), &translation.text) {
+                let invalid_translation = || {
+                    TranslateError::InvalidResponse(format!(
+                        "Invalid translation: {}, Result = {}",
+                        e, translation.text
+                    ))
+                };
+                if retry_count >= self.config.validation.max_retries {
+                    return Err(invalid_translation().into());
+                }
+
+                retry_count += 1;
+                tracing::info!(
+                    "Invalid translation, retrying with stricter prompt: Reason = {}, Retry = {}, Message = {}",
+                    e,
+                    retry_count,
+                    message.msgid()
+                );
+                let retried = match translator.translate_strict(message.msgid()).await? {
+                    Some(retried) => retried,
+                    None => return Err(invalid_translation().into()),
+                };
+                translation = Translation {
+                    text: self.post_processor.process(message.msgid(), retried.text),
+                    usage: TokenUsage::combine(translation.usage, retried.usage),
+                    ..retried
+                };
+            }
         }
 
-        tracing::debug!("Translating message: {}", message.msgid());
-        let translated = translator.translate(&message.msgid()).await?;
-        tracing::debug!("Translation completed: Result = {}\n", translated);
-        message.set_msgstr(translated)?;
+        message.set_msgstr(translation.text.clone())?;
+        if self.config.store_notes {
+            set_comment_lines(
+                message.comments_mut(),
+                NOTES_COMMENT_PREFIX,
+                translation.notes.as_deref(),
+            );
+        }
 
-        if self.config.as_fuzzy {
+        let as_fuzzy = rule
+            .and_then(|r| r.as_fuzzy)
+            .unwrap_or(self.config.as_fuzzy);
+        if as_fuzzy {
             message.flags_mut().add_flag("fuzzy");
         } else {
             message.flags_mut().remove_flag("fuzzy");
         }
 
-        return Ok(true);
+        Ok(translation)
     }
 
-    fn should_translate_message(&self, message: &MessageMutProxy) -> bool {
-        if self.config.skip_translated && message.is_translated() {
-            tracing::debug!("Skip translated message: {}", message.msgid());
-            return false;
+    /// Back-translate msgstr and compare it with msgid. Low quality translations are marked as
+    /// fuzzy, with the back-translation in the comments for the reviewers.
+    async fn estimate_quality<'a>(
+        &self,
+        back_translator: &dyn Translator,
+        back_config: &TranslatorConfig,
+        message: &mut MessageMutProxy<'a>,
+    ) -> Result<(QualityEstimate, Usage)> {
+        let msgstr = message.msgstr()?.to_string();
+        tracing::debug!("Back-translating message: {}", msgstr);
+        let back_translation = back_translator.translate_detailed(&msgstr).await?;
+        let estimated_usage = dry_run::estimate_usage(back_config, &msgstr, &self.config.prices);
+        let usage = self.actual_usage(back_config, estimated_usage, back_translation.usage);
+
+        let estimate = QualityEstimate {
+            score: quality::similarity(message.msgid(), &back_translation.text),
+            back_translation: back_translation.text,
+        };
+        if estimate.score < self.config.quality.threshold {
+            tracing::info!(
+                "Low translation quality, marked as fuzzy: Score = {:.2}, Message = {}",
+                estimate.score,
+                message.msgid()
+            );
+            message.flags_mut().add_flag("fuzzy");
+            set_comment_lines(
+                message.comments_mut(),
+                BACK_TRANSLATION_COMMENT_PREFIX,
+                Some(&format!(
+                    "({:.2}) {}",
+                    estimate.score, estimate.back_translation
+                )),
+            );
+        } else {
+            set_comment_lines(
+                message.comments_mut(),
+                BACK_TRANSLATION_COMMENT_PREFIX,
+                None,
+            );
         }
 
-        if self.config.skip_non_fuzzy && !message.is_fuzzy() {
-            tracing::debug!("Skip non fuzzy message: {}", message.msgid());
-            return false;
+        Ok((estimate, usage))
+    }
+
+    /// Usage of a request, with the tokens reported by the engine if any.
+    fn actual_usage(
+        &self,
+        translator_config: &TranslatorConfig,
+        estimated_usage: Usage,
+        token_usage: Option<TokenUsage>,
+    ) -> Usage {
+        let mut usage = estimated_usage;
+        if let Some(token_usage) = token_usage {
+            usage.prompt_tokens = token_usage.prompt_tokens;
+            usage.completion_tokens = token_usage.completion_tokens;
+            usage.cost = self.config.prices.cost(translator_config, &usage);
         }
+        usage
+    }
 
-        if message.msgid().starts_with("```") {
-            if self.config.skip_code_blocks {
-                tracing::debug!("Skip code block message: {}", message.msgid());
-                return false;
-            }
-        } else if self.config.skip_text {
-            tracing::debug!("Skip regular text message: {}", message.msgid());
-            return false;
+    /// Why a message is not selected for translation. `None` means the message will be translated.
+    pub fn skip_reason(
+        &self,
+        message: &dyn MessageView,
+        rule_index: Option<usize>,
+    ) -> Option<SkipReason> {
+        if rule_index.is_some_and(|index| self.config.rules[index].skip) {
+            return Some(SkipReason::Rule);
         }
 
-        if let Some(source_regex) = &self.config.source_regex {
-            let message_source_str = message.source();
-            let message_sources: Vec<&str> = self
-                .source_parser_regex
-                .find_iter(message_source_str)
-                .map(|m| m.as_str().trim())
-                .collect();
+        self.selections[rule_index.map_or(0, |index| index + 1)]
+            .iter()
+            .find(|(_, filter)| !filter.is_match(message))
+            .map(|(reason, _)| *reason)
+    }
 
-            if !message_sources.iter().any(|s| source_regex.is_match(s)) {
-                tracing::debug!(
-                    "Skip message not matching source regex: {}, Source = {}",
-                    message.msgid(),
-                    message_source_str
-                );
-                return false;
+    /// Singular messages selected for translation, used by the exports for translating outside
+    /// of potr. The limit applies, but the budget doesn't.
+    pub(crate) fn select_messages<'a>(&self, catalog: &'a Catalog) -> Vec<&'a dyn MessageView> {
+        let mut messages = vec![];
+        for message in catalog.messages() {
+            if self.config.message_limit > 0 && messages.len() >= self.config.message_limit as usize
+            {
+                tracing::info!("Message limit reached: {}", messages.len());
+                break;
             }
-        }
 
-        if let Some(include_message_regex) = &self.config.include_message_regex {
-            if !include_message_regex.is_match(message.msgid()) {
-                tracing::debug!(
-                    "Skip message not matching include regex: {}, Source = {}",
-                    message.msgid(),
-                    message.source()
-                );
-                return false;
+            // Plural forms are not supported yet.
+            if !message.is_singular() {
+                continue;
             }
-        }
 
-        if let Some(exclude_message_regex) = &self.config.exclude_message_regex {
-            if exclude_message_regex.is_match(message.msgid()) {
+            if let Some(reason) = self.skip_reason(message, self.find_rule(message)) {
                 tracing::debug!(
-                    "Skip message matching exclude regex: {}, Source = {}",
-                    message.msgid(),
-                    message.source()
+                    "Skip message: Reason = {}, Message = {}",
+                    reason,
+                    message.msgid()
                 );
-                return false;
+                continue;
             }
-        }
 
-        return true;
+            messages.push(message);
+        }
+        messages
     }
 
-    fn write_output_file(&self, po_file: Catalog) -> Result<(), anyhow::Error> {
+    fn write_output_file(&self, po_file: Catalog) -> Result<(), PotrError> {
         tracing::info!("Write to output: {:?}", self.config.output_file_path,);
-        polib::po_file::write(&po_file, &Path::new(&self.config.output_file_path))?;
-        Ok(())
+        formats::create_backend(self.catalog_format()).write(
+            &po_file,
+            Path::new(&self.config.po_file_path),
+            Path::new(&self.config.output_file_path),
+        )
     }
 }
+
+fn log_usage(usage: &Usage) {
+    tracing::info!(
+        "Usage: Characters = {}, PromptTokens = {}, CompletionTokens = {}, Cost = {}",
+        usage.characters,
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        match usage.cost {
+            Some(cost) => format!("${:.4}", cost),
+            None => "unknown".to_string(),
+        }
+    );
+}
+
+/// Replace the comment lines with the prefix, e.g. notes of the previous translation, with the
+/// new text.
+pub(crate) fn set_comment_lines(comments: &mut String, prefix: &str, text: Option<&str>) {
+    let mut lines: Vec<String> = comments
+        .lines()
+        .filter(|line| !line.starts_with(prefix))
+        .map(|line| line.to_string())
+        .collect();
+    if let Some(text) = text {
+        lines.extend(text.lines().map(|line| format!("{}{}", prefix, line)));
+    }
+    *comments = lines.join("\n");
+}
+
+/// FNV-1a hash of the text in hex, which is stable across builds, unlike the hashers in std.
+pub(crate) fn fingerprint(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
@@ -0,0 +1,151 @@
+use crate::translators::{Language, TranslatorConfig, TranslatorEngine};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct QualityConfig {
+    /// Back-translate the new translations to estimate their quality.
+    pub enabled: bool,
+
+    /// Engine used for back-translation. `None` means the engine used for translation.
+    pub engine: Option<TranslatorEngine>,
+
+    /// API key of the engine. Only needed when a different engine is used.
+    pub api_key: Option<String>,
+
+    pub model: Option<String>,
+
+    /// Language of msgid, which the translations are back-translated into.
+    pub source_lang: Language,
+
+    /// Translations with a lower similarity score are marked as fuzzy.
+    pub threshold: f64,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            engine: None,
+            api_key: None,
+            model: None,
+            source_lang: Language::English,
+            threshold: 0.5,
+        }
+    }
+}
+
+impl QualityConfig {
+    /// Local engines, e.g. clone, don't translate, so their output is not estimated.
+    pub fn should_estimate(&self, engine: TranslatorEngine) -> bool {
+        self.enabled && !matches!(engine, TranslatorEngine::Clear | TranslatorEngine::Clone)
+    }
+
+    /// Translator config used for back-translation, based on the one used for translation.
+    pub fn to_translator_config(&self, base: &TranslatorConfig) -> TranslatorConfig {
+        let mut config = base.clone();
+        if let Some(engine) = self.engine {
+            config.switch_engine(engine, self.api_key.as_deref());
+        }
+        if let Some(model) = &self.model {
+            config.model = Some(model.clone());
+        }
+
+        // Glossary maps the source terms to the target language, so it doesn't apply here.
+        config.target_lang = self.source_lang;
+        config.glossary.clear();
+        config.json_output = false;
+        config
+    }
+}
+
+/// Result of the back-translation of a message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityEstimate {
+    /// Similarity between msgid and the back-translation, from 0 to 1.
+    pub score: f64,
+    pub back_translation: String,
+}
+
+/// Similarity of 2 texts, from 0 to 1, by the Dice coefficient of their character bigrams.
+/// Case, punctuation and whitespace are ignored.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    let (a_bigrams, b_bigrams) = (bigrams(&a), bigrams(&b));
+    if a_bigrams.is_empty() || b_bigrams.is_empty() {
+        return if a == b { 1.0 } else { 0.0 };
+    }
+
+    let mut counts: HashMap<(char, char), usize> = HashMap::new();
+    for bigram in &a_bigrams {
+        *counts.entry(*bigram).or_default() += 1;
+    }
+
+    let mut common = 0;
+    for bigram in &b_bigrams {
+        if let Some(count) = counts.get_mut(bigram).filter(|count| **count > 0) {
+            *count -= 1;
+            common += 1;
+        }
+    }
+
+    2.0 * common as f64 / (a_bigrams.len() + b_bigrams.len()) as f64
+}
+
+fn normalize(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn bigrams(text: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = text.chars().collect();
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn similarity_should_ignore_case_and_punctuation() {
+        assert_eq!(similarity("This is a pen.", "this is a pen"), 1.0);
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("A", "B"), 0.0);
+
+        let close = similarity("This is a pen.", "This is a pen!");
+        let paraphrase = similarity("This is a pen.", "That is a pen.");
+        let unrelated = similarity("This is a pen.", "Open the settings dialog.");
+        assert!(close > paraphrase);
+        assert!(paraphrase > 0.5);
+        assert!(unrelated < 0.3);
+    }
+
+    #[test]
+    fn back_translation_should_use_source_language() {
+        let base = TranslatorConfig {
+            engine: TranslatorEngine::OpenAI,
+            target_lang: Language::German,
+            model: Some("gpt-4".to_string()),
+            glossary: BTreeMap::from([("pen".to_string(), "Stift".to_string())]),
+            ..Default::default()
+        };
+
+        let mut quality = QualityConfig::default();
+        let config = quality.to_translator_config(&base);
+        assert_eq!(config.engine, TranslatorEngine::OpenAI);
+        assert_eq!(config.target_lang, Language::English);
+        assert_eq!(config.model, Some("gpt-4".to_string()));
+        assert!(config.glossary.is_empty());
+
+        quality.engine = Some(TranslatorEngine::DeepL);
+        quality.api_key = Some("deepl-key".to_string());
+        let config = quality.to_translator_config(&base);
+        assert_eq!(config.engine, TranslatorEngine::DeepL);
+        assert_eq!(config.api_key, "deepl-key");
+        assert_eq!(config.model, None);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{budget::Usage, error::PotrError, potr::SkipReason, translators::TranslatorEngine};
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+
+/// Outcome of a single message in a run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MessageOutcome {
+    Translated,
+    Skipped { reason: SkipReason },
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MessageReport {
+    pub msgid: String,
+
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub msgctxt: String,
+
+    pub source: String,
+
+    #[serde(flatten)]
+    pub outcome: MessageOutcome,
+
+    /// Engine and model used by the message. Only set when translation is attempted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<TranslatorEngine>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+
+    /// Notes and confidence of the model, in JSON output mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+
+    /// Similarity score and back-translation, when quality estimation is enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality_score: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub back_translation: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RunTotals {
+    pub message_count: usize,
+    pub translated_count: usize,
+    pub skipped_count: usize,
+    pub failed_count: usize,
+    pub usage: Usage,
+}
+
+/// Machine readable report of a run, which can be written to a JSON file with `--report`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunReport {
+    pub po_file_path: String,
+    pub output_file_path: String,
+    pub engine: TranslatorEngine,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// Start time of the run, in seconds since unix epoch.
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub totals: RunTotals,
+    pub messages: Vec<MessageReport>,
+}
+
+impl RunReport {
+    pub fn add_message(&mut self, message: MessageReport) {
+        self.totals.message_count += 1;
+        match &message.outcome {
+            MessageOutcome::Translated => self.totals.translated_count += 1,
+            MessageOutcome::Skipped { .. } => self.totals.skipped_count += 1,
+            MessageOutcome::Failed { .. } => self.totals.failed_count += 1,
+        }
+        self.messages.push(message);
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), PotrError> {
+        tracing::info!("Write report: {:?}", path);
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{
+    error::PotrError,
+    filter::MessageFilter,
+    formats,
+    potr::set_comment_lines,
+    translators::{self, Translator, TranslatorConfig},
+};
+use anyhow::Result;
+use polib::{
+    catalog::Catalog,
+    message::{MessageMutView, MessageView},
+};
+use serde::Serialize;
+use std::path::Path;
+
+/// Prefix of the comment lines that hold the correction suggested by the review.
+const SUGGESTION_COMMENT_PREFIX: &str = "potr-review-suggestion: ";
+
+/// Prefix of the comment lines that hold the reason of the suggested correction.
+const REASON_COMMENT_PREFIX: &str = "potr-review-reason: ";
+
+#[derive(Debug, Clone, Default)]
+pub struct ReviewConfig {
+    pub po_file_path: String,
+    pub output_file_path: String,
+
+    /// Write the suggestions to this JSON file, instead of the PO file.
+    pub review_file_path: Option<String>,
+
+    pub filter: Option<MessageFilter>,
+
+    /// Maximum number of messages to review. 0 means no limit.
+    pub message_limit: usize,
+}
+
+/// Correction of an existing translation, suggested by the review.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReviewSuggestion {
+    pub msgid: String,
+
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub msgctxt: String,
+
+    pub source: String,
+    pub translation: String,
+    pub correction: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReviewReport {
+    pub reviewed_count: usize,
+    pub ok_count: usize,
+    pub failed_count: usize,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub suggestions: Vec<ReviewSuggestion>,
+}
+
+/// Asks an LLM engine to review the existing translations, including the human ones.
+///
+/// Corrections never overwrite msgstr. They are either written into the comments of the
+/// messages, which are marked as fuzzy, or into a separate review file, so reviewers can triage
+/// them with full context.
+pub struct PoReviewer {
+    pub config: ReviewConfig,
+    pub translator_config: TranslatorConfig,
+}
+
+impl PoReviewer {
+    pub fn new(config: ReviewConfig, translator_config: TranslatorConfig) -> PoReviewer {
+        PoReviewer {
+            config,
+            translator_config,
+        }
+    }
+
+    pub async fn run(&self) -> Result<ReviewReport, PotrError> {
+        tracing::info!("Loading po file: {}", self.config.po_file_path);
+        let mut po_file = formats::load_po_file(&self.config.po_file_path)?;
+
+        let translator = translators::create(self.translator_config.clone())?;
+        let report = self.review(translator.as_ref(), &mut po_file).await;
+        tracing::info!(
+            "Review completed! Reviewed = {}, Ok = {}, Suggestions = {}, Failed = {}",
+            report.reviewed_count,
+            report.ok_count,
+            report.suggestions.len(),
+            report.failed_count
+        );
+
+        match &self.config.review_file_path {
+            Some(review_file_path) => {
+                tracing::info!("Write review: {:?}", review_file_path);
+                std::fs::write(review_file_path, serde_json::to_string_pretty(&report)?)?;
+            }
+            None => {
+                tracing::info!("Write to output: {:?}", self.config.output_file_path);
+                polib::po_file::write(&po_file, Path::new(&self.config.output_file_path))?;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Review the translated messages. Without a review file, the suggestions are also written
+    /// into the catalog.
+    pub async fn review(&self, translator: &dyn Translator, catalog: &mut Catalog) -> ReviewReport {
+        let mut report = ReviewReport::default();
+        for mut message in catalog.messages_mut() {
+            if self.config.message_limit > 0 && report.reviewed_count >= self.config.message_limit {
+                tracing::info!("Message limit reached: {}", report.reviewed_count);
+                break;
+            }
+
+            // Fuzzy messages already need a review, and plural forms are not supported yet.
+            let translation = match message.msgstr() {
+                Ok(msgstr) if message.is_translated() && !message.is_fuzzy() => msgstr.to_string(),
+                _ => continue,
+            };
+            if !self
+                .config
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.is_match(&message))
+            {
+                continue;
+            }
+
+            tracing::debug!("Reviewing message: {}", message.msgid());
+            report.reviewed_count += 1;
+            let review = match translator.review(message.msgid(), &translation).await {
+                Ok(review) => review,
+                Err(e) => {
+                    report.failed_count += 1;
+                    tracing::error!(
+                        "Failed to review message: Error = {}, Message = {}",
+                        e,
+                        message.msgid()
+                    );
+                    continue;
+                }
+            };
+
+            let usage = review.usage.unwrap_or_default();
+            report.prompt_tokens += usage.prompt_tokens;
+            report.completion_tokens += usage.completion_tokens;
+
+            let correction = match review.correction {
+                Some(correction) if correction.trim() != translation.trim() => correction,
+                _ => {
+                    report.ok_count += 1;
+                    continue;
+                }
+            };
+
+            tracing::info!(
+                "Correction suggested: Message = {}, Correction = {}",
+                message.msgid(),
+                correction
+            );
+            if self.config.review_file_path.is_none() {
+                set_comment_lines(
+                    message.comments_mut(),
+                    SUGGESTION_COMMENT_PREFIX,
+                    Some(&correction),
+                );
+                set_comment_lines(
+                    message.comments_mut(),
+                    REASON_COMMENT_PREFIX,
+                    review.reason.as_deref(),
+                );
+                message.flags_mut().add_flag("fuzzy");
+            }
+
+            report.suggestions.push(ReviewSuggestion {
+                msgid: message.msgid().to_string(),
+                msgctxt: message.msgctxt().to_string(),
+                source: message.source().to_string(),
+                translation,
+                correction,
+                reason: review.reason,
+            });
+        }
+
+        report
+    }
+}
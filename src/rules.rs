@@ -0,0 +1,152 @@
+use crate::{
+    error::PotrError,
+    filter::{message_source_refs, MessageFilter},
+    translators::{TranslatorConfig, TranslatorEngine},
+};
+use anyhow::Result;
+use polib::message::MessageView;
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// A rule that changes how messages are translated, based on their source files or msgctxt.
+///
+/// Rules are evaluated in order for each message, and the first matching rule is used. Settings
+/// that are not set in the rule fall back to the ones in `PotrConfig` and `TranslatorConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct TranslationRule {
+    /// Source file pattern, matched against the message source paths without line numbers.
+    pub source: Option<Regex>,
+
+    /// Regex to match the message context (msgctxt).
+    pub msgctxt: Option<Regex>,
+
+    /// Filter expression that the message needs to match.
+    pub filter: Option<MessageFilter>,
+
+    /// Skip all messages that match this rule.
+    pub skip: bool,
+
+    pub skip_translated: Option<bool>,
+    pub skip_code_blocks: Option<bool>,
+    pub skip_text: Option<bool>,
+    pub as_fuzzy: Option<bool>,
+
+    pub engine: Option<TranslatorEngine>,
+
+    /// API key of the engine. Only needed when the rule uses a different engine.
+    pub api_key: Option<String>,
+
+    pub model: Option<String>,
+    pub prompt: Option<String>,
+
+    /// Extra glossary terms, which are added on top of the glossary in `TranslatorConfig`.
+    pub glossary: BTreeMap<String, String>,
+}
+
+impl TranslationRule {
+    /// Create a source pattern from a glob, e.g. "src/api/**" or "src/*.md".
+    pub fn source_from_glob(glob: &str) -> Result<Regex, PotrError> {
+        let mut pattern = String::from("^");
+        let mut chars = glob.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    pattern.push_str(".*");
+                }
+                '*' => pattern.push_str("[^/]*"),
+                '?' => pattern.push_str("[^/]"),
+                _ => pattern.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        pattern.push('$');
+
+        Ok(Regex::new(&pattern)?)
+    }
+
+    pub fn is_match(&self, message: &dyn MessageView) -> bool {
+        if let Some(source) = &self.source {
+            if !message_source_refs(message)
+                .iter()
+                .any(|(path, _)| source.is_match(path))
+            {
+                return false;
+            }
+        }
+
+        if let Some(msgctxt_regex) = &self.msgctxt {
+            if !msgctxt_regex.is_match(message.msgctxt()) {
+                return false;
+            }
+        }
+
+        if let Some(filter) = &self.filter {
+            if !filter.is_match(message) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Translator config used by this rule. `None` means the default translator can be used.
+    pub fn to_translator_config(&self, base: &TranslatorConfig) -> Option<TranslatorConfig> {
+        if self.engine.is_none()
+            && self.model.is_none()
+            && self.prompt.is_none()
+            && self.glossary.is_empty()
+        {
+            return None;
+        }
+
+        let mut config = base.clone();
+        if let Some(engine) = self.engine {
+            config.switch_engine(engine, self.api_key.as_deref());
+        }
+        if let Some(model) = &self.model {
+            config.model = Some(model.clone());
+        }
+        if let Some(prompt) = &self.prompt {
+            config.prompt = Some(prompt.clone());
+        }
+        config.glossary.extend(self.glossary.clone());
+
+        Some(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn source_glob_should_match_paths() {
+        let source = TranslationRule::source_from_glob("src/api/**").unwrap();
+        assert!(source.is_match("src/api/Pen.md"));
+        assert!(source.is_match("src/api/v1/Pen.md"));
+        assert!(!source.is_match("src/guide/Pen.md"));
+
+        let source = TranslationRule::source_from_glob("src/*.md").unwrap();
+        assert!(source.is_match("src/SUMMARY.md"));
+        assert!(!source.is_match("src/api/Pen.md"));
+        assert!(!source.is_match("src/SUMMARY.mdx"));
+    }
+
+    #[test]
+    fn rule_without_translator_settings_should_use_default_translator() {
+        let mut rule = TranslationRule {
+            skip_text: Some(true),
+            ..Default::default()
+        };
+        assert!(rule
+            .to_translator_config(&TranslatorConfig::default())
+            .is_none());
+
+        rule.engine = Some(TranslatorEngine::Clear);
+        let config = rule
+            .to_translator_config(&TranslatorConfig::default())
+            .unwrap();
+        assert_eq!(config.engine, TranslatorEngine::Clear);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{
+    error::PotrError,
+    formats,
+    potr::{fingerprint, Potr, PotrConfig},
+    translators::TranslatorConfig,
+};
+use anyhow::Result;
+use polib::{
+    catalog::Catalog,
+    message::{MessageMutView, MessageView},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, path::Path};
+use strum_macros::EnumString;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumString)]
+pub enum SpreadsheetFormat {
+    #[default]
+    #[strum(serialize = "csv")]
+    Csv,
+    #[strum(serialize = "tsv")]
+    Tsv,
+}
+
+impl SpreadsheetFormat {
+    fn delimiter(&self) -> u8 {
+        match self {
+            SpreadsheetFormat::Csv => b',',
+            SpreadsheetFormat::Tsv => b'\t',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SpreadsheetConfig {
+    pub po_file_path: String,
+    pub sheet_file_path: String,
+
+    /// PO file to write the imported translations to.
+    pub output_file_path: String,
+
+    pub format: SpreadsheetFormat,
+}
+
+/// A row of the sheet, one for each message. Only msgstr and fuzzy are meant to be edited.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpreadsheetRow {
+    /// Hash of msgctxt and msgid, to find the message on import.
+    pub key: String,
+
+    pub msgctxt: String,
+    pub msgid: String,
+
+    /// Only for reference, so the column can be removed from the sheet.
+    #[serde(default)]
+    pub source: String,
+
+    pub msgstr: String,
+
+    /// "yes" or "no". Other common spellings, e.g. "true" or "1", are also accepted on import.
+    pub fuzzy: String,
+}
+
+/// Exports the messages selected for translation to CSV or TSV, for reviewers who prefer
+/// spreadsheets. The selection is the same as the translate command, with its filters and rules.
+pub struct SpreadsheetExporter {
+    pub config: SpreadsheetConfig,
+
+    /// Only the message selection is used, so no translator is ever created.
+    potr: Potr,
+}
+
+impl SpreadsheetExporter {
+    pub fn new(config: SpreadsheetConfig, potr_config: PotrConfig) -> SpreadsheetExporter {
+        SpreadsheetExporter {
+            config,
+            potr: Potr::new(potr_config, TranslatorConfig::default()),
+        }
+    }
+
+    pub fn run(&self) -> Result<usize, PotrError> {
+        tracing::info!("Loading po file: {}", self.config.po_file_path);
+        let po_file = formats::load_po_file(&self.config.po_file_path)?;
+
+        let (sheet, exported_count) = self.export(&po_file)?;
+        tracing::info!("Export completed! Exported = {}", exported_count);
+
+        tracing::info!("Write to output: {:?}", self.config.sheet_file_path);
+        std::fs::write(&self.config.sheet_file_path, sheet)?;
+        Ok(exported_count)
+    }
+
+    /// Export the selected messages into a sheet, and return it with the number of exported
+    /// messages.
+    pub fn export(&self, catalog: &Catalog) -> Result<(String, usize), PotrError> {
+        let messages = self.potr.select_messages(catalog);
+        Ok((self.write_sheet(&messages)?, messages.len()))
+    }
+
+    fn write_sheet(&self, messages: &[&dyn MessageView]) -> Result<String> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(self.config.format.delimiter())
+            .from_writer(vec![]);
+        for message in messages {
+            writer.serialize(SpreadsheetRow {
+                key: message_key(message.msgctxt(), message.msgid()),
+                msgctxt: message.msgctxt().to_string(),
+                msgid: message.msgid().to_string(),
+                source: message.source().to_string(),
+                msgstr: message.msgstr().unwrap_or_default().to_string(),
+                fuzzy: if message.is_fuzzy() { "yes" } else { "no" }.to_string(),
+            })?;
+        }
+
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+/// Why a row is not applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowRejectReason {
+    /// No message has the key, e.g. its msgid is changed in the PO file since the export.
+    NotFound,
+
+    /// msgctxt or msgid of the row doesn't match the key, i.e. it is edited in the sheet.
+    SourceChanged,
+
+    /// The row can't be read, e.g. wrong number of cells or unknown fuzzy value.
+    Invalid,
+}
+
+impl fmt::Display for RowRejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            RowRejectReason::NotFound => "message not found",
+            RowRejectReason::SourceChanged => "source text changed",
+            RowRejectReason::Invalid => "invalid row",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RejectedRow {
+    /// Row number as shown in spreadsheet apps, the header being row 1.
+    pub row: usize,
+    pub msgid: String,
+    pub reason: RowRejectReason,
+}
+
+impl fmt::Display for RejectedRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "row {}: {}: {:?}", self.row, self.reason, self.msgid)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SpreadsheetImportReport {
+    pub applied_count: usize,
+
+    /// Rows with the same msgstr and fuzzy state as the PO file, or an empty msgstr.
+    pub unchanged_count: usize,
+
+    pub rejected: Vec<RejectedRow>,
+}
+
+/// Applies the edited msgstr and fuzzy state in a CSV or TSV sheet back to the PO file. Empty
+/// msgstr cells are ignored, so a blanked cell never removes a translation.
+pub struct SpreadsheetImporter {
+    pub config: SpreadsheetConfig,
+}
+
+impl SpreadsheetImporter {
+    pub fn new(config: SpreadsheetConfig) -> SpreadsheetImporter {
+        SpreadsheetImporter { config }
+    }
+
+    pub fn run(&self) -> Result<SpreadsheetImportReport, PotrError> {
+        tracing::info!("Loading po file: {}", self.config.po_file_path);
+        let mut po_file = formats::load_po_file(&self.config.po_file_path)?;
+
+        tracing::info!("Loading sheet file: {}", self.config.sheet_file_path);
+        let sheet = std::fs::read_to_string(&self.config.sheet_file_path)?;
+
+        let report = self.import(&mut po_file, &sheet)?;
+        tracing::info!(
+            "Import completed! Applied = {}, Unchanged = {}, Rejected = {}",
+            report.applied_count,
+            report.unchanged_count,
+            report.rejected.len()
+        );
+
+        tracing::info!("Write to output: {:?}", self.config.output_file_path);
+        polib::po_file::write(&po_file, Path::new(&self.config.output_file_path))?;
+        Ok(report)
+    }
+
+    pub fn import(
+        &self,
+        catalog: &mut Catalog,
+        sheet: &str,
+    ) -> Result<SpreadsheetImportReport, PotrError> {
+        let keys: HashMap<String, (String, String)> = catalog
+            .messages()
+            .filter(|message| message.is_singular())
+            .map(|message| {
+                (
+                    message_key(message.msgctxt(), message.msgid()),
+                    (message.msgctxt().to_string(), message.msgid().to_string()),
+                )
+            })
+            .collect();
+
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.config.format.delimiter())
+            .from_reader(sheet.as_bytes());
+
+        // Without these columns, every row would be rejected, so fail early instead.
+        let headers = reader.headers()?.clone();
+        for column in ["key", "msgctxt", "msgid", "msgstr", "fuzzy"] {
+            if !headers.iter().any(|header| header == column) {
+                return Err(PotrError::parse(format!(
+                    "Column is not found in sheet: {}",
+                    column
+                )));
+            }
+        }
+
+        let mut report = SpreadsheetImportReport::default();
+        for (index, result) in reader.deserialize::<SpreadsheetRow>().enumerate() {
+            let row_number = index + 2;
+            let row = match result {
+                Ok(row) => row,
+                Err(e) => {
+                    tracing::warn!("Invalid row: Row = {}, Error = {}", row_number, e);
+                    report.reject(row_number, String::new(), RowRejectReason::Invalid);
+                    continue;
+                }
+            };
+
+            if row.key != message_key(&row.msgctxt, &row.msgid) {
+                report.reject(row_number, row.msgid, RowRejectReason::SourceChanged);
+                continue;
+            }
+
+            let (msgctxt, msgid) = match keys.get(&row.key) {
+                Some(key) => key,
+                None => {
+                    report.reject(row_number, row.msgid, RowRejectReason::NotFound);
+                    continue;
+                }
+            };
+
+            let fuzzy = match parse_fuzzy(&row.fuzzy) {
+                Some(fuzzy) => fuzzy,
+                None => {
+                    report.reject(row_number, row.msgid, RowRejectReason::Invalid);
+                    continue;
+                }
+            };
+
+            let msgctxt = Some(msgctxt.as_str()).filter(|s| !s.is_empty());
+            let mut message = catalog.find_message_mut(msgctxt, msgid, None).unwrap();
+            if row.msgstr.is_empty()
+                || (message.msgstr().unwrap_or_default() == row.msgstr
+                    && message.is_fuzzy() == fuzzy)
+            {
+                report.unchanged_count += 1;
+                continue;
+            }
+
+            // Only singular messages are in the keys.
+            message.set_msgstr(row.msgstr).unwrap();
+            if fuzzy {
+                message.flags_mut().add_flag("fuzzy");
+            } else {
+                message.flags_mut().remove_flag("fuzzy");
+            }
+            report.applied_count += 1;
+        }
+
+        Ok(report)
+    }
+}
+
+impl SpreadsheetImportReport {
+    fn reject(&mut self, row: usize, msgid: String, reason: RowRejectReason) {
+        tracing::warn!("Row rejected: Row = {}, Reason = {}", row, reason);
+        self.rejected.push(RejectedRow { row, msgid, reason });
+    }
+}
+
+/// Key of a message, which stays the same as long as msgctxt and msgid are not changed.
+fn message_key(msgctxt: &str, msgid: &str) -> String {
+    fingerprint(&format!("{}\u{0004}{}", msgctxt, msgid))
+}
+
+fn parse_fuzzy(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "yes" | "y" | "true" | "1" | "x" => Some(true),
+        "no" | "n" | "false" | "0" | "" => Some(false),
+        _ => None,
+    }
+}
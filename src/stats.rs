@@ -0,0 +1,284 @@
+use crate::error::PotrError;
+use crate::filter::{message_source_refs, MessageFilter};
+use crate::formats;
+use crate::po_writer::parse_obsolete;
+use anyhow::Result;
+use polib::{catalog::Catalog, message::MessageView};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt::Write};
+use strum_macros::EnumString;
+
+#[derive(Debug, Clone, Default)]
+pub struct StatsConfig {
+    pub po_file_path: String,
+
+    /// Only count the messages matching the filter. Obsolete entries are always counted.
+    pub filter: Option<MessageFilter>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+pub enum StatsFormat {
+    #[strum(serialize = "table")]
+    Table,
+    #[strum(serialize = "json")]
+    Json,
+    #[strum(serialize = "csv")]
+    Csv,
+}
+
+/// Number of entries, with the words and characters of their source text (msgid).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct EntryCounts {
+    pub count: usize,
+    pub words: usize,
+    pub characters: usize,
+}
+
+impl EntryCounts {
+    fn add(&mut self, text: &str) {
+        self.count += 1;
+        self.words += text.split_whitespace().count();
+        self.characters += text.chars().count();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CompletionStats {
+    pub translated: EntryCounts,
+    pub fuzzy: EntryCounts,
+    pub untranslated: EntryCounts,
+
+    /// Obsolete (`#~`) entries. They have no source references, so they are only counted in
+    /// the totals.
+    pub obsolete: EntryCounts,
+
+    /// Percentage of translated entries, obsolete entries excluded. 100 if there are no entries,
+    /// as nothing is left to translate.
+    pub completion: f64,
+}
+
+impl Default for CompletionStats {
+    fn default() -> Self {
+        Self {
+            translated: EntryCounts::default(),
+            fuzzy: EntryCounts::default(),
+            untranslated: EntryCounts::default(),
+            obsolete: EntryCounts::default(),
+            completion: 100.0,
+        }
+    }
+}
+
+impl CompletionStats {
+    fn add(&mut self, message: &dyn MessageView) {
+        if message.is_fuzzy() {
+            self.fuzzy.add(message.msgid());
+        } else if message.is_translated() {
+            self.translated.add(message.msgid());
+        } else {
+            self.untranslated.add(message.msgid());
+        }
+
+        let total = self.translated.count + self.fuzzy.count + self.untranslated.count;
+        self.completion = self.translated.count as f64 * 100.0 / total as f64;
+    }
+}
+
+/// Completeness of a PO file, in total and broken down by source file and message kind.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PoStats {
+    pub total: CompletionStats,
+
+    /// Stats by message kind: "code_block" or "text".
+    pub by_kind: BTreeMap<String, CompletionStats>,
+
+    /// Stats by source file. Messages referenced by multiple files are counted in each of them.
+    pub by_source: BTreeMap<String, CompletionStats>,
+}
+
+/// Collects the translation statistics of PO files.
+pub struct PoStatsCollector {
+    pub config: StatsConfig,
+}
+
+impl PoStatsCollector {
+    pub fn new(config: StatsConfig) -> PoStatsCollector {
+        PoStatsCollector { config }
+    }
+
+    pub fn run(&self) -> Result<PoStats, PotrError> {
+        tracing::info!("Loading po file: {}", self.config.po_file_path);
+        let po_file = formats::load_po_file(&self.config.po_file_path)?;
+
+        let mut stats = self.collect(&po_file);
+
+        // Obsolete entries are dropped by the PO parser, so they are counted from the file content.
+        stats.total.obsolete = count_obsolete(&std::fs::read_to_string(&self.config.po_file_path)?);
+        Ok(stats)
+    }
+
+    pub fn collect(&self, catalog: &Catalog) -> PoStats {
+        let mut stats = PoStats::default();
+        for message in catalog.messages() {
+            if !self
+                .config
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.is_match(message))
+            {
+                continue;
+            }
+            stats.total.add(message);
+
+            let kind = if message.msgid().starts_with("```") {
+                "code_block"
+            } else {
+                "text"
+            };
+            stats
+                .by_kind
+                .entry(kind.to_string())
+                .or_default()
+                .add(message);
+
+            let mut paths: Vec<&str> = message_source_refs(message)
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect();
+            paths.sort();
+            paths.dedup();
+            for path in paths {
+                stats
+                    .by_source
+                    .entry(path.to_string())
+                    .or_default()
+                    .add(message);
+            }
+        }
+
+        stats
+    }
+}
+
+impl PoStats {
+    /// Fail if the completion percentage is lower than the threshold.
+    pub fn check_threshold(&self, threshold: f64) -> Result<(), PotrError> {
+        if self.total.completion < threshold {
+            return Err(PotrError::BelowThreshold {
+                completion: self.total.completion,
+                threshold,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn format(&self, format: StatsFormat) -> Result<String, PotrError> {
+        match format {
+            StatsFormat::Table => Ok(self.to_table()),
+            StatsFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            StatsFormat::Csv => Ok(self.to_csv()),
+        }
+    }
+
+    /// All rows with their group, in the order of total, kinds and source files.
+    fn rows(&self) -> Vec<(&str, &str, &CompletionStats)> {
+        let mut rows = vec![("total", "", &self.total)];
+        rows.extend(self.by_kind.iter().map(|(k, s)| ("kind", k.as_str(), s)));
+        rows.extend(
+            self.by_source
+                .iter()
+                .map(|(k, s)| ("source", k.as_str(), s)),
+        );
+        rows
+    }
+
+    fn to_table(&self) -> String {
+        let cell = |c: &EntryCounts| format!("{} ({}w, {}c)", c.count, c.words, c.characters);
+        let mut table = vec![[
+            "Name".to_string(),
+            "Translated".to_string(),
+            "Fuzzy".to_string(),
+            "Untranslated".to_string(),
+            "Obsolete".to_string(),
+            "Completion".to_string(),
+        ]];
+        for (group, name, stats) in self.rows() {
+            table.push([
+                match group {
+                    "total" => "Total".to_string(),
+                    "kind" => format!("[{}]", name),
+                    _ => name.to_string(),
+                },
+                cell(&stats.translated),
+                cell(&stats.fuzzy),
+                cell(&stats.untranslated),
+                cell(&stats.obsolete),
+                format!("{:.2}%", stats.completion),
+            ]);
+        }
+
+        let widths: Vec<usize> = (0..table[0].len())
+            .map(|i| {
+                table
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .max()
+                    .unwrap()
+            })
+            .collect();
+        let mut output = String::new();
+        for row in &table {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| format!("{:<width$}", value, width = width))
+                .collect();
+            writeln!(output, "{}", line.join("  ").trim_end()).unwrap();
+        }
+        output
+    }
+
+    fn to_csv(&self) -> String {
+        let mut output = String::from("group,name");
+        for state in ["translated", "fuzzy", "untranslated", "obsolete"] {
+            write!(output, ",{0},{0}_words,{0}_characters", state).unwrap();
+        }
+        output.push_str(",completion\n");
+
+        for (group, name, stats) in self.rows() {
+            write!(output, "{},{}", group, csv_escape(name)).unwrap();
+            for counts in [
+                &stats.translated,
+                &stats.fuzzy,
+                &stats.untranslated,
+                &stats.obsolete,
+            ] {
+                write!(
+                    output,
+                    ",{},{},{}",
+                    counts.count, counts.words, counts.characters
+                )
+                .unwrap();
+            }
+            writeln!(output, ",{:.2}", stats.completion).unwrap();
+        }
+        output
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Count the obsolete entries, e.g. `#~ msgid "..."`, and the words and characters of their msgid.
+fn count_obsolete(content: &str) -> EntryCounts {
+    let mut counts = EntryCounts::default();
+    for message in parse_obsolete(content) {
+        counts.add(message.msgid());
+    }
+    counts
+}
@@ -0,0 +1,364 @@
+//! Record and replay of the HTTP requests of the translators, so their tests can run without API
+//! keys or network.
+//!
+//! The tests send the requests of the translator through `CassetteProxy`, a local HTTPS proxy.
+//! When the `POTR_RECORD_CASSETTES` environment variable is set, the proxy forwards the requests to
+//! the real service, and saves them with the responses into a cassette in `tests/data/cassettes`.
+//! Otherwise, the proxy replays the responses from the cassette, and fails the test if the requests
+//! are not the same as the recorded ones.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use tokio_native_tls::{native_tls, TlsAcceptor};
+
+const RECORD_ENV_VAR: &str = "POTR_RECORD_CASSETTES";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+
+    /// JSON bodies are saved as JSON and form bodies as objects, so the cassettes are readable.
+    body: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    content_type: Option<String>,
+    body: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Default)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+
+    /// Index of the next interaction to replay.
+    next: usize,
+
+    errors: Vec<String>,
+}
+
+pub struct CassetteProxy {
+    path: PathBuf,
+    recording: bool,
+    addr: SocketAddr,
+    cassette: Arc<Mutex<Cassette>>,
+    server: JoinHandle<()>,
+}
+
+impl CassetteProxy {
+    /// Start the proxy with the cassette of the name.
+    pub async fn start(name: &str) -> CassetteProxy {
+        let path = cassette_dir().join(format!("{}.json", name));
+        let recording = is_recording();
+
+        let mut cassette = Cassette::default();
+        if !recording {
+            let content = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+                panic!(
+                    "Failed to read cassette {}: {}. Set {} to record it.",
+                    path.display(),
+                    e,
+                    RECORD_ENV_VAR
+                )
+            });
+            cassette.interactions = serde_json::from_str(&content).unwrap();
+        }
+        let cassette = Arc::new(Mutex::new(cassette));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let acceptor = tls_acceptor();
+        let server = tokio::spawn({
+            let cassette = cassette.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let acceptor = acceptor.clone();
+                    let cassette = cassette.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) =
+                            handle_connection(stream, acceptor, &cassette, recording).await
+                        {
+                            cassette.lock().unwrap().errors.push(format!("{:#}", e));
+                        }
+                    });
+                }
+            }
+        });
+
+        CassetteProxy {
+            path,
+            recording,
+            addr,
+            cassette,
+            server,
+        }
+    }
+
+    /// HTTP client which sends all the requests through the proxy.
+    pub fn http_client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .proxy(reqwest::Proxy::all(format!("http://{}", self.addr)).unwrap())
+            // The proxy uses the same self-signed certificate for all hosts.
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap()
+    }
+
+    /// Save the cassette when recording, or check that all the recorded requests are sent when
+    /// replaying.
+    pub fn finish(self) {
+        self.server.abort();
+
+        let cassette = self.cassette.lock().unwrap();
+        if !cassette.errors.is_empty() {
+            panic!(
+                "Cassette {} failed:\n{}",
+                self.path.display(),
+                cassette.errors.join("\n")
+            );
+        }
+
+        if self.recording {
+            let content = serde_json::to_string_pretty(&cassette.interactions).unwrap();
+            std::fs::write(&self.path, content + "\n").unwrap();
+        } else if cassette.next < cassette.interactions.len() {
+            panic!(
+                "Cassette {} failed: {} of {} recorded requests are not sent",
+                self.path.display(),
+                cassette.interactions.len() - cassette.next,
+                cassette.interactions.len()
+            );
+        }
+    }
+}
+
+/// API key of the service. The key is only needed for recording, as the proxy doesn't check it
+/// when replaying, and it is never saved into the cassettes.
+pub fn api_key(env_var: &str) -> String {
+    if is_recording() {
+        std::env::var(env_var).unwrap_or_else(|_| panic!("{} is required to record", env_var))
+    } else {
+        "replay".to_string()
+    }
+}
+
+fn is_recording() -> bool {
+    std::env::var(RECORD_ENV_VAR).is_ok()
+}
+
+fn cassette_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/cassettes")
+}
+
+fn tls_acceptor() -> TlsAcceptor {
+    let cert = std::fs::read(cassette_dir().join("proxy-cert.pem")).unwrap();
+    let key = std::fs::read(cassette_dir().join("proxy-key.pem")).unwrap();
+    let identity = native_tls::Identity::from_pkcs8(&cert, &key).unwrap();
+    TlsAcceptor::from(native_tls::TlsAcceptor::new(identity).unwrap())
+}
+
+/// The clients open a tunnel with CONNECT for each HTTPS host, so the proxy terminates TLS in the
+/// tunnel to see the requests. Every connection serves a single request.
+async fn handle_connection(
+    stream: TcpStream,
+    acceptor: TlsAcceptor,
+    cassette: &Mutex<Cassette>,
+    recording: bool,
+) -> Result<()> {
+    let mut stream = BufReader::new(stream);
+    let (method, target, _) = read_head(&mut stream).await?;
+    anyhow::ensure!(
+        method == "CONNECT",
+        "Only HTTPS requests are supported: {} {}",
+        method,
+        target
+    );
+    let host = target.trim_end_matches(":443").to_string();
+    stream
+        .get_mut()
+        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+        .await?;
+
+    let mut stream = BufReader::new(acceptor.accept(stream.into_inner()).await?);
+    let (method, path, headers) = read_head(&mut stream).await?;
+    let content_length = match headers.get("content-length") {
+        Some(length) => length.parse()?,
+        None => 0,
+    };
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).await?;
+
+    let request = RecordedRequest {
+        method,
+        url: format!("https://{}{}", host, path),
+        body: request_body(headers.get("content-type"), &body),
+    };
+    let response = if recording {
+        let response = forward(&request, &headers, body).await?;
+        cassette.lock().unwrap().interactions.push(Interaction {
+            request,
+            response: response.clone(),
+        });
+        response
+    } else {
+        replay(cassette, request)
+    };
+
+    write_response(stream.get_mut(), &response).await
+}
+
+fn replay(cassette: &Mutex<Cassette>, request: RecordedRequest) -> RecordedResponse {
+    let mut cassette = cassette.lock().unwrap();
+    let index = cassette.next;
+    cassette.next += 1;
+
+    let error = match cassette.interactions.get(index) {
+        Some(interaction) if interaction.request == request => {
+            return interaction.response.clone();
+        }
+        Some(interaction) => format!(
+            "Request {} doesn't match the cassette.\nExpected: {:#}\nActual: {:#}",
+            index,
+            serde_json::to_value(&interaction.request).unwrap(),
+            serde_json::to_value(&request).unwrap()
+        ),
+        None => format!(
+            "Request {} is not in the cassette: {:#}",
+            index,
+            serde_json::to_value(&request).unwrap()
+        ),
+    };
+    cassette.errors.push(error.clone());
+
+    RecordedResponse {
+        status: 502,
+        content_type: None,
+        body: Value::String(error),
+    }
+}
+
+async fn forward(
+    request: &RecordedRequest,
+    headers: &HashMap<String, String>,
+    body: Vec<u8>,
+) -> Result<RecordedResponse> {
+    let mut builder = reqwest::Client::new().request(
+        reqwest::Method::from_bytes(request.method.as_bytes())?,
+        &request.url,
+    );
+    for (name, value) in headers {
+        if !matches!(
+            name.as_str(),
+            "host" | "content-length" | "connection" | "accept-encoding"
+        ) {
+            builder = builder.header(name, value);
+        }
+    }
+
+    let response = builder.body(body).send().await?;
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    let body = response.bytes().await?;
+
+    Ok(RecordedResponse {
+        status,
+        content_type,
+        body: serde_json::from_slice(&body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned())),
+    })
+}
+
+/// Form fields are sorted, as the clients don't send them in a stable order.
+fn request_body(content_type: Option<&String>, body: &[u8]) -> Value {
+    if let Ok(json) = serde_json::from_slice(body) {
+        return json;
+    }
+
+    let body = String::from_utf8_lossy(body).into_owned();
+    match content_type {
+        Some(content_type) if content_type.starts_with("application/x-www-form-urlencoded") => {
+            let url = reqwest::Url::parse(&format!("http://localhost/?{}", body)).unwrap();
+            let fields: BTreeMap<String, String> = url.query_pairs().into_owned().collect();
+            serde_json::to_value(fields).unwrap()
+        }
+        _ => Value::String(body),
+    }
+}
+
+/// Request line and the headers, with the names in lowercase.
+async fn read_head(
+    stream: &mut (impl AsyncBufRead + Unpin),
+) -> Result<(String, String, HashMap<String, String>)> {
+    let mut line = String::new();
+    stream.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        anyhow::bail!("Invalid request line: {:?}", line);
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        stream.read_line(&mut line).await?;
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+
+    Ok((method, target, headers))
+}
+
+async fn write_response(
+    stream: &mut (impl AsyncWrite + Unpin),
+    response: &RecordedResponse,
+) -> Result<()> {
+    let body = match &response.body {
+        Value::String(body) => body.clone(),
+        body => body.to_string(),
+    };
+    let reason = reqwest::StatusCode::from_u16(response.status)?
+        .canonical_reason()
+        .unwrap_or_default();
+
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\ncontent-length: {}\r\nconnection: close\r\n",
+        response.status,
+        reason,
+        body.len()
+    );
+    if let Some(content_type) = &response.content_type {
+        head.push_str(&format!("content-type: {}\r\n", content_type));
+    }
+    head.push_str("\r\n");
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
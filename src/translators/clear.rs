@@ -1,5 +1,4 @@
-use super::{Translator, TranslatorConfig, TranslatorEngine};
-use anyhow::Result;
+use super::{TranslateError, Translator, TranslatorConfig, TranslatorEngine};
 use async_trait::async_trait;
 
 pub struct ClearTranslator {}
@@ -16,7 +15,7 @@ impl Translator for ClearTranslator {
         TranslatorEngine::Clear
     }
 
-    async fn translate(&self, _: &str) -> Result<String> {
+    async fn translate(&self, _: &str) -> Result<String, TranslateError> {
         Ok(String::from(""))
     }
 }
@@ -26,7 +25,7 @@ mod tests {
     use super::*;
     use crate::translators::Language;
     use pretty_assertions::assert_eq;
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
     #[tokio::test]
     async fn test_clear_translator() {
@@ -39,6 +38,9 @@ mod tests {
             api_version: None,
             api_deployment_id: None,
             extra_params: HashMap::new(),
+            prompt: None,
+            glossary: BTreeMap::new(),
+            json_output: false,
         };
         let translator = ClearTranslator::new(config);
         let result = translator
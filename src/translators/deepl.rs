@@ -1,18 +1,48 @@
-use super::{Language, Translator, TranslatorConfig, TranslatorEngine};
-use anyhow::Result;
+use super::{
+    engine_extra_params, invalid_extra_param, Language, TranslateError, Translator,
+    TranslatorConfig, TranslatorEngine,
+};
 use async_trait::async_trait;
-use deepl::{DeepLApi, Lang};
+use deepl::{DeepLApi, Formality, Lang, TagHandling};
+use std::collections::HashMap;
+
+type Result<T> = std::result::Result<T, TranslateError>;
+
+/// Extra parameters of the DeepL engine, set on every request.
+pub(crate) const EXTRA_PARAMS: &[&str] = &["formality", "glossary_id", "tag_handling"];
 
 pub struct DeeplTranslator {
     target_lang: Lang,
+    params: HashMap<String, String>,
     client: DeepLApi,
 }
 
 impl DeeplTranslator {
     pub fn new(config: TranslatorConfig) -> Result<DeeplTranslator> {
-        let client = DeepLApi::with(&config.api_key).new();
+        Self::with_http_client(config, reqwest::Client::new())
+    }
+
+    /// Create the translator with the HTTP client, e.g. to send the requests through a proxy.
+    pub fn with_http_client(
+        config: TranslatorConfig,
+        http_client: reqwest::Client,
+    ) -> Result<DeeplTranslator> {
+        let params = engine_extra_params(&config, EXTRA_PARAMS)?;
+        for (key, value) in &params {
+            let is_valid = match key.as_str() {
+                "formality" => formality(value).is_some(),
+                "tag_handling" => tag_handling(value).is_some(),
+                _ => true,
+            };
+            if !is_valid {
+                return Err(invalid_extra_param(key, value, "invalid value"));
+            }
+        }
+
+        let client = DeepLApi::with(&config.api_key).client(http_client).new();
         let translator = DeeplTranslator {
             target_lang: Self::langauage_to_deepl_code(config.target_lang)?,
+            params,
             client,
         };
 
@@ -48,7 +78,12 @@ impl DeeplTranslator {
             Language::Turkish => Lang::TR,
             Language::Ukrainian => Lang::UK,
             Language::Chinese => Lang::ZH,
-            _ => anyhow::bail!("Unsupported language: {:?}", target_lang),
+            _ => {
+                return Err(TranslateError::UnsupportedLanguage {
+                    engine: TranslatorEngine::DeepL,
+                    language: target_lang,
+                })
+            }
         };
 
         Ok(code)
@@ -62,38 +97,153 @@ impl Translator for DeeplTranslator {
     }
 
     async fn translate(&self, text: &str) -> Result<String> {
-        let translated = self
-            .client
-            .translate_text(text, self.target_lang.clone())
-            .await?;
+        let mut request = self.client.translate_text(text, self.target_lang.clone());
+        if let Some(value) = self.params.get("formality").and_then(|v| formality(v)) {
+            request.formality(value);
+        }
+        if let Some(value) = self.params.get("glossary_id") {
+            request.glossary_id(value.clone());
+        }
+        if let Some(value) = self
+            .params
+            .get("tag_handling")
+            .and_then(|v| tag_handling(v))
+        {
+            request.tag_handling(value);
+        }
+        let translated = request.await?;
+
+        match translated.translations.first() {
+            Some(sentence) => Ok(sentence.text.clone()),
+            None => Err(TranslateError::InvalidResponse(
+                "No translation is returned".to_string(),
+            )),
+        }
+    }
+}
 
-        Ok(translated.translations[0].text.clone())
+// The options of the requests are neither `Clone` nor `FromStr`, so they are parsed for each request.
+fn formality(value: &str) -> Option<Formality> {
+    match value {
+        "default" => Some(Formality::Default),
+        "more" => Some(Formality::More),
+        "less" => Some(Formality::Less),
+        "prefer_more" => Some(Formality::PreferMore),
+        "prefer_less" => Some(Formality::PreferLess),
+        _ => None,
+    }
+}
+
+fn tag_handling(value: &str) -> Option<TagHandling> {
+    match value {
+        "xml" => Some(TagHandling::Xml),
+        "html" => Some(TagHandling::Html),
+        _ => None,
+    }
+}
+
+/// DeepL reports failed requests with the message of the API only, e.g. "Quota exceeded" for
+/// HTTP 456, so the kind of the error is told by the message.
+impl From<deepl::Error> for TranslateError {
+    fn from(e: deepl::Error) -> Self {
+        match e {
+            deepl::Error::RequestFail(message) => {
+                let lowercase = message.to_lowercase();
+                if lowercase.contains("quota") {
+                    TranslateError::QuotaExceeded(message)
+                } else if lowercase.contains("too many requests") {
+                    TranslateError::rate_limited(message)
+                } else if lowercase.contains("auth") || lowercase.contains("forbidden") {
+                    TranslateError::Auth(message)
+                } else {
+                    TranslateError::Request(message)
+                }
+            }
+            deepl::Error::InvalidResponse(message) => TranslateError::InvalidResponse(message),
+            e => TranslateError::Request(e.to_string()),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::translators::cassette::{self, CassetteProxy};
     use pretty_assertions::assert_eq;
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn deepl_errors_should_be_mapped_by_message() {
+        let quota: TranslateError = deepl::Error::RequestFail("Quota exceeded".to_string()).into();
+        assert_eq!(
+            quota,
+            TranslateError::QuotaExceeded("Quota exceeded".to_string())
+        );
+
+        let auth: TranslateError =
+            deepl::Error::RequestFail("Authorization failure, check auth_key".to_string()).into();
+        assert!(matches!(auth, TranslateError::Auth(_)));
+
+        let rate_limited: TranslateError =
+            deepl::Error::RequestFail("Too many requests".to_string()).into();
+        assert!(matches!(rate_limited, TranslateError::RateLimited { .. }));
+    }
+
+    #[test]
+    fn invalid_extra_params_should_fail_on_creation() {
+        let mut config = TranslatorConfig {
+            engine: TranslatorEngine::DeepL,
+            extra_params: HashMap::from([("formality".to_string(), "prefer_more".to_string())]),
+            ..Default::default()
+        };
+        assert!(DeeplTranslator::new(config.clone()).is_ok());
+
+        config
+            .extra_params
+            .insert("formality".to_string(), "casual".to_string());
+        assert!(matches!(
+            DeeplTranslator::new(config).err(),
+            Some(TranslateError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn unsupported_language_should_fail_on_creation() {
+        let config = TranslatorConfig {
+            engine: TranslatorEngine::DeepL,
+            target_lang: Language::Welsh,
+            ..Default::default()
+        };
+        assert_eq!(
+            DeeplTranslator::new(config).err(),
+            Some(TranslateError::UnsupportedLanguage {
+                engine: TranslatorEngine::DeepL,
+                language: Language::Welsh,
+            })
+        );
+    }
 
     #[tokio::test]
     async fn test_deepl_translator() {
+        let proxy = CassetteProxy::start("deepl-translator").await;
         let config = TranslatorConfig {
             engine: TranslatorEngine::DeepL,
             target_lang: Language::English,
-            api_key: std::env::var("POTR_API_KEY_DEEPL").unwrap(),
+            api_key: cassette::api_key("POTR_API_KEY_DEEPL"),
             model: None,
             api_base: None,
             api_version: None,
             api_deployment_id: None,
             extra_params: HashMap::new(),
+            prompt: None,
+            glossary: BTreeMap::new(),
+            json_output: false,
         };
-        let translator = DeeplTranslator::new(config).unwrap();
-        let result = translator
-            .translate("这是一段中文文本。")
-            .await
-            .expect("Failed to translate");
+        let translator = DeeplTranslator::with_http_client(config, proxy.http_client()).unwrap();
+        let result = translator.translate("这是一段中文文本。").await;
+        proxy.finish();
+
+        let result = result.expect("Failed to translate");
 
         assert_eq!(result, "This is a Chinese text.");
     }
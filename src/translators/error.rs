@@ -0,0 +1,137 @@
+use super::{Language, TranslatorEngine};
+use regex::Regex;
+use std::{fmt, time::Duration};
+
+/// Errors of the translation engines, so the callers can tell the failures that need the user,
+/// e.g. a bad API key, from the ones that might go away on retry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranslateError {
+    /// API key is missing, invalid or has no access to the model.
+    Auth(String),
+
+    /// Quota or credit of the account is used up.
+    QuotaExceeded(String),
+
+    /// Too many requests. `retry_after` is the wait suggested by the engine, if any.
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+
+    UnsupportedLanguage {
+        engine: TranslatorEngine,
+        language: Language,
+    },
+
+    /// The engine refused to translate the text, e.g. by its content filter.
+    ContentFiltered(String),
+
+    /// The response can't be used, e.g. malformed JSON output or a refusal.
+    InvalidResponse(String),
+
+    /// The engine is not set up correctly, e.g. Azure OpenAI without a deployment id.
+    InvalidConfig(String),
+
+    /// The operation is not supported by the engine, e.g. reviews by DeepL.
+    Unsupported(String),
+
+    /// Network errors and the other errors reported by the engine.
+    Request(String),
+}
+
+impl TranslateError {
+    /// Errors that will fail all the other messages too, so the run should stop.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            TranslateError::Auth(_)
+                | TranslateError::QuotaExceeded(_)
+                | TranslateError::UnsupportedLanguage { .. }
+                | TranslateError::InvalidConfig(_)
+        )
+    }
+
+    /// Rate limit error, with the wait hint in the message if any, e.g. "Please try again in
+    /// 20s." from OpenAI.
+    pub(crate) fn rate_limited(message: String) -> TranslateError {
+        let retry_regex = Regex::new(r"(?i)try again in (\d+(?:\.\d+)?)(ms|s)\b").unwrap();
+        let retry_after = retry_regex.captures(&message).and_then(|captures| {
+            let value: f64 = captures[1].parse().ok()?;
+            Some(match &captures[2] {
+                "ms" => Duration::from_secs_f64(value / 1000.0),
+                _ => Duration::from_secs_f64(value),
+            })
+        });
+        TranslateError::RateLimited {
+            message,
+            retry_after,
+        }
+    }
+}
+
+impl fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranslateError::Auth(message) => write!(f, "Authentication failed: {}", message),
+            TranslateError::QuotaExceeded(message) => write!(f, "Quota exceeded: {}", message),
+            TranslateError::RateLimited {
+                message,
+                retry_after: Some(retry_after),
+            } => write!(
+                f,
+                "Rate limited, retry after {:.1}s: {}",
+                retry_after.as_secs_f64(),
+                message
+            ),
+            TranslateError::RateLimited { message, .. } => write!(f, "Rate limited: {}", message),
+            TranslateError::UnsupportedLanguage { engine, language } => write!(
+                f,
+                "Unsupported language: {:?} is not supported by {}",
+                language, engine
+            ),
+            TranslateError::ContentFiltered(message) => write!(f, "Content filtered: {}", message),
+            TranslateError::InvalidResponse(message) => write!(f, "Invalid response: {}", message),
+            TranslateError::InvalidConfig(message) => write!(f, "Invalid config: {}", message),
+            TranslateError::Unsupported(message) => write!(f, "Not supported: {}", message),
+            TranslateError::Request(message) => write!(f, "Request failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for TranslateError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn retry_hint_should_be_parsed_from_rate_limit_message() {
+        let error = TranslateError::rate_limited(
+            "Rate limit reached for gpt-4 on tokens per min. Please try again in 1.5s.".to_string(),
+        );
+        assert_eq!(
+            error,
+            TranslateError::RateLimited {
+                message:
+                    "Rate limit reached for gpt-4 on tokens per min. Please try again in 1.5s."
+                        .to_string(),
+                retry_after: Some(Duration::from_millis(1500)),
+            }
+        );
+
+        let error = TranslateError::rate_limited("Try again in 20ms.".to_string());
+        assert!(
+            matches!(error, TranslateError::RateLimited { retry_after, .. } if retry_after == Some(Duration::from_millis(20)))
+        );
+
+        let error = TranslateError::rate_limited("Too many requests".to_string());
+        assert!(matches!(
+            error,
+            TranslateError::RateLimited {
+                retry_after: None,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,257 @@
+use super::{
+    engine_extra_params, invalid_extra_param, parse_extra_param, Language, TranslateError,
+    Translator, TranslatorConfig, TranslatorEngine,
+};
+use async_trait::async_trait;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use strum_macros::EnumString;
+
+/// Extra parameters of the mock engine.
+pub(crate) const EXTRA_PARAMS: &[&str] = &[
+    "mapping",
+    "transform",
+    "prefix",
+    "fail_at",
+    "fail_with",
+    "latency_ms",
+];
+
+/// How the mock engine translates the texts that are not in the mapping.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumString)]
+pub enum MockTransform {
+    #[default]
+    #[strum(serialize = "identity")]
+    Identity,
+    #[strum(serialize = "uppercase")]
+    Uppercase,
+    #[strum(serialize = "reverse")]
+    Reverse,
+    #[strum(serialize = "prefix")]
+    Prefix,
+}
+
+/// Error returned by the injected failures.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumString)]
+pub enum MockFailure {
+    #[default]
+    #[strum(serialize = "request")]
+    Request,
+    #[strum(serialize = "auth")]
+    Auth,
+    #[strum(serialize = "quota")]
+    Quota,
+    #[strum(serialize = "rate-limit")]
+    RateLimit,
+    #[strum(serialize = "content-filter")]
+    ContentFilter,
+    #[strum(serialize = "invalid-response")]
+    InvalidResponse,
+}
+
+impl MockFailure {
+    fn to_error(self, index: usize) -> TranslateError {
+        let message = format!("Mock failure at request {}", index);
+        match self {
+            MockFailure::Request => TranslateError::Request(message),
+            MockFailure::Auth => TranslateError::Auth(message),
+            MockFailure::Quota => TranslateError::QuotaExceeded(message),
+            MockFailure::RateLimit => TranslateError::RateLimited {
+                message,
+                retry_after: Some(Duration::from_secs(1)),
+            },
+            MockFailure::ContentFilter => TranslateError::ContentFiltered(message),
+            MockFailure::InvalidResponse => TranslateError::InvalidResponse(message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MockConfig {
+    /// Fixed translations of the texts. The other texts are translated by the transform.
+    pub mapping: HashMap<String, String>,
+
+    pub transform: MockTransform,
+
+    /// Prefix added by the prefix transform.
+    pub prefix: String,
+
+    /// Requests to fail, by their 0-based index in the order they are received.
+    pub fail_at: BTreeSet<usize>,
+
+    pub fail_with: MockFailure,
+
+    /// Delay of each request.
+    pub latency: Duration,
+}
+
+impl MockConfig {
+    /// Settings from the extra parameters of the engine:
+    ///
+    /// - `mapping`: JSON file with an object of the texts and their translations.
+    /// - `transform`: "identity", "uppercase", "reverse" or "prefix".
+    /// - `prefix`: Prefix added by the prefix transform. "[<target_lang>] " by default.
+    /// - `fail_at`: Comma separated indices of the requests to fail, e.g. "1,3".
+    /// - `fail_with`: "request", "auth", "quota", "rate-limit", "content-filter" or
+    ///   "invalid-response".
+    /// - `latency_ms`: Delay of each request in milliseconds.
+    pub fn from_extra_params(
+        params: &HashMap<String, String>,
+        target_lang: Language,
+    ) -> Result<MockConfig, TranslateError> {
+        let mut config = MockConfig {
+            prefix: format!("[{}] ", target_lang.as_ref()),
+            ..Default::default()
+        };
+
+        if let Some(path) = params.get("mapping") {
+            let mapping = std::fs::read_to_string(path)
+                .map_err(|e| invalid_extra_param("mapping", path, &e.to_string()))?;
+            config.mapping = serde_json::from_str(&mapping)
+                .map_err(|e| invalid_extra_param("mapping", path, &e.to_string()))?;
+        }
+        if let Some(transform) = parse_extra_param(params, "transform")? {
+            config.transform = transform;
+        }
+        if let Some(prefix) = params.get("prefix") {
+            config.prefix = prefix.clone();
+        }
+        if let Some(fail_at) = params.get("fail_at") {
+            config.fail_at = fail_at
+                .split(',')
+                .filter(|index| !index.trim().is_empty())
+                .map(|index| {
+                    index
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid_extra_param("fail_at", fail_at, "invalid value"))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(fail_with) = parse_extra_param(params, "fail_with")? {
+            config.fail_with = fail_with;
+        }
+        if let Some(latency_ms) = parse_extra_param(params, "latency_ms")? {
+            config.latency = Duration::from_millis(latency_ms);
+        }
+
+        Ok(config)
+    }
+}
+
+/// Offline engine with deterministic output, for testing without API keys or network.
+pub struct MockTranslator {
+    config: MockConfig,
+    request_count: AtomicUsize,
+}
+
+impl MockTranslator {
+    pub fn new(config: MockConfig) -> MockTranslator {
+        MockTranslator {
+            config,
+            request_count: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn from_translator_config(
+        config: TranslatorConfig,
+    ) -> Result<MockTranslator, TranslateError> {
+        let params = engine_extra_params(&config, EXTRA_PARAMS)?;
+        let mock_config = MockConfig::from_extra_params(&params, config.target_lang)?;
+        Ok(MockTranslator::new(mock_config))
+    }
+
+    /// Number of requests received so far, including the failed ones.
+    pub fn request_count(&self) -> usize {
+        self.request_count.load(Ordering::SeqCst)
+    }
+
+    fn transform(&self, text: &str) -> String {
+        match self.config.transform {
+            MockTransform::Identity => text.to_string(),
+            MockTransform::Uppercase => text.to_uppercase(),
+            MockTransform::Reverse => text.chars().rev().collect(),
+            MockTransform::Prefix => format!("{}{}", self.config.prefix, text),
+        }
+    }
+}
+
+#[async_trait]
+impl Translator for MockTranslator {
+    fn name(&self) -> TranslatorEngine {
+        TranslatorEngine::Mock
+    }
+
+    async fn translate(&self, text: &str) -> Result<String, TranslateError> {
+        let index = self.request_count.fetch_add(1, Ordering::SeqCst);
+        if !self.config.latency.is_zero() {
+            tokio::time::sleep(self.config.latency).await;
+        }
+
+        if self.config.fail_at.contains(&index) {
+            return Err(self.config.fail_with.to_error(index));
+        }
+
+        match self.config.mapping.get(text) {
+            Some(translation) => Ok(translation.clone()),
+            None => Ok(self.transform(text)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn mock_config_should_be_parsed_from_extra_params() {
+        let params = HashMap::from([
+            ("transform".to_string(), "prefix".to_string()),
+            ("fail_at".to_string(), "1, 3".to_string()),
+            ("fail_with".to_string(), "rate-limit".to_string()),
+            ("latency_ms".to_string(), "20".to_string()),
+        ]);
+        assert_eq!(
+            MockConfig::from_extra_params(&params, Language::German).unwrap(),
+            MockConfig {
+                mapping: HashMap::new(),
+                transform: MockTransform::Prefix,
+                prefix: "[de] ".to_string(),
+                fail_at: BTreeSet::from([1, 3]),
+                fail_with: MockFailure::RateLimit,
+                latency: Duration::from_millis(20),
+            }
+        );
+
+        let params = HashMap::from([("transform".to_string(), "shuffle".to_string())]);
+        assert!(matches!(
+            MockConfig::from_extra_params(&params, Language::German),
+            Err(TranslateError::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn mock_translator_should_fail_at_given_requests() {
+        let translator = MockTranslator::new(MockConfig {
+            mapping: HashMap::from([("pen".to_string(), "Stift".to_string())]),
+            transform: MockTransform::Reverse,
+            fail_at: BTreeSet::from([1]),
+            fail_with: MockFailure::Auth,
+            ..Default::default()
+        });
+
+        assert_eq!(translator.translate("pen").await.unwrap(), "Stift");
+        assert_eq!(
+            translator.translate("pen").await,
+            Err(TranslateError::Auth(
+                "Mock failure at request 1".to_string()
+            ))
+        );
+        assert_eq!(translator.translate("pencil").await.unwrap(), "licnep");
+        assert_eq!(translator.request_count(), 3);
+    }
+}
@@ -1,17 +1,32 @@
+#[cfg(test)]
+mod cassette;
 mod clear;
 mod clone;
 mod deepl;
+mod error;
+mod mock;
 mod openai;
+mod registry;
+
+pub use error::*;
+pub use mock::*;
+pub use registry::*;
+
+pub(crate) use openai::{system_prompt, DEFAULT_MODEL, DEFAULT_PROMPT, TEXT_REQUEST_MESSAGE};
 
-use anyhow::Result;
 use async_trait::async_trait;
-use std::{collections::HashMap, fmt};
-use strum_macros::EnumString;
+use serde::{Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+};
+use strum_macros::{AsRefStr, EnumString};
 
 /// Language list with code.
 ///
 /// This is defined in ISO-639-1: https://localizely.com/iso-639-1-list/.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumString)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, AsRefStr, EnumString)]
 pub enum Language {
     #[strum(serialize = "aa")]
     Afar,
@@ -389,18 +404,67 @@ impl fmt::Display for Language {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumString)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TranslatorEngine {
-    #[strum(serialize = "clear")]
     Clear,
-    #[strum(serialize = "clone")]
     Clone,
-    #[strum(serialize = "openai")]
     OpenAI,
-    #[strum(serialize = "azure-openai")]
     AzureOpenAI,
-    #[strum(serialize = "deepl")]
     DeepL,
+
+    /// Offline engine with deterministic output, for testing.
+    Mock,
+
+    /// Engine registered with `register_translator`.
+    Custom(&'static str),
+}
+
+impl TranslatorEngine {
+    pub const BUILT_IN: [TranslatorEngine; 6] = [
+        TranslatorEngine::Clear,
+        TranslatorEngine::Clone,
+        TranslatorEngine::OpenAI,
+        TranslatorEngine::AzureOpenAI,
+        TranslatorEngine::DeepL,
+        TranslatorEngine::Mock,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TranslatorEngine::Clear => "clear",
+            TranslatorEngine::Clone => "clone",
+            TranslatorEngine::OpenAI => "openai",
+            TranslatorEngine::AzureOpenAI => "azure-openai",
+            TranslatorEngine::DeepL => "deepl",
+            TranslatorEngine::Mock => "mock",
+            TranslatorEngine::Custom(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for TranslatorEngine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Built-in engines, or the custom engines registered so far.
+impl FromStr for TranslatorEngine {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TranslatorEngine::BUILT_IN
+            .into_iter()
+            .find(|engine| engine.name() == s)
+            .or_else(|| registry::find_custom_engine(s))
+            .ok_or(strum::ParseError::VariantNotFound)
+    }
+}
+
+impl Serialize for TranslatorEngine {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -428,6 +492,16 @@ pub struct TranslatorConfig {
 
     /// Extra parameters for each engine.
     pub extra_params: HashMap<String, String>,
+
+    /// Prompt template for LLM based engines. "{target_lang}" is replaced with the target language.
+    pub prompt: Option<String>,
+
+    /// Glossary of terms and their translations, which the engine is asked to follow.
+    pub glossary: BTreeMap<String, String>,
+
+    /// Ask LLM based engines to reply in JSON with the translation, notes and confidence,
+    /// instead of free-form text.
+    pub json_output: bool,
 }
 
 impl Default for TranslatorConfig {
@@ -441,24 +515,159 @@ impl Default for TranslatorConfig {
             api_version: None,
             api_deployment_id: None,
             extra_params: Default::default(),
+            prompt: None,
+            glossary: Default::default(),
+            json_output: false,
         }
     }
 }
 
+impl TranslatorConfig {
+    /// Switch to another engine, e.g. for a rule or back-translation. The API key and model of
+    /// the current engine don't apply to a different engine, so they are replaced.
+    pub(crate) fn switch_engine(&mut self, engine: TranslatorEngine, api_key: Option<&str>) {
+        if engine != self.engine {
+            self.api_key = api_key.unwrap_or_default().to_string();
+            self.model = None;
+        }
+        self.engine = engine;
+    }
+}
+
+/// Tokens used by a translation request, as reported by the engine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+}
+
+impl TokenUsage {
+    /// Sum of 2 usages, e.g. of a request and its retry. `None` if neither is reported.
+    pub fn combine(a: Option<TokenUsage>, b: Option<TokenUsage>) -> Option<TokenUsage> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(TokenUsage {
+                prompt_tokens: a.prompt_tokens + b.prompt_tokens,
+                completion_tokens: a.completion_tokens + b.completion_tokens,
+            }),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// Translated text, along with the details reported by the engine.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Translation {
+    pub text: String,
+    pub usage: Option<TokenUsage>,
+
+    /// Notes of the model about the translation, e.g. ambiguities. Only returned in JSON output
+    /// mode.
+    pub notes: Option<String>,
+
+    /// Confidence of the model in the translation, from 0 to 1. Only returned in JSON output mode.
+    pub confidence: Option<f64>,
+}
+
+/// Result of reviewing an existing translation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Review {
+    /// Corrected translation. `None` means the translation is OK.
+    pub correction: Option<String>,
+
+    /// Why the translation is corrected.
+    pub reason: Option<String>,
+
+    pub usage: Option<TokenUsage>,
+}
+
 #[async_trait]
 pub trait Translator: Send + Sync {
     fn name(&self) -> TranslatorEngine;
 
-    async fn translate(&self, text: &str) -> Result<String>;
+    async fn translate(&self, text: &str) -> Result<String, TranslateError>;
+
+    /// Translate the text, and also return the usage and notes if the engine reports them.
+    async fn translate_detailed(&self, text: &str) -> Result<Translation, TranslateError> {
+        Ok(Translation {
+            text: self.translate(text).await?,
+            ..Default::default()
+        })
+    }
+
+    /// Translate the text again with a stricter prompt, after the translation is rejected by the
+    /// validation. `None` if the engine doesn't use prompts, so retrying won't help.
+    async fn translate_strict(&self, _text: &str) -> Result<Option<Translation>, TranslateError> {
+        Ok(None)
+    }
+
+    /// Review the translation of the text. Only supported by LLM based engines.
+    async fn review(&self, _text: &str, _translation: &str) -> Result<Review, TranslateError> {
+        Err(TranslateError::Unsupported(format!(
+            "Review is not supported by engine: {}",
+            self.name()
+        )))
+    }
+}
+
+/// Extra parameters of each built-in engine.
+const ENGINE_EXTRA_PARAMS: [&[&str]; 3] = [
+    openai::EXTRA_PARAMS,
+    deepl::EXTRA_PARAMS,
+    mock::EXTRA_PARAMS,
+];
+
+/// Extra parameters of the config that the engine supports. Keys that none of the built-in engines
+/// supports are rejected, while the keys of the other engines are ignored, so the same parameters
+/// can be shared by the rules with different engines.
+pub(crate) fn engine_extra_params(
+    config: &TranslatorConfig,
+    supported: &[&str],
+) -> Result<HashMap<String, String>, TranslateError> {
+    let mut params = HashMap::new();
+    for (key, value) in &config.extra_params {
+        if supported.contains(&key.as_str()) {
+            params.insert(key.clone(), value.clone());
+        } else if !ENGINE_EXTRA_PARAMS
+            .iter()
+            .any(|keys| keys.contains(&key.as_str()))
+        {
+            return Err(invalid_extra_param(key, value, "unknown parameter"));
+        }
+    }
+    Ok(params)
 }
 
-pub fn create(config: TranslatorConfig) -> Result<Box<dyn Translator>> {
+/// Parse the extra parameter, if it is set.
+pub(crate) fn parse_extra_param<T: FromStr>(
+    params: &HashMap<String, String>,
+    key: &str,
+) -> Result<Option<T>, TranslateError> {
+    params
+        .get(key)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| invalid_extra_param(key, value, "invalid value"))
+        })
+        .transpose()
+}
+
+pub(crate) fn invalid_extra_param(key: &str, value: &str, reason: &str) -> TranslateError {
+    TranslateError::InvalidConfig(format!(
+        "Invalid extra parameter: {} = {:?}, {}",
+        key, value, reason
+    ))
+}
+
+pub fn create(config: TranslatorConfig) -> Result<Box<dyn Translator>, TranslateError> {
     let translator: Box<dyn Translator> = match config.engine {
         TranslatorEngine::Clear => Box::new(clear::ClearTranslator::new(config)),
         TranslatorEngine::Clone => Box::new(clone::CloneTranslator::new(config)),
-        TranslatorEngine::OpenAI => Box::new(openai::OpenAITranslator::new(config)),
+        TranslatorEngine::OpenAI => Box::new(openai::OpenAITranslator::new(config)?),
         TranslatorEngine::AzureOpenAI => Box::new(openai::AzureOpenAITranslator::new(config)?),
         TranslatorEngine::DeepL => Box::new(deepl::DeeplTranslator::new(config)?),
+        TranslatorEngine::Mock => Box::new(mock::MockTranslator::from_translator_config(config)?),
+        TranslatorEngine::Custom(name) => registry::create_custom_translator(name, config)?,
     };
 
     Ok(translator)
@@ -505,6 +714,9 @@ mod tests {
             api_version: None,
             api_deployment_id: Some("mock-deployment".to_string()),
             extra_params: HashMap::new(),
+            prompt: None,
+            glossary: BTreeMap::new(),
+            json_output: false,
         };
 
         let engines = vec![
@@ -513,6 +725,7 @@ mod tests {
             TranslatorEngine::OpenAI,
             TranslatorEngine::AzureOpenAI,
             TranslatorEngine::DeepL,
+            TranslatorEngine::Mock,
         ];
         for engine in engines {
             config.engine = engine;
@@ -520,4 +733,45 @@ mod tests {
             assert_eq!(translator.name(), engine);
         }
     }
+
+    #[test]
+    fn switching_engine_should_reset_engine_settings() {
+        let mut config = TranslatorConfig {
+            engine: TranslatorEngine::OpenAI,
+            api_key: "sk-openai".to_string(),
+            model: Some("gpt-4".to_string()),
+            ..Default::default()
+        };
+        config.switch_engine(TranslatorEngine::OpenAI, Some("other"));
+        assert_eq!(config.api_key, "sk-openai");
+        assert_eq!(config.model.as_deref(), Some("gpt-4"));
+
+        config.switch_engine(TranslatorEngine::DeepL, Some("deepl-key"));
+        assert_eq!(config.engine, TranslatorEngine::DeepL);
+        assert_eq!(config.api_key, "deepl-key");
+        assert_eq!(config.model, None);
+    }
+
+    #[test]
+    fn extra_params_of_other_engines_should_be_ignored() {
+        let mut config = TranslatorConfig {
+            extra_params: HashMap::from([
+                ("temperature".to_string(), "0.2".to_string()),
+                ("formality".to_string(), "less".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            engine_extra_params(&config, openai::EXTRA_PARAMS).unwrap(),
+            HashMap::from([("temperature".to_string(), "0.2".to_string())])
+        );
+
+        config
+            .extra_params
+            .insert("temprature".to_string(), "0.2".to_string());
+        assert!(matches!(
+            engine_extra_params(&config, openai::EXTRA_PARAMS),
+            Err(TranslateError::InvalidConfig(_))
+        ));
+    }
 }
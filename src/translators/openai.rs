@@ -1,63 +1,464 @@
-use super::{Language, Translator, TranslatorConfig, TranslatorEngine};
-use anyhow::Result;
+use super::{
+    engine_extra_params, parse_extra_param, Review, TokenUsage, TranslateError, Translation,
+    Translator, TranslatorConfig, TranslatorEngine,
+};
+use crate::validate;
 use async_openai::{
     config::{AzureConfig, Config, OpenAIConfig},
-    types::{ChatCompletionRequestMessageArgs, CreateChatCompletionRequestArgs, Role},
+    error::OpenAIError,
+    types::{
+        ChatCompletionFunctionCall, ChatCompletionFunctions, ChatCompletionFunctionsArgs,
+        ChatCompletionRequestMessageArgs, ChatCompletionResponseMessage,
+        CreateChatCompletionRequestArgs, CreateChatCompletionResponse, Role,
+    },
     Client,
 };
 use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+
+type Result<T> = std::result::Result<T, TranslateError>;
+
+/// By default, we use the GPT3.5 model for cost-saving purpose.
+pub(crate) const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
+
+/// User message sent before the text, so the model doesn't treat the text as instructions.
+pub(crate) const TEXT_REQUEST_MESSAGE: &str = "I understand. Please give me the text.";
+
+/// Default system prompt. "{target_lang}" is replaced with the target language.
+pub(crate) const DEFAULT_PROMPT: &str =
+    "You are a professional translator. Please translate the text into {target_lang} without explanation.";
+
+/// System prompt of reviews. "{target_lang}" is replaced with the target language.
+const REVIEW_PROMPT: &str = "You are a professional translator, reviewing translations into {target_lang}. Check the translation of the text for mistranslations, omissions, grammar and terminology, and submit the review with the submit_review function. Reply \"ok\" if the translation is correct, otherwise reply \"corrected\" with the corrected translation and the reason.";
+
+/// Function the model is asked to call with the translation in JSON output mode.
+const SUBMIT_FUNCTION_NAME: &str = "submit_translation";
+
+/// Function the model is asked to call with the review.
+const REVIEW_FUNCTION_NAME: &str = "submit_review";
+
+/// Appended to the system prompt in JSON output mode.
+const JSON_OUTPUT_PROMPT: &str = " Submit the translation with the submit_translation function. Only add notes when the text is ambiguous or the translation needs a review.";
+
+/// Extra parameters of the OpenAI engines, set on every request.
+pub(crate) const EXTRA_PARAMS: &[&str] = &[
+    "temperature",
+    "top_p",
+    "max_tokens",
+    "presence_penalty",
+    "frequency_penalty",
+];
+
+/// Times to retry when the JSON output is malformed.
+const JSON_OUTPUT_RETRIES: usize = 2;
+
+/// Arguments of the submit function, i.e. the translation in JSON output mode.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct JsonTranslation {
+    translation: String,
+
+    #[serde(default)]
+    notes: Option<String>,
+
+    #[serde(default)]
+    confidence: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ReviewStatus {
+    Ok,
+    Corrected,
+}
+
+/// Arguments of the review function.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct JsonReview {
+    status: ReviewStatus,
+
+    #[serde(default)]
+    correction: Option<String>,
+
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Request settings from the extra parameters. `None` means the default of the API.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct RequestParams {
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    max_tokens: Option<u16>,
+    presence_penalty: Option<f32>,
+    frequency_penalty: Option<f32>,
+}
+
+impl RequestParams {
+    fn from_config(config: &TranslatorConfig) -> Result<RequestParams> {
+        let params = engine_extra_params(config, EXTRA_PARAMS)?;
+        Ok(RequestParams {
+            temperature: parse_extra_param(&params, "temperature")?,
+            top_p: parse_extra_param(&params, "top_p")?,
+            max_tokens: parse_extra_param(&params, "max_tokens")?,
+            presence_penalty: parse_extra_param(&params, "presence_penalty")?,
+            frequency_penalty: parse_extra_param(&params, "frequency_penalty")?,
+        })
+    }
+}
 
 pub struct OpenAITranslatorT<C: Config> {
     model: String,
-    target_lang: Language,
+    system_prompt: String,
+
+    /// System prompt used to retry after an invalid translation.
+    strict_system_prompt: String,
+
+    review_prompt: String,
+    json_output: bool,
+    params: RequestParams,
     client: Client<C>,
 }
 
 impl<C: Config> OpenAITranslatorT<C> {
-    async fn do_translate(&self, text: &str) -> Result<String> {
-        let req = CreateChatCompletionRequestArgs::default()
-            .model(self.model.clone())
-            .messages([
-                ChatCompletionRequestMessageArgs::default()
-                    .role(Role::System)
-                    .content(format!("You are a professional translator. Please translate the text into {:?} without explanation.", self.target_lang))
-                    .build()?,
-                ChatCompletionRequestMessageArgs::default()
-                    .role(Role::User)
-                    .content("I understand. Please give me the text.".to_string())
-                    .build()?,
-                ChatCompletionRequestMessageArgs::default()
-                    .role(Role::User)
-                    .content(text.to_string())
-                    .build()?,
-            ]).build()?;
-
-        let response = self.client.chat().create(req).await?;
-        let result = match &response.choices[0].message.content {
-            Some(content) => content.clone(),
-            None => String::from(""),
-        };
-        Ok(result)
+    async fn do_translate(&self, system_prompt: &str, text: &str) -> Result<Translation> {
+        if !self.json_output {
+            let response = self.request(system_prompt, text, None).await?;
+            return Ok(Translation {
+                text: first_message(&response)?
+                    .content
+                    .clone()
+                    .unwrap_or_default(),
+                usage: token_usage(&response),
+                ..Default::default()
+            });
+        }
+
+        let function = ChatCompletionFunctionsArgs::default()
+            .name(SUBMIT_FUNCTION_NAME)
+            .description("Submit the translation of the text.")
+            .parameters(json_output_schema())
+            .build()?;
+        let (result, usage) = self
+            .call_function(system_prompt, text, function, parse_json_translation)
+            .await?;
+        Ok(Translation {
+            text: result.translation,
+            usage,
+            notes: result.notes.filter(|notes| !notes.trim().is_empty()),
+            confidence: result.confidence,
+        })
+    }
+
+    async fn do_review(&self, text: &str, translation: &str) -> Result<Review> {
+        let function = ChatCompletionFunctionsArgs::default()
+            .name(REVIEW_FUNCTION_NAME)
+            .description("Submit the review of the translation.")
+            .parameters(review_schema())
+            .build()?;
+        let request = format!("Text:\n{}\n\nTranslation:\n{}", text, translation);
+        let (result, usage) = self
+            .call_function(&self.review_prompt, &request, function, parse_json_review)
+            .await?;
+        Ok(Review {
+            correction: match result.status {
+                ReviewStatus::Ok => None,
+                ReviewStatus::Corrected => result.correction,
+            },
+            reason: result.reason.filter(|reason| !reason.trim().is_empty()),
+            usage,
+        })
+    }
+
+    /// Ask the model to call the function, and parse its arguments. Malformed arguments are
+    /// retried.
+    async fn call_function<T>(
+        &self,
+        system_prompt: &str,
+        text: &str,
+        function: ChatCompletionFunctions,
+        parse: fn(&str) -> Result<T>,
+    ) -> Result<(T, Option<TokenUsage>)> {
+        let mut usage = None;
+        let mut retry_count = 0;
+        loop {
+            let response = self
+                .request(system_prompt, text, Some(function.clone()))
+                .await?;
+            usage = TokenUsage::combine(usage, token_usage(&response));
+
+            // Some models reply with the JSON as content, instead of calling the function.
+            let message = first_message(&response)?;
+            let output = match &message.function_call {
+                Some(function_call) => function_call.arguments.clone(),
+                None => message.content.clone().unwrap_or_default(),
+            };
+            match parse(&output) {
+                Ok(result) => return Ok((result, usage)),
+                Err(e) if retry_count < JSON_OUTPUT_RETRIES => {
+                    retry_count += 1;
+                    tracing::info!(
+                        "Malformed JSON output, retrying: Error = {}, Retry = {}",
+                        e,
+                        retry_count
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn request(
+        &self,
+        system_prompt: &str,
+        text: &str,
+        function: Option<ChatCompletionFunctions>,
+    ) -> Result<CreateChatCompletionResponse> {
+        let mut args = CreateChatCompletionRequestArgs::default();
+        args.model(self.model.clone()).messages([
+            ChatCompletionRequestMessageArgs::default()
+                .role(Role::System)
+                .content(system_prompt.to_string())
+                .build()?,
+            ChatCompletionRequestMessageArgs::default()
+                .role(Role::User)
+                .content(TEXT_REQUEST_MESSAGE.to_string())
+                .build()?,
+            ChatCompletionRequestMessageArgs::default()
+                .role(Role::User)
+                .content(text.to_string())
+                .build()?,
+        ]);
+
+        if let Some(temperature) = self.params.temperature {
+            args.temperature(temperature);
+        }
+        if let Some(top_p) = self.params.top_p {
+            args.top_p(top_p);
+        }
+        if let Some(max_tokens) = self.params.max_tokens {
+            args.max_tokens(max_tokens);
+        }
+        if let Some(presence_penalty) = self.params.presence_penalty {
+            args.presence_penalty(presence_penalty);
+        }
+        if let Some(frequency_penalty) = self.params.frequency_penalty {
+            args.frequency_penalty(frequency_penalty);
+        }
+
+        if let Some(function) = function {
+            let function_call =
+                ChatCompletionFunctionCall::Object(json!({ "name": function.name.clone() }));
+            args.functions([function]).function_call(function_call);
+        }
+
+        let response = self.client.chat().create(args.build()?).await?;
+        if response
+            .choices
+            .iter()
+            .any(|choice| choice.finish_reason.as_deref() == Some("content_filter"))
+        {
+            return Err(TranslateError::ContentFiltered(
+                "Response is stopped by the content filter".to_string(),
+            ));
+        }
+        Ok(response)
+    }
+}
+
+/// OpenAI and Azure OpenAI tell the kind of the error by its code or type, e.g.
+/// "invalid_api_key" or "insufficient_quota".
+impl From<OpenAIError> for TranslateError {
+    fn from(e: OpenAIError) -> Self {
+        match e {
+            OpenAIError::ApiError(api_error) => {
+                let code = api_error
+                    .code
+                    .as_ref()
+                    .and_then(|code| code.as_str())
+                    .unwrap_or_default();
+                match (code, api_error.r#type.as_str()) {
+                    ("invalid_api_key", _) | (_, "invalid_api_key") | ("401", _) => {
+                        TranslateError::Auth(api_error.message)
+                    }
+                    ("insufficient_quota", _) | (_, "insufficient_quota") => {
+                        TranslateError::QuotaExceeded(api_error.message)
+                    }
+                    ("rate_limit_exceeded", _) | ("429", _) | (_, "requests") | (_, "tokens") => {
+                        TranslateError::rate_limited(api_error.message)
+                    }
+                    ("content_filter", _) => TranslateError::ContentFiltered(api_error.message),
+                    _ => TranslateError::Request(api_error.message),
+                }
+            }
+            OpenAIError::JSONDeserialize(e) => TranslateError::InvalidResponse(e.to_string()),
+            OpenAIError::InvalidArgument(message) => TranslateError::InvalidConfig(message),
+            e => TranslateError::Request(e.to_string()),
+        }
+    }
+}
+
+/// Message of the first choice. A response without any choice is invalid.
+fn first_message(
+    response: &CreateChatCompletionResponse,
+) -> Result<&ChatCompletionResponseMessage> {
+    response
+        .choices
+        .first()
+        .map(|choice| &choice.message)
+        .ok_or_else(|| TranslateError::InvalidResponse("No choice is returned".to_string()))
+}
+
+fn token_usage(response: &CreateChatCompletionResponse) -> Option<TokenUsage> {
+    response.usage.as_ref().map(|usage| TokenUsage {
+        prompt_tokens: usage.prompt_tokens as usize,
+        completion_tokens: usage.completion_tokens as usize,
+    })
+}
+
+/// JSON schema of the translation in JSON output mode.
+fn json_output_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "translation": {
+                "type": "string",
+                "description": "The translated text, without explanation."
+            },
+            "notes": {
+                "type": "string",
+                "description": "Notes for the reviewer, e.g. ambiguities or terms kept untranslated."
+            },
+            "confidence": {
+                "type": "number",
+                "minimum": 0,
+                "maximum": 1,
+                "description": "Confidence in the translation, from 0 to 1."
+            }
+        },
+        "required": ["translation"]
+    })
+}
+
+/// JSON schema of the review.
+fn review_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "status": {
+                "type": "string",
+                "enum": ["ok", "corrected"],
+                "description": "\"ok\" if the translation is correct, otherwise \"corrected\"."
+            },
+            "correction": {
+                "type": "string",
+                "description": "The corrected translation, without explanation."
+            },
+            "reason": {
+                "type": "string",
+                "description": "Why the translation is corrected."
+            }
+        },
+        "required": ["status"]
+    })
+}
+
+/// Parse the JSON output and validate it against the schema.
+fn parse_json_translation(output: &str) -> Result<JsonTranslation> {
+    let result: JsonTranslation = serde_json::from_str(output).map_err(|e| {
+        TranslateError::InvalidResponse(format!(
+            "Malformed JSON output: {}, Output = {}",
+            e, output
+        ))
+    })?;
+
+    if let Some(confidence) = result.confidence {
+        if !(0.0..=1.0).contains(&confidence) {
+            return Err(TranslateError::InvalidResponse(format!(
+                "Malformed JSON output: confidence {} is out of range",
+                confidence
+            )));
+        }
     }
+
+    Ok(result)
+}
+
+fn parse_json_review(output: &str) -> Result<JsonReview> {
+    let result: JsonReview = serde_json::from_str(output).map_err(|e| {
+        TranslateError::InvalidResponse(format!(
+            "Malformed JSON output: {}, Output = {}",
+            e, output
+        ))
+    })?;
+
+    if result.status == ReviewStatus::Corrected
+        && result.correction.as_deref().is_none_or(str::is_empty)
+    {
+        return Err(TranslateError::InvalidResponse(
+            "Malformed JSON output: correction is missing".to_string(),
+        ));
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn system_prompt(config: &TranslatorConfig) -> String {
+    let mut prompt = config
+        .prompt
+        .as_deref()
+        .unwrap_or(DEFAULT_PROMPT)
+        .replace("{target_lang}", &format!("{:?}", config.target_lang));
+    prompt.push_str(&glossary_prompt(config));
+
+    if config.json_output {
+        prompt.push_str(JSON_OUTPUT_PROMPT);
+    }
+
+    prompt
+}
+
+fn review_prompt(config: &TranslatorConfig) -> String {
+    let mut prompt = REVIEW_PROMPT.replace("{target_lang}", &format!("{:?}", config.target_lang));
+    prompt.push_str(&glossary_prompt(config));
+    prompt
+}
+
+fn glossary_prompt(config: &TranslatorConfig) -> String {
+    let mut prompt = String::new();
+    if !config.glossary.is_empty() {
+        prompt.push_str(" Please use the following glossary:");
+        for (term, translation) in &config.glossary {
+            prompt.push_str(&format!("\n- {} => {}", term, translation));
+        }
+    }
+    prompt
 }
 
 pub type OpenAITranslator = OpenAITranslatorT<OpenAIConfig>;
 
 impl OpenAITranslator {
-    pub fn new(config: TranslatorConfig) -> OpenAITranslator {
+    pub fn new(config: TranslatorConfig) -> Result<OpenAITranslator> {
+        Self::with_http_client(config, reqwest::Client::new())
+    }
+
+    /// Create the translator with the HTTP client, e.g. to send the requests through a proxy.
+    pub fn with_http_client(
+        config: TranslatorConfig,
+        http_client: reqwest::Client,
+    ) -> Result<OpenAITranslator> {
         let openai_config = OpenAIConfig::new().with_api_key(config.api_key.clone());
-        let client = Client::with_config(openai_config);
+        let client = Client::with_config(openai_config).with_http_client(http_client);
 
-        OpenAITranslator {
-            model: if let Some(model) = config.model {
-                model
-            } else {
-                // By default, we use the GPT3.5 model for cost-saving purpose.
-                String::from("gpt-3.5-turbo")
-            },
-            target_lang: config.target_lang,
+        Ok(OpenAITranslator {
+            system_prompt: system_prompt(&config),
+            strict_system_prompt: system_prompt(&validate::strict_translator_config(&config)),
+            review_prompt: review_prompt(&config),
+            json_output: config.json_output,
+            params: RequestParams::from_config(&config)?,
+            model: config.model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
             client,
-        }
+        })
     }
 }
 
@@ -68,7 +469,21 @@ impl Translator for OpenAITranslator {
     }
 
     async fn translate(&self, text: &str) -> Result<String> {
-        self.do_translate(text).await
+        Ok(self.do_translate(&self.system_prompt, text).await?.text)
+    }
+
+    async fn translate_detailed(&self, text: &str) -> Result<Translation> {
+        self.do_translate(&self.system_prompt, text).await
+    }
+
+    async fn translate_strict(&self, text: &str) -> Result<Option<Translation>> {
+        Ok(Some(
+            self.do_translate(&self.strict_system_prompt, text).await?,
+        ))
+    }
+
+    async fn review(&self, text: &str, translation: &str) -> Result<Review> {
+        self.do_review(text, translation).await
     }
 }
 
@@ -77,17 +492,20 @@ pub type AzureOpenAITranslator = OpenAITranslatorT<AzureConfig>;
 impl AzureOpenAITranslator {
     pub fn new(config: TranslatorConfig) -> Result<AzureOpenAITranslator> {
         if config.api_base.is_none() {
-            anyhow::bail!("Azure OpenAI requires an API URL");
+            return Err(TranslateError::InvalidConfig(
+                "Azure OpenAI requires an API URL".to_string(),
+            ));
         }
 
         if config.api_deployment_id.is_none() {
-            anyhow::bail!("Azure OpenAI requires an deployment id");
+            return Err(TranslateError::InvalidConfig(
+                "Azure OpenAI requires an deployment id".to_string(),
+            ));
         }
 
-        let api_version = if config.api_version.is_none() {
-            String::from("2023-03-15-preview")
-        } else {
-            config.api_version.unwrap()
+        let api_version = match &config.api_version {
+            Some(api_version) => api_version.clone(),
+            None => String::from("2023-03-15-preview"),
         };
 
         let openai_config = AzureConfig::new()
@@ -99,13 +517,12 @@ impl AzureOpenAITranslator {
         let client = Client::with_config(openai_config);
 
         let translator = OpenAITranslatorT {
-            model: if let Some(model) = config.model {
-                model
-            } else {
-                // By default, we use the GPT3.5 model for cost-saving purpose.
-                String::from("gpt-3.5-turbo")
-            },
-            target_lang: config.target_lang,
+            system_prompt: system_prompt(&config),
+            strict_system_prompt: system_prompt(&validate::strict_translator_config(&config)),
+            review_prompt: review_prompt(&config),
+            json_output: config.json_output,
+            params: RequestParams::from_config(&config)?,
+            model: config.model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
             client,
         };
 
@@ -120,34 +537,202 @@ impl Translator for AzureOpenAITranslator {
     }
 
     async fn translate(&self, text: &str) -> Result<String> {
-        self.do_translate(text).await
+        Ok(self.do_translate(&self.system_prompt, text).await?.text)
+    }
+
+    async fn translate_detailed(&self, text: &str) -> Result<Translation> {
+        self.do_translate(&self.system_prompt, text).await
+    }
+
+    async fn translate_strict(&self, text: &str) -> Result<Option<Translation>> {
+        Ok(Some(
+            self.do_translate(&self.strict_system_prompt, text).await?,
+        ))
+    }
+
+    async fn review(&self, text: &str, translation: &str) -> Result<Review> {
+        self.do_review(text, translation).await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::translators::{
+        cassette::{self, CassetteProxy},
+        Language,
+    };
     use pretty_assertions::assert_eq;
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn response_without_choices_should_be_invalid() {
+        let response: CreateChatCompletionResponse = serde_json::from_value(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "gpt-3.5-turbo",
+            "choices": [],
+        }))
+        .unwrap();
+        assert!(matches!(
+            first_message(&response),
+            Err(TranslateError::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn request_params_should_be_parsed_from_extra_params() {
+        let mut config = TranslatorConfig {
+            extra_params: HashMap::from([
+                ("temperature".to_string(), "0.2".to_string()),
+                ("max_tokens".to_string(), "500".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            RequestParams::from_config(&config).unwrap(),
+            RequestParams {
+                temperature: Some(0.2),
+                max_tokens: Some(500),
+                ..Default::default()
+            }
+        );
+
+        config
+            .extra_params
+            .insert("top_p".to_string(), "high".to_string());
+        assert!(matches!(
+            RequestParams::from_config(&config),
+            Err(TranslateError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn system_prompt_should_include_target_lang_and_glossary() {
+        let mut config = TranslatorConfig {
+            target_lang: Language::German,
+            prompt: Some("Translate API reference into {target_lang} literally.".to_string()),
+            ..Default::default()
+        };
+        config
+            .glossary
+            .insert("pen".to_string(), "Stift".to_string());
+
+        assert_eq!(
+            system_prompt(&config),
+            "Translate API reference into German literally. Please use the following glossary:\n- pen => Stift"
+        );
+    }
+
+    #[test]
+    fn json_output_should_be_validated() {
+        assert_eq!(
+            parse_json_translation(
+                r#"{"translation": "Das ist ein Stift.", "notes": "Pen could also be Kugelschreiber.", "confidence": 0.8}"#
+            )
+            .unwrap(),
+            JsonTranslation {
+                translation: "Das ist ein Stift.".to_string(),
+                notes: Some("Pen could also be Kugelschreiber.".to_string()),
+                confidence: Some(0.8),
+            }
+        );
+        assert_eq!(
+            parse_json_translation(r#"{"translation": "Das ist ein Stift."}"#)
+                .unwrap()
+                .notes,
+            None
+        );
+
+        assert!(parse_json_translation("Das ist ein Stift.").is_err());
+        assert!(parse_json_translation(r#"{"notes": "No translation."}"#).is_err());
+        assert!(parse_json_translation(
+            r#"{"translation": "Das ist ein Stift.", "confidence": 80}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn review_should_have_correction_when_corrected() {
+        assert_eq!(
+            parse_json_review(r#"{"status": "ok"}"#).unwrap().status,
+            ReviewStatus::Ok
+        );
+        assert_eq!(
+            parse_json_review(
+                r#"{"status": "corrected", "correction": "Das ist ein Stift.", "reason": "Wrong article."}"#
+            )
+            .unwrap()
+            .correction,
+            Some("Das ist ein Stift.".to_string())
+        );
+
+        assert!(parse_json_review(r#"{"status": "corrected"}"#).is_err());
+        assert!(parse_json_review(r#"{"status": "wrong"}"#).is_err());
+    }
+
+    #[test]
+    fn openai_errors_should_be_mapped_by_code_and_type() {
+        let api_error = |code: Option<&str>, r#type: &str, message: &str| {
+            TranslateError::from(OpenAIError::ApiError(async_openai::error::ApiError {
+                message: message.to_string(),
+                r#type: r#type.to_string(),
+                param: None,
+                code: code.map(|code| json!(code)),
+            }))
+        };
+
+        assert_eq!(
+            api_error(Some("invalid_api_key"), "invalid_request_error", "Bad key"),
+            TranslateError::Auth("Bad key".to_string())
+        );
+        assert_eq!(
+            api_error(None, "insufficient_quota", "No credit"),
+            TranslateError::QuotaExceeded("No credit".to_string())
+        );
+        assert_eq!(
+            api_error(
+                Some("rate_limit_exceeded"),
+                "tokens",
+                "Please try again in 2s."
+            ),
+            TranslateError::RateLimited {
+                message: "Please try again in 2s.".to_string(),
+                retry_after: Some(std::time::Duration::from_secs(2)),
+            }
+        );
+        assert_eq!(
+            api_error(Some("content_filter"), "invalid_request_error", "Filtered"),
+            TranslateError::ContentFiltered("Filtered".to_string())
+        );
+        assert_eq!(
+            api_error(Some("model_not_found"), "invalid_request_error", "No model"),
+            TranslateError::Request("No model".to_string())
+        );
+    }
 
     #[tokio::test]
     async fn test_openai_translator() {
+        let proxy = CassetteProxy::start("openai-translator").await;
         let config = TranslatorConfig {
             engine: TranslatorEngine::OpenAI,
             target_lang: Language::English,
-            api_key: std::env::var("POTR_API_KEY_OPENAI").unwrap(),
+            api_key: cassette::api_key("POTR_API_KEY_OPENAI"),
             model: None,
             api_base: None,
             api_version: None,
             api_deployment_id: None,
             extra_params: HashMap::new(),
+            prompt: None,
+            glossary: BTreeMap::new(),
+            json_output: false,
         };
-        let translator = OpenAITranslator::new(config);
-        let result = translator
-            .translate("这是一段中文文本。")
-            .await
-            .expect("Failed to translate");
+        let translator = OpenAITranslator::with_http_client(config, proxy.http_client()).unwrap();
+        let result = translator.translate("这是一段中文文本。").await;
+        proxy.finish();
 
+        let result = result.expect("Failed to translate");
         assert_eq!(result, "This is a Chinese text.");
     }
 
@@ -167,8 +752,11 @@ mod tests {
             api_version: None,
             api_deployment_id: Some(std::env::var("POTR_API_DEPLOYMENT_ID_AZURE_OPENAI").unwrap()),
             extra_params: HashMap::new(),
+            prompt: None,
+            glossary: BTreeMap::new(),
+            json_output: false,
         };
-        let translator = OpenAITranslator::new(config);
+        let translator = OpenAITranslator::new(config).unwrap();
         let result = translator
             .translate("这是一段中文文本。")
             .await
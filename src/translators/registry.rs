@@ -0,0 +1,66 @@
+use super::{TranslateError, Translator, TranslatorConfig, TranslatorEngine};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+/// Creates the translator of a custom engine from its config.
+pub type TranslatorFactory =
+    Arc<dyn Fn(TranslatorConfig) -> Result<Box<dyn Translator>, TranslateError> + Send + Sync>;
+
+/// Factories of the custom engines, keyed by their names. The names are leaked once on
+/// registration, so `TranslatorEngine` can stay `Copy`.
+fn registry() -> &'static RwLock<BTreeMap<&'static str, TranslatorFactory>> {
+    static REGISTRY: OnceLock<RwLock<BTreeMap<&'static str, TranslatorFactory>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Register a custom engine under the name, and return the engine to use in
+/// `TranslatorConfig`. Once registered, the name can also be used wherever an engine name is
+/// parsed, e.g. the config file and rules.
+///
+/// Registering the same name again replaces its factory. The names of the built-in engines can't
+/// be used.
+pub fn register_translator<F>(name: &str, factory: F) -> Result<TranslatorEngine, TranslateError>
+where
+    F: Fn(TranslatorConfig) -> Result<Box<dyn Translator>, TranslateError> + Send + Sync + 'static,
+{
+    if name.is_empty() || TranslatorEngine::BUILT_IN.iter().any(|e| e.name() == name) {
+        return Err(TranslateError::InvalidConfig(format!(
+            "Engine name can't be used for custom engines: {:?}",
+            name
+        )));
+    }
+
+    let mut registry = registry().write().unwrap();
+    let name = match registry.keys().find(|key| **key == name) {
+        Some(key) => *key,
+        None => Box::leak(name.to_string().into_boxed_str()),
+    };
+    registry.insert(name, Arc::new(factory));
+    Ok(TranslatorEngine::Custom(name))
+}
+
+/// Custom engine registered under the name, if any.
+pub(crate) fn find_custom_engine(name: &str) -> Option<TranslatorEngine> {
+    let registry = registry().read().unwrap();
+    registry
+        .keys()
+        .find(|key| **key == name)
+        .map(|key| TranslatorEngine::Custom(key))
+}
+
+pub(crate) fn create_custom_translator(
+    name: &str,
+    config: TranslatorConfig,
+) -> Result<Box<dyn Translator>, TranslateError> {
+    // Cloned out of the lock, so the factory can use the registry too.
+    let factory = registry().read().unwrap().get(name).cloned();
+    match factory {
+        Some(factory) => factory(config),
+        None => Err(TranslateError::InvalidConfig(format!(
+            "Engine is not registered: {}",
+            name
+        ))),
+    }
+}
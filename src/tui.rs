@@ -0,0 +1,316 @@
+use crate::{
+    error::PotrError,
+    filter::MessageFilter,
+    formats,
+    translators::{Translator, TranslatorEngine},
+};
+use polib::{
+    catalog::Catalog,
+    message::{MessageMutView, MessageView},
+};
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+    str::FromStr,
+};
+
+const HELP: &str = "Actions:
+  a          Accept the suggestion
+  k          Keep the current translation, and clear the fuzzy flag
+  e          Edit the translation, use \\n for new lines
+  r [engine] Regenerate the suggestion, with the next or the given engine
+  f          Mark as fuzzy, the suggestion is used if there is no translation yet
+  s          Skip this message
+  q          Save and quit";
+
+#[derive(Debug, Clone, Default)]
+pub struct TuiConfig {
+    pub po_file_path: String,
+    pub output_file_path: String,
+    pub filter: Option<MessageFilter>,
+
+    /// Only step through fuzzy messages. By default, translated messages are also included.
+    pub fuzzy_only: bool,
+}
+
+/// Action chosen by the reviewer for a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TuiAction {
+    Accept,
+    Keep,
+    Edit,
+
+    /// Get a new suggestion, from the given engine or the next one.
+    Regenerate(Option<TranslatorEngine>),
+
+    MarkFuzzy,
+    Skip,
+    Quit,
+}
+
+impl FromStr for TuiAction {
+    type Err = PotrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let action = match (parts.next(), parts.next()) {
+            (Some("a" | "accept"), None) => TuiAction::Accept,
+            (Some("k" | "keep"), None) => TuiAction::Keep,
+            (Some("e" | "edit"), None) => TuiAction::Edit,
+            (Some("r" | "regenerate"), engine) => {
+                let engine = engine
+                    .map(|engine| {
+                        TranslatorEngine::from_str(engine)
+                            .map_err(|_| PotrError::parse(format!("Unknown engine: {}", engine)))
+                    })
+                    .transpose()?;
+                TuiAction::Regenerate(engine)
+            }
+            (Some("f" | "fuzzy"), None) => TuiAction::MarkFuzzy,
+            (Some("s" | "skip"), None) => TuiAction::Skip,
+            (Some("q" | "quit"), None) => TuiAction::Quit,
+            _ => return Err(PotrError::parse(format!("Unknown action: {}", s.trim()))),
+        };
+        Ok(action)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TuiSummary {
+    pub reviewed_count: usize,
+    pub changed_count: usize,
+    pub remaining_count: usize,
+}
+
+/// Steps through the selected messages in the terminal, and lets the reviewer decide what to do
+/// with each of them. Changes are saved after each message, so nothing is lost on quit.
+pub struct TuiReviewer {
+    pub config: TuiConfig,
+
+    /// The first translator makes the suggestions, and the others can be picked to regenerate.
+    translators: Vec<Box<dyn Translator>>,
+}
+
+impl TuiReviewer {
+    pub fn new(config: TuiConfig, translators: Vec<Box<dyn Translator>>) -> TuiReviewer {
+        TuiReviewer {
+            config,
+            translators,
+        }
+    }
+
+    pub async fn run(&self) -> Result<TuiSummary, PotrError> {
+        tracing::info!("Loading po file: {}", self.config.po_file_path);
+        let mut po_file = formats::load_po_file(&self.config.po_file_path)?;
+
+        let output_file_path = Path::new(&self.config.output_file_path);
+        let stdin = std::io::stdin();
+        self.review(&mut po_file, stdin.lock(), std::io::stdout(), |catalog| {
+            tracing::debug!("Write to output: {:?}", output_file_path);
+            polib::po_file::write(catalog, output_file_path)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Review the selected messages with the actions read from `input`. `save` is called after
+    /// each changed message.
+    pub async fn review<R: BufRead, W: Write>(
+        &self,
+        catalog: &mut Catalog,
+        mut input: R,
+        mut output: W,
+        mut save: impl FnMut(&Catalog) -> Result<(), PotrError>,
+    ) -> Result<TuiSummary, PotrError> {
+        if self.translators.is_empty() {
+            return Err(PotrError::config(
+                "No translator is available for suggestions.",
+            ));
+        }
+
+        let indices: Vec<usize> = catalog
+            .messages()
+            .enumerate()
+            .filter(|(_, message)| self.is_selected(*message))
+            .map(|(index, _)| index)
+            .collect();
+
+        let mut summary = TuiSummary {
+            remaining_count: indices.len(),
+            ..Default::default()
+        };
+        for (position, index) in indices.iter().enumerate() {
+            let message = catalog.messages().nth(*index).unwrap();
+            let msgid = message.msgid().to_string();
+            writeln!(
+                output,
+                "\n[{}/{}] {}",
+                position + 1,
+                indices.len(),
+                message.source()
+            )?;
+            if !message.msgctxt().is_empty() {
+                writeln!(output, "Context:     {}", indent(message.msgctxt()))?;
+            }
+            writeln!(output, "Source:      {}", indent(&msgid))?;
+
+            let mut translator_index = 0;
+            let mut suggestion = self.suggest(translator_index, &msgid, &mut output).await?;
+            // Only singular messages are selected, so msgstr can always be read and set.
+            let changed = loop {
+                let message = catalog.messages().nth(*index).unwrap();
+                writeln!(
+                    output,
+                    "Translation: {}{}",
+                    indent(message.msgstr().unwrap()),
+                    if message.is_fuzzy() { " [fuzzy]" } else { "" }
+                )?;
+                write!(output, "[a/k/e/r/f/s/q, ? for help] > ")?;
+                output.flush()?;
+
+                // End of input quits the review, e.g. Ctrl-D.
+                let line = read_line(&mut input)?.unwrap_or_else(|| "q".to_string());
+                if line.trim() == "?" {
+                    writeln!(output, "{}", HELP)?;
+                    continue;
+                }
+
+                let action = match TuiAction::from_str(&line) {
+                    Ok(action) => action,
+                    Err(e) => {
+                        writeln!(output, "{}\n{}", e, HELP)?;
+                        continue;
+                    }
+                };
+
+                let mut message = catalog.messages_mut().nth(*index).unwrap();
+                match action {
+                    TuiAction::Accept => match &suggestion {
+                        Some(text) => {
+                            message.set_msgstr(text.clone()).unwrap();
+                            message.flags_mut().remove_flag("fuzzy");
+                            break true;
+                        }
+                        None => writeln!(output, "No suggestion to accept.")?,
+                    },
+                    TuiAction::Keep => {
+                        let changed = message.is_fuzzy();
+                        message.flags_mut().remove_flag("fuzzy");
+                        break changed;
+                    }
+                    TuiAction::Edit => {
+                        write!(output, "New translation (empty to cancel) > ")?;
+                        output.flush()?;
+                        match read_line(&mut input)? {
+                            Some(text) if !text.trim().is_empty() => {
+                                message.set_msgstr(text.replace("\\n", "\n")).unwrap();
+                                message.flags_mut().remove_flag("fuzzy");
+                                break true;
+                            }
+                            _ => continue,
+                        }
+                    }
+                    TuiAction::Regenerate(engine) => {
+                        translator_index = match engine {
+                            Some(engine) => match self
+                                .translators
+                                .iter()
+                                .position(|translator| translator.name() == engine)
+                            {
+                                Some(index) => index,
+                                None => {
+                                    writeln!(output, "Engine is not available: {}", engine)?;
+                                    continue;
+                                }
+                            },
+                            None => (translator_index + 1) % self.translators.len(),
+                        };
+                        suggestion = self.suggest(translator_index, &msgid, &mut output).await?;
+                    }
+                    TuiAction::MarkFuzzy => {
+                        if !message.is_translated() {
+                            if let Some(text) = &suggestion {
+                                message.set_msgstr(text.clone()).unwrap();
+                            }
+                        }
+                        message.flags_mut().add_flag("fuzzy");
+                        break true;
+                    }
+                    TuiAction::Skip => break false,
+                    TuiAction::Quit => {
+                        writeln!(output)?;
+                        return Ok(summary);
+                    }
+                }
+            };
+
+            summary.reviewed_count += 1;
+            summary.remaining_count -= 1;
+            if changed {
+                summary.changed_count += 1;
+                save(catalog)?;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    fn is_selected(&self, message: &dyn MessageView) -> bool {
+        // Plural forms are not supported yet.
+        if !message.is_singular() {
+            return false;
+        }
+
+        let is_selected = if self.config.fuzzy_only {
+            message.is_fuzzy()
+        } else {
+            message.is_fuzzy() || message.is_translated()
+        };
+        is_selected
+            && self
+                .config
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.is_match(message))
+    }
+
+    /// Get a suggestion from the translator. Errors are shown to the reviewer, instead of
+    /// stopping the review.
+    async fn suggest<W: Write>(
+        &self,
+        translator_index: usize,
+        msgid: &str,
+        output: &mut W,
+    ) -> std::io::Result<Option<String>> {
+        let translator = &self.translators[translator_index];
+        match translator.translate(msgid).await {
+            Ok(text) => {
+                writeln!(
+                    output,
+                    "Suggestion:  {} ({})",
+                    indent(&text),
+                    translator.name()
+                )?;
+                Ok(Some(text))
+            }
+            Err(e) => {
+                writeln!(output, "Suggestion:  failed ({}): {}", translator.name(), e)?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Indent the following lines of a multiline text, so they are aligned with the first line.
+fn indent(text: &str) -> String {
+    text.replace('\n', "\n             ")
+}
+
+/// Read a line without the line ending. `None` means the end of input.
+fn read_line<R: BufRead>(input: &mut R) -> std::io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
@@ -0,0 +1,561 @@
+use crate::{
+    error::PotrError,
+    filter::message_source_refs,
+    formats,
+    potr::{fingerprint, Potr, PotrConfig},
+    translators::{Language, TranslatorConfig},
+};
+use anyhow::Result;
+use polib::{
+    catalog::Catalog,
+    message::{MessageMutView, MessageView},
+};
+use roxmltree::{Document, Node};
+use serde::Serialize;
+use std::{fmt, fmt::Write, path::Path};
+use strum_macros::{Display, EnumString};
+
+const XLIFF_1_2_NAMESPACE: &str = "urn:oasis:names:tc:xliff:document:1.2";
+const XLIFF_2_0_NAMESPACE: &str = "urn:oasis:names:tc:xliff:document:2.0";
+
+/// Namespace of the potr extensions, e.g. the fingerprint of msgstr at export time.
+const POTR_NAMESPACE: &str = "https://github.com/r12f/potr";
+
+/// Sub-state of the XLIFF 2.0 segments exported from fuzzy messages, as 2.0 has no review state.
+const FUZZY_SUB_STATE: &str = "potr:fuzzy";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, EnumString)]
+pub enum XliffVersion {
+    #[default]
+    #[strum(serialize = "1.2")]
+    V1_2,
+    #[strum(serialize = "2.0")]
+    V2_0,
+}
+
+#[derive(Debug, Clone)]
+pub struct XliffConfig {
+    pub po_file_path: String,
+    pub xliff_file_path: String,
+
+    /// PO file to write the imported translations to.
+    pub output_file_path: String,
+
+    /// Version of the exported file. Imported files are detected by their version attribute.
+    pub version: XliffVersion,
+
+    pub source_lang: Language,
+    pub target_lang: Language,
+}
+
+impl Default for XliffConfig {
+    fn default() -> Self {
+        Self {
+            po_file_path: Default::default(),
+            xliff_file_path: Default::default(),
+            output_file_path: Default::default(),
+            version: XliffVersion::default(),
+            source_lang: Language::English,
+            target_lang: Language::English,
+        }
+    }
+}
+
+/// Exports the messages selected for translation to XLIFF, so they can be translated in CAT
+/// tools. The selection is the same as the translate command, with its filters and rules.
+pub struct XliffExporter {
+    pub config: XliffConfig,
+
+    /// Only the message selection is used, so no translator is ever created.
+    potr: Potr,
+}
+
+impl XliffExporter {
+    pub fn new(config: XliffConfig, potr_config: PotrConfig) -> XliffExporter {
+        XliffExporter {
+            config,
+            potr: Potr::new(potr_config, TranslatorConfig::default()),
+        }
+    }
+
+    pub fn run(&self) -> Result<usize, PotrError> {
+        tracing::info!("Loading po file: {}", self.config.po_file_path);
+        let po_file = formats::load_po_file(&self.config.po_file_path)?;
+
+        let (xliff, exported_count) = self.export(&po_file);
+        tracing::info!("Export completed! Exported = {}", exported_count);
+
+        tracing::info!("Write to output: {:?}", self.config.xliff_file_path);
+        std::fs::write(&self.config.xliff_file_path, xliff)?;
+        Ok(exported_count)
+    }
+
+    /// Export the selected messages into an XLIFF document, and return it with the number of
+    /// exported messages.
+    pub fn export(&self, catalog: &Catalog) -> (String, usize) {
+        let messages = self.potr.select_messages(catalog);
+        let original = Path::new(&self.config.po_file_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut xliff = String::new();
+        writeln!(xliff, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        match self.config.version {
+            XliffVersion::V1_2 => {
+                writeln!(
+                    xliff,
+                    r#"<xliff version="1.2" xmlns="{}" xmlns:potr="{}">"#,
+                    XLIFF_1_2_NAMESPACE, POTR_NAMESPACE
+                )
+                .unwrap();
+                writeln!(
+                    xliff,
+                    r#"  <file original="{}" source-language="{}" target-language="{}" datatype="po">"#,
+                    escape(&original),
+                    self.config.source_lang.as_ref(),
+                    self.config.target_lang.as_ref()
+                )
+                .unwrap();
+                writeln!(xliff, "    <body>").unwrap();
+                for (index, message) in messages.iter().enumerate() {
+                    write_trans_unit(&mut xliff, index + 1, *message);
+                }
+                writeln!(xliff, "    </body>").unwrap();
+                writeln!(xliff, "  </file>").unwrap();
+            }
+            XliffVersion::V2_0 => {
+                writeln!(
+                    xliff,
+                    r#"<xliff version="2.0" xmlns="{}" xmlns:potr="{}" srcLang="{}" trgLang="{}">"#,
+                    XLIFF_2_0_NAMESPACE,
+                    POTR_NAMESPACE,
+                    self.config.source_lang.as_ref(),
+                    self.config.target_lang.as_ref()
+                )
+                .unwrap();
+                writeln!(
+                    xliff,
+                    r#"  <file id="f1" original="{}">"#,
+                    escape(&original)
+                )
+                .unwrap();
+                for (index, message) in messages.iter().enumerate() {
+                    write_unit(&mut xliff, index + 1, *message);
+                }
+                writeln!(xliff, "  </file>").unwrap();
+            }
+        }
+        writeln!(xliff, "</xliff>").unwrap();
+
+        (xliff, messages.len())
+    }
+}
+
+fn write_trans_unit(xliff: &mut String, id: usize, message: &dyn MessageView) {
+    let msgstr = message.msgstr().unwrap_or_default();
+    writeln!(
+        xliff,
+        r#"      <trans-unit id="{}" xml:space="preserve" potr:fingerprint="{}">"#,
+        id,
+        fingerprint(msgstr)
+    )
+    .unwrap();
+    writeln!(
+        xliff,
+        "        <source>{}</source>",
+        escape(message.msgid())
+    )
+    .unwrap();
+    if message.is_translated() {
+        let state = if message.is_fuzzy() {
+            "needs-review-translation"
+        } else {
+            "translated"
+        };
+        writeln!(
+            xliff,
+            r#"        <target state="{}">{}</target>"#,
+            state,
+            escape(msgstr)
+        )
+        .unwrap();
+    }
+    if !message.msgctxt().is_empty() {
+        writeln!(
+            xliff,
+            r#"        <context-group name="po-entry" purpose="information"><context context-type="x-po-msgctxt">{}</context></context-group>"#,
+            escape(message.msgctxt())
+        )
+        .unwrap();
+    }
+    for (path, line) in message_source_refs(message) {
+        write!(
+            xliff,
+            r#"        <context-group name="po-reference" purpose="location"><context context-type="sourcefile">{}</context>"#,
+            escape(path)
+        )
+        .unwrap();
+        if let Some(line) = line {
+            write!(
+                xliff,
+                r#"<context context-type="linenumber">{}</context>"#,
+                line
+            )
+            .unwrap();
+        }
+        writeln!(xliff, "</context-group>").unwrap();
+    }
+    if !message.comments().is_empty() {
+        writeln!(
+            xliff,
+            r#"        <note from="developer">{}</note>"#,
+            escape(message.comments())
+        )
+        .unwrap();
+    }
+    writeln!(xliff, "      </trans-unit>").unwrap();
+}
+
+fn write_unit(xliff: &mut String, id: usize, message: &dyn MessageView) {
+    let msgstr = message.msgstr().unwrap_or_default();
+    writeln!(
+        xliff,
+        r#"    <unit id="{}" potr:fingerprint="{}">"#,
+        id,
+        fingerprint(msgstr)
+    )
+    .unwrap();
+
+    let source_refs = message_source_refs(message);
+    if !message.msgctxt().is_empty() || !source_refs.is_empty() || !message.comments().is_empty() {
+        writeln!(xliff, "      <notes>").unwrap();
+        if !message.msgctxt().is_empty() {
+            writeln!(
+                xliff,
+                r#"        <note category="msgctxt">{}</note>"#,
+                escape(message.msgctxt())
+            )
+            .unwrap();
+        }
+        for (path, line) in source_refs {
+            let location = match line {
+                Some(line) => format!("{}:{}", path, line),
+                None => path.to_string(),
+            };
+            writeln!(
+                xliff,
+                r#"        <note category="location">{}</note>"#,
+                escape(&location)
+            )
+            .unwrap();
+        }
+        if !message.comments().is_empty() {
+            writeln!(
+                xliff,
+                r#"        <note category="developer">{}</note>"#,
+                escape(message.comments())
+            )
+            .unwrap();
+        }
+        writeln!(xliff, "      </notes>").unwrap();
+    }
+
+    let state = match (message.is_translated(), message.is_fuzzy()) {
+        (false, _) => r#"state="initial""#.to_string(),
+        (true, true) => format!(r#"state="translated" subState="{}""#, FUZZY_SUB_STATE),
+        (true, false) => r#"state="translated""#.to_string(),
+    };
+    writeln!(xliff, "      <segment {}>", state).unwrap();
+    writeln!(
+        xliff,
+        r#"        <source xml:space="preserve">{}</source>"#,
+        escape(message.msgid())
+    )
+    .unwrap();
+    if message.is_translated() {
+        writeln!(
+            xliff,
+            r#"        <target xml:space="preserve">{}</target>"#,
+            escape(msgstr)
+        )
+        .unwrap();
+    }
+    writeln!(xliff, "      </segment>").unwrap();
+    writeln!(xliff, "    </unit>").unwrap();
+}
+
+/// Why a translated unit is not imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum XliffConflictReason {
+    /// The message is not in the PO file anymore, e.g. removed by merge.
+    NotFound,
+
+    /// The translation in the PO file is changed since the export.
+    Changed,
+
+    /// The message has plural forms, which can't be imported from a single target.
+    Plural,
+}
+
+impl fmt::Display for XliffConflictReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            XliffConflictReason::NotFound => "message not found",
+            XliffConflictReason::Changed => "translation changed since export",
+            XliffConflictReason::Plural => "plural message not supported",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct XliffConflict {
+    pub msgid: String,
+    pub msgctxt: String,
+    pub reason: XliffConflictReason,
+}
+
+impl fmt::Display for XliffConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.msgctxt.is_empty() {
+            write!(f, "{}: {:?}", self.reason, self.msgid)
+        } else {
+            write!(f, "{}: {:?} ({})", self.reason, self.msgid, self.msgctxt)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct XliffImportReport {
+    pub imported_count: usize,
+    pub untranslated_count: usize,
+
+    /// Units left as exported, while the PO file is changed since the export. The translations
+    /// in the PO file are kept.
+    pub outdated_count: usize,
+
+    pub conflicts: Vec<XliffConflict>,
+}
+
+/// Translation unit read from an XLIFF file.
+struct XliffUnit {
+    msgid: String,
+    msgctxt: String,
+    target: String,
+    fuzzy: bool,
+    fingerprint: Option<String>,
+}
+
+/// Merges the translations in an XLIFF file back into the PO file. Units whose message is
+/// removed, or changed in both the PO file and the XLIFF file since the export, are reported as
+/// conflicts instead of being imported.
+pub struct XliffImporter {
+    pub config: XliffConfig,
+}
+
+impl XliffImporter {
+    pub fn new(config: XliffConfig) -> XliffImporter {
+        XliffImporter { config }
+    }
+
+    pub fn run(&self) -> Result<XliffImportReport, PotrError> {
+        tracing::info!("Loading po file: {}", self.config.po_file_path);
+        let mut po_file = formats::load_po_file(&self.config.po_file_path)?;
+
+        tracing::info!("Loading xliff file: {}", self.config.xliff_file_path);
+        let xliff = std::fs::read_to_string(&self.config.xliff_file_path)?;
+
+        let report = self.import(&mut po_file, &xliff)?;
+        tracing::info!(
+            "Import completed! Imported = {}, Untranslated = {}, Outdated = {}, Conflicts = {}",
+            report.imported_count,
+            report.untranslated_count,
+            report.outdated_count,
+            report.conflicts.len()
+        );
+
+        tracing::info!("Write to output: {:?}", self.config.output_file_path);
+        polib::po_file::write(&po_file, Path::new(&self.config.output_file_path))?;
+        Ok(report)
+    }
+
+    pub fn import(
+        &self,
+        catalog: &mut Catalog,
+        xliff: &str,
+    ) -> Result<XliffImportReport, PotrError> {
+        let mut report = XliffImportReport::default();
+        for unit in parse_units(xliff)? {
+            if unit.target.is_empty() {
+                report.untranslated_count += 1;
+                continue;
+            }
+
+            // Plural messages are never exported, but can be in the files of other tools.
+            if catalog.messages().any(|message| {
+                !message.is_singular()
+                    && message.msgctxt() == unit.msgctxt
+                    && message.msgid() == unit.msgid
+            }) {
+                report.add_conflict(unit, XliffConflictReason::Plural);
+                continue;
+            }
+
+            let msgctxt = Some(unit.msgctxt.as_str()).filter(|s| !s.is_empty());
+            let mut message = match catalog.find_message_mut(msgctxt, &unit.msgid, None) {
+                Some(message) => message,
+                None => {
+                    report.add_conflict(unit, XliffConflictReason::NotFound);
+                    continue;
+                }
+            };
+
+            // Without the fingerprint, e.g. files not exported by potr, the PO file is assumed
+            // to be unchanged.
+            let msgstr = message.msgstr().unwrap_or_default();
+            if let Some(exported) = unit
+                .fingerprint
+                .as_ref()
+                .filter(|exported| **exported != fingerprint(msgstr) && msgstr != unit.target)
+            {
+                if *exported == fingerprint(&unit.target) {
+                    report.outdated_count += 1;
+                } else {
+                    report.add_conflict(unit, XliffConflictReason::Changed);
+                }
+                continue;
+            }
+
+            // Only singular messages are found without msgid_plural.
+            message.set_msgstr(unit.target).unwrap();
+            if unit.fuzzy {
+                message.flags_mut().add_flag("fuzzy");
+            } else {
+                message.flags_mut().remove_flag("fuzzy");
+            }
+            report.imported_count += 1;
+        }
+
+        Ok(report)
+    }
+}
+
+impl XliffImportReport {
+    fn add_conflict(&mut self, unit: XliffUnit, reason: XliffConflictReason) {
+        tracing::warn!(
+            "Conflict found: Reason = {}, Message = {}",
+            reason,
+            unit.msgid
+        );
+        self.conflicts.push(XliffConflict {
+            msgid: unit.msgid,
+            msgctxt: unit.msgctxt,
+            reason,
+        });
+    }
+}
+
+fn parse_units(xliff: &str) -> Result<Vec<XliffUnit>, PotrError> {
+    let document = Document::parse(xliff)?;
+    let root = document.root_element();
+    match root.attribute("version") {
+        Some(version) if version.starts_with("1.") => Ok(root
+            .descendants()
+            .filter(|node| node.has_tag_name((XLIFF_1_2_NAMESPACE, "trans-unit")))
+            .map(parse_trans_unit)
+            .collect()),
+        Some(version) if version.starts_with("2.") => Ok(root
+            .descendants()
+            .filter(|node| node.has_tag_name((XLIFF_2_0_NAMESPACE, "unit")))
+            .map(parse_unit)
+            .collect()),
+        _ => Err(PotrError::parse(format!(
+            "Unsupported XLIFF version: {:?}",
+            root.attribute("version")
+        ))),
+    }
+}
+
+fn parse_trans_unit(node: Node) -> XliffUnit {
+    let child = |name: &str| {
+        node.children()
+            .find(|child| child.has_tag_name((XLIFF_1_2_NAMESPACE, name)))
+    };
+
+    let target = child("target");
+    let fuzzy = match target.and_then(|target| target.attribute("state")) {
+        Some(state) => state.starts_with("needs-"),
+        None => false,
+    };
+    XliffUnit {
+        msgid: child("source").map(text_of).unwrap_or_default(),
+        msgctxt: node
+            .descendants()
+            .find(|context| context.attribute("context-type") == Some("x-po-msgctxt"))
+            .map(text_of)
+            .unwrap_or_default(),
+        target: target.map(text_of).unwrap_or_default(),
+        fuzzy,
+        fingerprint: node
+            .attribute((POTR_NAMESPACE, "fingerprint"))
+            .map(str::to_string),
+    }
+}
+
+fn parse_unit(node: Node) -> XliffUnit {
+    let mut unit = XliffUnit {
+        msgid: String::new(),
+        msgctxt: node
+            .descendants()
+            .find(|note| {
+                note.has_tag_name((XLIFF_2_0_NAMESPACE, "note"))
+                    && note.attribute("category") == Some("msgctxt")
+            })
+            .map(text_of)
+            .unwrap_or_default(),
+        target: String::new(),
+        fuzzy: false,
+        fingerprint: node
+            .attribute((POTR_NAMESPACE, "fingerprint"))
+            .map(str::to_string),
+    };
+
+    // CAT tools may split the unit into more segments, so they are joined back in order.
+    for segment in node.children().filter(|child| {
+        child.has_tag_name((XLIFF_2_0_NAMESPACE, "segment"))
+            || child.has_tag_name((XLIFF_2_0_NAMESPACE, "ignorable"))
+    }) {
+        for child in segment.children() {
+            if child.has_tag_name((XLIFF_2_0_NAMESPACE, "source")) {
+                unit.msgid += &text_of(child);
+            } else if child.has_tag_name((XLIFF_2_0_NAMESPACE, "target")) {
+                unit.target += &text_of(child);
+            }
+        }
+
+        // Initial segments with a target are drafts, so they are imported as fuzzy too.
+        if matches!(segment.attribute("state"), Some("initial"))
+            || segment.attribute("subState") == Some(FUZZY_SUB_STATE)
+        {
+            unit.fuzzy = true;
+        }
+    }
+    unit
+}
+
+/// Text of an element, including the text inside its inline elements.
+fn text_of(node: Node) -> String {
+    node.descendants()
+        .filter(|child| child.is_text())
+        .filter_map(|child| child.text())
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
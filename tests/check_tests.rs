@@ -0,0 +1,86 @@
+use potr::*;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn check_should_report_problems_in_translations() {
+    let mut check_config = CheckConfig::default();
+    check_config.po_file_path = "tests/data/check-input.po".to_string();
+
+    let report = PoChecker::new(check_config)
+        .run()
+        .expect("Failed to run check");
+
+    let issues: Vec<(&str, CheckKind)> = report
+        .issues
+        .iter()
+        .map(|i| (i.msgid.as_str(), i.kind))
+        .collect();
+    assert_eq!(report.checked_count, 10);
+    assert_eq!(
+        issues,
+        vec![
+            ("Hello, {name}! You have %d pens.", CheckKind::Placeholder),
+            (
+                "See [the guide](guide.md) for `pen` usage.",
+                CheckKind::Markdown
+            ),
+            ("Pens (and pencils) are tools.", CheckKind::Brackets),
+            ("Buy a pen!\n", CheckKind::Whitespace),
+            ("That is a pencil!", CheckKind::Untranslated),
+            ("That is a pencil!", CheckKind::Script),
+            (
+                "A pen is a tool for writing and drawing with ink on paper.",
+                CheckKind::Length
+            ),
+            ("This is a pen!", CheckKind::Duplicate),
+            ("This is a pen too!", CheckKind::Duplicate),
+        ]
+    );
+}
+
+#[test]
+fn check_should_only_check_messages_matching_filter() {
+    let mut check_config = CheckConfig::default();
+    check_config.po_file_path = "tests/data/check-input.po".to_string();
+    check_config.filter = Some(MessageFilter::parse("line <= 5").unwrap());
+
+    let report = PoChecker::new(check_config)
+        .run()
+        .expect("Failed to run check");
+
+    let issues: Vec<(&str, CheckKind)> = report
+        .issues
+        .iter()
+        .map(|i| (i.msgid.as_str(), i.kind))
+        .collect();
+    assert_eq!(report.checked_count, 5);
+    assert_eq!(
+        issues,
+        vec![
+            ("Hello, {name}! You have %d pens.", CheckKind::Placeholder),
+            (
+                "See [the guide](guide.md) for `pen` usage.",
+                CheckKind::Markdown
+            ),
+            ("Pens (and pencils) are tools.", CheckKind::Brackets),
+        ]
+    );
+}
+
+#[test]
+fn check_should_mark_messages_with_problems_as_fuzzy() {
+    let mut check_config = CheckConfig::default();
+    check_config.po_file_path = "tests/data/check-input.po".to_string();
+    check_config.output_file_path = "tests/data/check-fuzzy-result.po".to_string();
+    check_config.mark_fuzzy = true;
+
+    let report = PoChecker::new(check_config.clone())
+        .run()
+        .expect("Failed to run check");
+    assert_eq!(report.marked_fuzzy_count, 8);
+
+    let result = polib::po_file::parse(std::path::Path::new(&check_config.output_file_path))
+        .expect("Failed to parse result file");
+    let fuzzy_count = result.messages().filter(|m| m.is_fuzzy()).count();
+    assert_eq!(fuzzy_count, 8);
+}
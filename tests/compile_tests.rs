@@ -0,0 +1,102 @@
+use polib::catalog::Catalog;
+use potr::*;
+use pretty_assertions::assert_eq;
+use std::path::Path;
+
+#[test]
+fn compile_should_skip_fuzzy_messages_by_default() {
+    let compile_config = CompileConfig::default();
+
+    let (stats, catalog) = run_compile_test("compile", compile_config);
+    assert_eq!(
+        stats,
+        CompileStats {
+            compiled_count: 4,
+            fuzzy_skipped_count: 1,
+            untranslated_skipped_count: 1,
+            filtered_count: 0,
+        }
+    );
+    assert!(catalog
+        .find_message(None, "That is an apple!", None)
+        .is_none());
+}
+
+#[test]
+fn compile_should_include_fuzzy_messages_when_specified() {
+    let mut compile_config = CompileConfig::default();
+    compile_config.include_fuzzy = true;
+
+    let (stats, catalog) = run_compile_test("compile-fuzzy", compile_config);
+    assert_eq!(
+        stats,
+        CompileStats {
+            compiled_count: 5,
+            fuzzy_skipped_count: 0,
+            untranslated_skipped_count: 1,
+            filtered_count: 0,
+        }
+    );
+    assert_eq!(
+        catalog
+            .find_message(None, "That is an apple!", None)
+            .unwrap()
+            .msgstr()
+            .unwrap(),
+        "Das ist ein Apfel?"
+    );
+}
+
+#[test]
+fn compile_should_skip_messages_not_matching_filter() {
+    let mut compile_config = CompileConfig::default();
+    compile_config.filter = Some(MessageFilter::parse("!plural && msgctxt == \"\"").unwrap());
+
+    let (stats, _) = run_compile_test("compile-filter", compile_config);
+    assert_eq!(
+        stats,
+        CompileStats {
+            compiled_count: 2,
+            fuzzy_skipped_count: 1,
+            untranslated_skipped_count: 1,
+            filtered_count: 2,
+        }
+    );
+}
+
+/// Compile the PO file, parse the MO file back, and check every compiled message round trips.
+fn run_compile_test(test_name: &str, mut compile_config: CompileConfig) -> (CompileStats, Catalog) {
+    compile_config.po_file_path = "tests/data/compile-input.po".to_string();
+    compile_config.output_file_path = format!("tests/data/{}-result.mo", test_name);
+
+    let compiler = MoCompiler::new(compile_config.clone());
+    let stats = compiler.run().expect("Failed to run compile");
+
+    let data = std::fs::read(&compile_config.output_file_path).expect("Failed to read result file");
+    let catalog = parse_mo(&data).expect("Failed to parse result file");
+
+    let po_file = polib::po_file::parse(Path::new(&compile_config.po_file_path))
+        .expect("Failed to parse po file");
+    assert_eq!(catalog.metadata.language, po_file.metadata.language);
+    assert_eq!(
+        catalog.metadata.plural_rules.dump(),
+        po_file.metadata.plural_rules.dump()
+    );
+
+    let mut compiled_count = 0;
+    for message in po_file.messages() {
+        let msgctxt = Some(message.msgctxt()).filter(|s| !s.is_empty());
+        let compiled = catalog.find_message(msgctxt, message.msgid(), message.msgid_plural().ok());
+        let compiled = match compiled {
+            Some(compiled) => compiled,
+            None => continue,
+        };
+
+        compiled_count += 1;
+        assert_eq!(compiled.msgstr().ok(), message.msgstr().ok());
+        assert_eq!(compiled.msgstr_plural().ok(), message.msgstr_plural().ok());
+    }
+    assert_eq!(compiled_count, stats.compiled_count);
+
+    (stats, catalog)
+}
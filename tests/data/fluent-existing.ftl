@@ -0,0 +1,4 @@
+-brand = Potr
+
+login-input = 预定义的值
+    .placeholder = email@example.com
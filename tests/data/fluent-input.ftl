@@ -0,0 +1,19 @@
+### Messages of the home page.
+
+# Shown when the user is logged in.
+# $name (String) - Name of the user.
+welcome = Welcome, { $name }!
+
+-brand = Potr
+
+login-input = Predefined value
+    .placeholder = email@example.com
+    .aria-label = Login input value
+
+## Notifications
+
+emails =
+    { $unread_emails ->
+        [one] You have one unread email.
+       *[other] You have { $unread_emails } unread emails.
+    }
@@ -0,0 +1,90 @@
+use potr::*;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn merge_should_update_po_file_from_template() {
+    let merge_config = MergeConfig::default();
+
+    let stats = run_merge_test("merge", merge_config);
+    assert_eq!(
+        stats,
+        MergeStats {
+            matched_count: 1,
+            fuzzy_count: 1,
+            untranslated_count: 1,
+            obsolete_count: 1,
+        }
+    );
+}
+
+#[test]
+fn merge_should_not_use_fuzzy_matching_when_disabled() {
+    let mut merge_config = MergeConfig::default();
+    merge_config.fuzzy_matching = false;
+
+    let stats = run_merge_test("merge-no-fuzzy", merge_config);
+    assert_eq!(
+        stats,
+        MergeStats {
+            matched_count: 1,
+            fuzzy_count: 0,
+            untranslated_count: 2,
+            obsolete_count: 2,
+        }
+    );
+}
+
+#[test]
+fn merge_should_keep_obsolete_entries_and_bring_back_matching_ones() {
+    let mut merge_config = MergeConfig::default();
+    merge_config.po_file_path = "tests/data/merge-obsolete-input.po".to_string();
+
+    let stats = run_merge_test("merge-obsolete", merge_config);
+    assert_eq!(
+        stats,
+        MergeStats {
+            matched_count: 2,
+            fuzzy_count: 1,
+            untranslated_count: 0,
+            obsolete_count: 1,
+        }
+    );
+}
+
+#[test]
+fn merge_should_only_use_fuzzy_matching_for_messages_matching_filter() {
+    let mut merge_config = MergeConfig::default();
+    merge_config.filter = Some(MessageFilter::parse("len(msgid) > 30").unwrap());
+
+    let stats = run_merge_test("merge-filter", merge_config);
+    assert_eq!(stats.fuzzy_count, 0);
+    assert_eq!(stats.untranslated_count, 2);
+}
+
+fn run_merge_test(test_name: &str, mut merge_config: MergeConfig) -> MergeStats {
+    if merge_config.po_file_path.is_empty() {
+        merge_config.po_file_path = "tests/data/merge-input.po".to_string();
+    }
+    merge_config.pot_file_path = "tests/data/merge-input.pot".to_string();
+    merge_config.output_file_path = format!("tests/data/{}-result.po", test_name);
+
+    let merger = PoMerger::new(merge_config.clone());
+    let stats = merger.run().expect("Failed to run merge");
+
+    let result = std::fs::read_to_string(&merge_config.output_file_path)
+        .expect("Failed to read result file");
+
+    let expected_file_path = format!("tests/data/{}-expected.po", test_name);
+
+    // If POTR_GENERATE_TEST_RESULT environment variable is set, we replace the expected file with the result file.
+    if std::env::var("POTR_GENERATE_TEST_RESULT").is_ok() {
+        std::fs::write(&expected_file_path, result).expect("Failed to write expected file");
+    } else {
+        let expected =
+            std::fs::read_to_string(&expected_file_path).expect("Failed to read expected file");
+
+        assert_eq!(result, expected);
+    }
+
+    stats
+}
@@ -1,6 +1,16 @@
+use async_trait::async_trait;
+use polib::message::MessageView;
 use potr::*;
 use pretty_assertions::assert_eq;
 use regex::Regex;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
 
 #[tokio::test]
 async fn potr_should_translate_untranslated_messages() {
@@ -41,6 +51,14 @@ async fn potr_should_translate_messages_from_filtered_source_file() {
     run_potr_test("source-filter", potr_config).await;
 }
 
+#[tokio::test]
+async fn potr_should_translate_messages_matching_filter() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.filter = Some(MessageFilter::parse("source !~ \"Pen\" && line == 3").unwrap());
+
+    run_potr_test("filter", potr_config).await;
+}
+
 #[tokio::test]
 async fn potr_should_translate_code_block_messages() {
     let mut potr_config = PotrConfig::default();
@@ -74,11 +92,544 @@ async fn potr_should_skip_non_fuzzy_message() {
     run_potr_test("fuzzy-filter", potr_config).await;
 }
 
-async fn run_potr_test(test_name: &str, mut potr_config: PotrConfig) {
-    potr_config.po_file_path = format!("tests/data/{}-input.po", test_name);
-    potr_config.output_file_path = format!("tests/data/{}-result.po", test_name);
+#[tokio::test]
+async fn potr_should_stop_translating_when_budget_is_exceeded() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.skip_translated = false;
+    potr_config.budget.max_characters = Some(40);
+
+    run_potr_test("budget", potr_config).await;
+}
+
+#[tokio::test]
+async fn potr_should_apply_first_matching_rule() {
+    let mut potr_config = PotrConfig::default();
+
+    let mut skip_button_rule = TranslationRule::default();
+    skip_button_rule.msgctxt = Some(Regex::new("^button$").unwrap());
+    skip_button_rule.skip = true;
+
+    let mut api_rule = TranslationRule::default();
+    api_rule.source = Some(TranslationRule::source_from_glob("src/api/**").unwrap());
+    api_rule.engine = Some(TranslatorEngine::Clear);
+    api_rule.skip_translated = Some(false);
+    api_rule.skip_code_blocks = Some(false);
+
+    let mut guide_rule = TranslationRule::default();
+    guide_rule.source = Some(TranslationRule::source_from_glob("src/guide/**").unwrap());
+    guide_rule.as_fuzzy = Some(true);
+
+    potr_config.rules = vec![skip_button_rule, api_rule, guide_rule];
+
+    run_potr_test("rules", potr_config).await;
+}
+
+#[tokio::test]
+async fn potr_should_write_run_report() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.skip_translated = false;
+    potr_config.message_limit = 2;
+    potr_config.report_file_path = Some("tests/data/report-result.json".to_string());
+
+    run_potr_test("report", potr_config).await;
+
+    let report = std::fs::read_to_string("tests/data/report-result.json")
+        .expect("Failed to read report file");
+    let report: serde_json::Value = serde_json::from_str(&report).expect("Failed to parse report");
+    assert_eq!(report["engine"], "clone");
+    assert_eq!(report["totals"]["message_count"], 5);
+    assert_eq!(report["totals"]["translated_count"], 2);
+    assert_eq!(report["totals"]["skipped_count"], 3);
+    assert_eq!(report["totals"]["failed_count"], 0);
+    assert_eq!(report["totals"]["usage"]["characters"], 31);
+
+    let outcomes: Vec<String> = report["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| match m["reason"].as_str() {
+            Some(reason) => format!("{}: {}", m["status"].as_str().unwrap(), reason),
+            None => m["status"].as_str().unwrap().to_string(),
+        })
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            "translated",
+            "translated",
+            "skipped: code_block",
+            "skipped: limit",
+            "skipped: limit"
+        ]
+    );
+    assert_eq!(report["messages"][4]["msgctxt"], "button");
+}
+
+#[tokio::test]
+async fn potr_should_translate_json_files() {
+    run_format_test("json", "json", PotrConfig::default()).await;
+}
+
+#[tokio::test]
+async fn potr_should_translate_fluent_files() {
+    run_format_test("fluent", "ftl", PotrConfig::default()).await;
+}
+
+#[tokio::test]
+async fn potr_should_translate_with_mock_engine_and_keep_failed_messages() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.skip_translated = false;
+
+    let mut translator_config = TranslatorConfig::default();
+    translator_config.engine = TranslatorEngine::Mock;
+    translator_config.extra_params = HashMap::from([
+        (
+            "mapping".to_string(),
+            "tests/data/mock-mapping.json".to_string(),
+        ),
+        ("transform".to_string(), "uppercase".to_string()),
+        ("fail_at".to_string(), "1".to_string()),
+    ]);
+
+    run_translator_test("mock", "po", potr_config, translator_config).await;
+}
+
+/// Stops the run once the given number of messages are translated.
+struct CancelAfter {
+    count: usize,
+    cancel_flag: Arc<AtomicBool>,
+    translated_count: Mutex<usize>,
+}
+
+impl PotrObserver for CancelAfter {
+    fn on_message_translated(&self, _message: &dyn MessageView, _report: &MessageReport) {
+        let mut translated_count = self.translated_count.lock().unwrap();
+        *translated_count += 1;
+        if *translated_count == self.count {
+            self.cancel_flag.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+#[tokio::test]
+async fn potr_should_skip_remaining_messages_when_canceled() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.skip_translated = false;
+
+    let mut translator_config = TranslatorConfig::default();
+    translator_config.engine = TranslatorEngine::Mock;
+    translator_config.extra_params = HashMap::from([("latency_ms".to_string(), "5".to_string())]);
+
+    let mut potr = Potr::new(potr_config, translator_config);
+    potr.add_observer(Arc::new(CancelAfter {
+        count: 1,
+        cancel_flag: potr.cancel_flag(),
+        translated_count: Mutex::new(0),
+    }));
+
+    let mut catalog = polib::po_file::parse(Path::new("tests/data/mock-input.po")).unwrap();
+    let report = potr
+        .translate_catalog(&mut catalog)
+        .await
+        .expect("Failed to translate catalog");
+
+    let outcomes: Vec<MessageOutcome> = report.messages.into_iter().map(|m| m.outcome).collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            MessageOutcome::Translated,
+            MessageOutcome::Skipped {
+                reason: SkipReason::Canceled
+            },
+            MessageOutcome::Skipped {
+                reason: SkipReason::CodeBlock
+            },
+            MessageOutcome::Skipped {
+                reason: SkipReason::Canceled
+            },
+            MessageOutcome::Skipped {
+                reason: SkipReason::Canceled
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn potr_should_not_write_json_translations_into_source_file() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.po_file_path = "tests/data/json-input.json".to_string();
+    potr_config.output_file_path = potr_config.po_file_path.clone();
+
+    let potr = Potr::new(potr_config, TranslatorConfig::default());
+    assert!(potr.run().await.is_err());
+}
+
+#[tokio::test]
+async fn potr_should_fail_with_typed_error_for_unsupported_language() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.po_file_path = "tests/data/untranslated-input.po".to_string();
+    potr_config.output_file_path = "tests/data/unsupported-language-result.po".to_string();
+
+    let mut translator_config = TranslatorConfig::default();
+    translator_config.engine = TranslatorEngine::DeepL;
+    translator_config.target_lang = Language::Welsh;
+
+    let potr = Potr::new(potr_config, translator_config);
+    let error = potr.run().await.unwrap_err();
+    assert!(matches!(
+        error,
+        PotrError::Translate(TranslateError::UnsupportedLanguage {
+            engine: TranslatorEngine::DeepL,
+            language: Language::Welsh,
+        })
+    ));
+}
+
+#[tokio::test]
+async fn potr_should_fail_with_io_error_for_missing_file() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.po_file_path = "tests/data/missing-input.po".to_string();
+    potr_config.output_file_path = "tests/data/missing-result.po".to_string();
+
+    let potr = Potr::new(potr_config, TranslatorConfig::default());
+    let error = potr.run().await.unwrap_err();
+    assert!(matches!(error, PotrError::Io(_)));
+}
+
+/// Engine that fails with an invalid API key after translating some messages.
+struct ExpiringTranslator {
+    remaining: Mutex<usize>,
+}
+
+#[async_trait]
+impl Translator for ExpiringTranslator {
+    fn name(&self) -> TranslatorEngine {
+        TranslatorEngine::OpenAI
+    }
 
-    let translator_config = TranslatorConfig::default();
+    async fn translate(&self, text: &str) -> Result<String, TranslateError> {
+        let mut remaining = self.remaining.lock().unwrap();
+        if *remaining == 0 {
+            return Err(TranslateError::Auth("API key expired".to_string()));
+        }
+
+        *remaining -= 1;
+        Ok(text.to_uppercase())
+    }
+}
+
+/// Keeps the errors of the failed messages, to check what observers receive.
+#[derive(Default)]
+struct FailureRecorder {
+    errors: Mutex<Vec<String>>,
+}
+
+impl PotrObserver for FailureRecorder {
+    fn on_message_failed(&self, _message: &dyn MessageView, error: &PotrError) {
+        let error = match error {
+            PotrError::Translate(TranslateError::Auth(message)) => format!("auth: {}", message),
+            error => format!("other: {}", error),
+        };
+        self.errors.lock().unwrap().push(error);
+    }
+}
+
+#[tokio::test]
+async fn potr_should_stop_on_fatal_error_of_injected_translator() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.po_file_path = "tests/data/report-input.po".to_string();
+    potr_config.skip_translated = false;
+    potr_config.skip_code_blocks = false;
+
+    let translator = ExpiringTranslator {
+        remaining: Mutex::new(1),
+    };
+    let mut potr = Potr::with_translator(
+        potr_config,
+        TranslatorConfig::default(),
+        Box::new(translator),
+    );
+    assert_eq!(potr.translator_config.engine, TranslatorEngine::OpenAI);
+    let recorder = Arc::new(FailureRecorder::default());
+    potr.add_observer(recorder.clone());
+
+    let mut catalog = polib::po_file::parse(Path::new("tests/data/report-input.po")).unwrap();
+    let error = potr.translate_catalog(&mut catalog).await.unwrap_err();
+    assert!(matches!(
+        error,
+        PotrError::Translate(TranslateError::Auth(_))
+    ));
+    assert_eq!(
+        *recorder.errors.lock().unwrap(),
+        vec!["auth: API key expired".to_string()]
+    );
+    assert_eq!(
+        catalog
+            .find_message(None, "This is a pen!", None)
+            .unwrap()
+            .msgstr()
+            .unwrap(),
+        "THIS IS A PEN!"
+    );
+    assert_eq!(
+        catalog
+            .find_message(None, "That is a pencil!", None)
+            .unwrap()
+            .msgstr()
+            .unwrap(),
+        "That is an old pencil!"
+    );
+}
+
+/// Refuses to translate, unless it is asked with the stricter prompt.
+#[derive(Default)]
+struct RefusingTranslator {
+    strict_count: Mutex<usize>,
+}
+
+#[async_trait]
+impl Translator for RefusingTranslator {
+    fn name(&self) -> TranslatorEngine {
+        TranslatorEngine::OpenAI
+    }
+
+    async fn translate(&self, _text: &str) -> Result<String, TranslateError> {
+        Ok("I'm sorry, I can't translate this text.".to_string())
+    }
+
+    async fn translate_strict(&self, text: &str) -> Result<Option<Translation>, TranslateError> {
+        *self.strict_count.lock().unwrap() += 1;
+        Ok(Some(Translation {
+            text: text.to_uppercase(),
+            ..Default::default()
+        }))
+    }
+}
+
+#[tokio::test]
+async fn potr_should_retry_invalid_translation_with_injected_translator() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.skip_translated = false;
+
+    let translator = Arc::new(RefusingTranslator::default());
+    let potr = Potr::with_translator(
+        potr_config,
+        TranslatorConfig::default(),
+        Box::new(SharedTranslator(translator.clone())),
+    );
+
+    let mut catalog = polib::po_file::parse(Path::new("tests/data/report-input.po")).unwrap();
+    let report = potr.translate_catalog(&mut catalog).await.unwrap();
+    assert_eq!(report.totals.failed_count, 0);
+    assert_eq!(
+        *translator.strict_count.lock().unwrap(),
+        report.totals.translated_count
+    );
+    assert_eq!(
+        catalog
+            .find_message(None, "This is a pen!", None)
+            .unwrap()
+            .msgstr()
+            .unwrap(),
+        "THIS IS A PEN!"
+    );
+}
+
+/// Translator shared with the test, so its state can be checked after the run.
+struct SharedTranslator<T>(Arc<T>);
+
+#[async_trait]
+impl<T: Translator> Translator for SharedTranslator<T> {
+    fn name(&self) -> TranslatorEngine {
+        self.0.name()
+    }
+
+    async fn translate(&self, text: &str) -> Result<String, TranslateError> {
+        self.0.translate(text).await
+    }
+
+    async fn translate_strict(&self, text: &str) -> Result<Option<Translation>, TranslateError> {
+        self.0.translate_strict(text).await
+    }
+}
+
+#[derive(Default)]
+struct EventRecorder {
+    events: Mutex<Vec<String>>,
+    progress: Mutex<Option<Progress>>,
+}
+
+impl PotrObserver for EventRecorder {
+    fn on_message_selected(&self, message: &dyn MessageView) {
+        self.record(format!("selected: {}", message.msgid()));
+    }
+
+    fn on_message_skipped(&self, message: &dyn MessageView, reason: SkipReason) {
+        self.record(format!("skipped ({}): {}", reason, message.msgid()));
+    }
+
+    fn on_message_translated(&self, message: &dyn MessageView, _report: &MessageReport) {
+        self.record(format!("translated: {}", message.msgstr().unwrap()));
+    }
+
+    fn on_progress(&self, progress: &Progress) {
+        *self.progress.lock().unwrap() = Some(*progress);
+    }
+}
+
+impl EventRecorder {
+    fn record(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+#[tokio::test]
+async fn potr_should_translate_catalog_in_memory_with_events() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.skip_translated = false;
+    potr_config.message_limit = 2;
+
+    let recorder = Arc::new(EventRecorder::default());
+    let mut potr = Potr::new(potr_config, TranslatorConfig::default());
+    potr.add_observer(recorder.clone());
+
+    let mut catalog = polib::po_file::parse(Path::new("tests/data/report-input.po")).unwrap();
+    let report = potr
+        .translate_catalog(&mut catalog)
+        .await
+        .expect("Failed to translate catalog");
+    assert_eq!(report.totals.translated_count, 2);
+
+    assert_eq!(
+        *recorder.events.lock().unwrap(),
+        vec![
+            "selected: This is a pen!",
+            "translated: This is a pen!",
+            "selected: That is a pencil!",
+            "translated: That is a pencil!",
+            "skipped (code block): ```bash\necho pen\n```",
+            "skipped (limit reached): This is a guide of pens!",
+            "skipped (limit reached): Buy a pen!",
+        ]
+    );
+    assert_eq!(
+        *recorder.progress.lock().unwrap(),
+        Some(Progress {
+            visited_count: 5,
+            total_count: 5,
+            translated_count: 2,
+            skipped_count: 3,
+            failed_count: 0,
+        })
+    );
+    assert_eq!(
+        catalog
+            .find_message(None, "That is a pencil!", None)
+            .unwrap()
+            .msgstr()
+            .unwrap(),
+        "That is a pencil!"
+    );
+}
+
+#[test]
+fn potr_dry_run_should_report_selected_messages_without_writing_output() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.po_file_path = "tests/data/rules-input.po".to_string();
+    potr_config.output_file_path = "tests/data/dry-run-result.po".to_string();
+    potr_config.skip_translated = false;
+    potr_config.message_limit = 2;
+
+    let mut translator_config = TranslatorConfig::default();
+    translator_config.engine = TranslatorEngine::DeepL;
+
+    let _ = std::fs::remove_file(&potr_config.output_file_path);
+    let potr = Potr::new(potr_config.clone(), translator_config);
+    let report = potr.dry_run().expect("Failed to run dry run");
+
+    let selected: Vec<&str> = report.selected.iter().map(|m| m.msgid.as_str()).collect();
+    assert_eq!(selected, vec!["This is a pen!", "That is a pencil!"]);
+
+    let skipped: Vec<SkipReason> = report.skipped.iter().map(|m| m.reason).collect();
+    assert_eq!(
+        skipped,
+        vec![SkipReason::CodeBlock, SkipReason::Limit, SkipReason::Limit]
+    );
+
+    assert_eq!(report.estimates.len(), 1);
+    assert_eq!(report.estimates[0].usage.characters, 31);
+    assert_eq!(report.total_cost(), Some(25.0 * 31.0 / 1_000_000.0));
+    assert!(!Path::new(&potr_config.output_file_path).exists());
+
+    let text = report.to_string();
+    assert!(text.contains("  [deepl] src/SUMMARY.md:3: This is a pen!"));
+    assert!(text.contains("  deepl: Messages = 2, Characters = 31,"));
+}
+
+#[test]
+fn potr_should_match_source_regex_with_line_numbers() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.po_file_path = "tests/data/rules-input.po".to_string();
+    potr_config.skip_translated = false;
+    potr_config.source_regex = Some(Regex::new(r"SUMMARY\.md:3$").unwrap());
+
+    let potr = Potr::new(potr_config, TranslatorConfig::default());
+    let report = potr.dry_run().expect("Failed to run dry run");
+
+    let selected: Vec<&str> = report.selected.iter().map(|m| m.msgid.as_str()).collect();
+    assert_eq!(selected, vec!["This is a pen!"]);
+}
+
+#[test]
+fn potr_dry_run_should_include_back_translation_in_estimates() {
+    let mut potr_config = PotrConfig::default();
+    potr_config.po_file_path = "tests/data/rules-input.po".to_string();
+    potr_config.skip_translated = false;
+    potr_config.message_limit = 2;
+    potr_config.quality.enabled = true;
+
+    let mut translator_config = TranslatorConfig::default();
+    translator_config.engine = TranslatorEngine::DeepL;
+
+    let potr = Potr::new(potr_config, translator_config);
+    let report = potr.dry_run().expect("Failed to run dry run");
+
+    assert_eq!(report.estimates.len(), 1);
+    assert_eq!(report.estimates[0].message_count, 2);
+    assert_eq!(report.estimates[0].usage.characters, 62);
+    assert_eq!(report.total_cost(), Some(25.0 * 62.0 / 1_000_000.0));
+}
+
+async fn run_potr_test(test_name: &str, potr_config: PotrConfig) {
+    run_format_test(test_name, "po", potr_config).await;
+}
+
+async fn run_format_test(test_name: &str, extension: &str, potr_config: PotrConfig) {
+    run_translator_test(
+        test_name,
+        extension,
+        potr_config,
+        TranslatorConfig::default(),
+    )
+    .await;
+}
+
+/// Formats other than PO load the existing translations from the output file, so
+/// "<name>-existing.<extension>" is copied to it before the run if it exists.
+async fn run_translator_test(
+    test_name: &str,
+    extension: &str,
+    mut potr_config: PotrConfig,
+    translator_config: TranslatorConfig,
+) {
+    potr_config.po_file_path = format!("tests/data/{}-input.{}", test_name, extension);
+    potr_config.output_file_path = format!("tests/data/{}-result.{}", test_name, extension);
+
+    let existing_file_path = format!("tests/data/{}-existing.{}", test_name, extension);
+    if Path::new(&existing_file_path).exists() {
+        std::fs::copy(&existing_file_path, &potr_config.output_file_path)
+            .expect("Failed to copy existing translations");
+    }
 
     let potr = Potr::new(potr_config.clone(), translator_config);
     potr.run().await.expect("Failed to run potr");
@@ -86,7 +637,7 @@ async fn run_potr_test(test_name: &str, mut potr_config: PotrConfig) {
     let result =
         std::fs::read_to_string(&potr_config.output_file_path).expect("Failed to read result file");
 
-    let expected_file_path = format!("tests/data/{}-expected.po", test_name);
+    let expected_file_path = format!("tests/data/{}-expected.{}", test_name, extension);
 
     // If POTR_GENERATE_TEST_RESULT environment variable is set, we replace the expected file with the result file.
     if std::env::var("POTR_GENERATE_TEST_RESULT").is_ok() {
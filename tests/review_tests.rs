@@ -0,0 +1,67 @@
+use async_trait::async_trait;
+use potr::*;
+use pretty_assertions::assert_eq;
+use std::path::Path;
+
+/// Reviewer that corrects "钢笔" (fountain pen) into "铅笔" (pencil).
+struct MockReviewer {}
+
+#[async_trait]
+impl Translator for MockReviewer {
+    fn name(&self) -> TranslatorEngine {
+        TranslatorEngine::OpenAI
+    }
+
+    async fn translate(&self, text: &str) -> Result<String, TranslateError> {
+        Ok(text.to_string())
+    }
+
+    async fn review(&self, _text: &str, translation: &str) -> Result<Review, TranslateError> {
+        if !translation.contains("钢笔") {
+            return Ok(Review::default());
+        }
+
+        Ok(Review {
+            correction: Some(translation.replace("钢笔", "铅笔")),
+            reason: Some("Pencil is mistranslated as fountain pen.".to_string()),
+            usage: None,
+        })
+    }
+}
+
+#[tokio::test]
+async fn review_should_write_corrections_as_fuzzy_suggestions() {
+    let reviewer = PoReviewer::new(ReviewConfig::default(), TranslatorConfig::default());
+    let mut catalog = polib::po_file::parse(Path::new("tests/data/review-input.po")).unwrap();
+
+    let report = reviewer.review(&MockReviewer {}, &mut catalog).await;
+    assert_eq!(report.reviewed_count, 2);
+    assert_eq!(report.ok_count, 1);
+    assert_eq!(report.suggestions.len(), 1);
+    assert_eq!(report.suggestions[0].correction, "那是一支铅笔！");
+
+    // The existing translation is kept, and the suggestion is left for the reviewers.
+    let message = catalog
+        .messages()
+        .find(|m| m.msgid() == "That is a pencil!")
+        .unwrap();
+    assert_eq!(message.msgstr().unwrap(), "那是一支钢笔！");
+    assert!(message.is_fuzzy());
+    assert_eq!(
+        message.comments(),
+        "potr-review-suggestion: 那是一支铅笔！\npotr-review-reason: Pencil is mistranslated as fountain pen."
+    );
+}
+
+#[tokio::test]
+async fn review_should_not_change_catalog_with_review_file() {
+    let mut review_config = ReviewConfig::default();
+    review_config.review_file_path = Some("review.json".to_string());
+    let reviewer = PoReviewer::new(review_config, TranslatorConfig::default());
+    let mut catalog = polib::po_file::parse(Path::new("tests/data/review-input.po")).unwrap();
+
+    let report = reviewer.review(&MockReviewer {}, &mut catalog).await;
+    assert_eq!(report.suggestions.len(), 1);
+    assert_eq!(catalog.messages().filter(|m| m.is_fuzzy()).count(), 1);
+    assert!(catalog.messages().all(|m| m.comments().is_empty()));
+}
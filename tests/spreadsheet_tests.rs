@@ -0,0 +1,128 @@
+use potr::*;
+use pretty_assertions::assert_eq;
+use std::path::Path;
+
+fn load_catalog() -> polib::catalog::Catalog {
+    polib::po_file::parse(Path::new("tests/data/xliff-input.po")).unwrap()
+}
+
+fn create_config(format: SpreadsheetFormat) -> SpreadsheetConfig {
+    let mut config = SpreadsheetConfig::default();
+    config.format = format;
+    config
+}
+
+fn export(catalog: &polib::catalog::Catalog, format: SpreadsheetFormat) -> String {
+    let mut potr_config = PotrConfig::default();
+    potr_config.skip_translated = false;
+    let (sheet, exported_count) = SpreadsheetExporter::new(create_config(format), potr_config)
+        .export(catalog)
+        .unwrap();
+    assert_eq!(exported_count, 4);
+    sheet
+}
+
+fn msgstr_of(catalog: &polib::catalog::Catalog, msgid: &str) -> (String, bool) {
+    let message = catalog.messages().find(|m| m.msgid() == msgid).unwrap();
+    (message.msgstr().unwrap().to_string(), message.is_fuzzy())
+}
+
+#[test]
+fn csv_export_should_include_message_columns() {
+    let sheet = export(&load_catalog(), SpreadsheetFormat::Csv);
+    let lines: Vec<&str> = sheet.lines().collect();
+    assert_eq!(lines[0], "key,msgctxt,msgid,source,msgstr,fuzzy");
+    assert!(lines[1].ends_with(",,This is a pen!,src/SUMMARY.md:3,这是一支笔！,no"));
+    assert!(lines[2].ends_with(",tool,Pen & <Pencil>,src/guide/Pen.md:1 src/guide/Tools.md:7,,no"));
+    assert!(lines[3].ends_with(",,Pens are tools.,src/guide/Pen.md:3,笔是工具。,yes"));
+}
+
+#[test]
+fn tsv_import_should_apply_edited_rows() {
+    let mut catalog = load_catalog();
+    let sheet = export(&catalog, SpreadsheetFormat::Tsv)
+        .replace("Tools.md:7\t\tno", "Tools.md:7\t钢笔和铅笔\tno")
+        .replace("笔是工具。\tyes", "笔是一种工具。\tNo")
+        .replace(
+            "Buy a pen!\tsrc/guide/Pen.md:5\t\tno",
+            "Buy a pen!\tsrc/guide/Pen.md:5\t买一支笔！\tyes",
+        );
+
+    let report = SpreadsheetImporter::new(create_config(SpreadsheetFormat::Tsv))
+        .import(&mut catalog, &sheet)
+        .unwrap();
+    assert_eq!(
+        report,
+        SpreadsheetImportReport {
+            applied_count: 3,
+            unchanged_count: 1,
+            rejected: vec![],
+        }
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "This is a pen!"),
+        ("这是一支笔！".to_string(), false)
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "Pen & <Pencil>"),
+        ("钢笔和铅笔".to_string(), false)
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "Pens are tools."),
+        ("笔是一种工具。".to_string(), false)
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "Buy a pen!"),
+        ("买一支笔！".to_string(), true)
+    );
+}
+
+#[test]
+fn csv_import_should_reject_changed_rows() {
+    let mut catalog = load_catalog();
+    let sheet = export(&catalog, SpreadsheetFormat::Csv)
+        .replace("This is a pen!,", "This is a big pen!,")
+        .replace("笔是工具。,yes", "笔是一种工具。,maybe")
+        .replace("src/guide/Pen.md:5,,no", "src/guide/Pen.md:5,买一支笔！,no");
+    catalog.delete_message(None, "Buy a pen!", None);
+
+    let report = SpreadsheetImporter::new(create_config(SpreadsheetFormat::Csv))
+        .import(&mut catalog, &sheet)
+        .unwrap();
+    assert_eq!(
+        report,
+        SpreadsheetImportReport {
+            applied_count: 0,
+            unchanged_count: 1,
+            rejected: vec![
+                RejectedRow {
+                    row: 2,
+                    msgid: "This is a big pen!".to_string(),
+                    reason: RowRejectReason::SourceChanged,
+                },
+                RejectedRow {
+                    row: 4,
+                    msgid: "Pens are tools.".to_string(),
+                    reason: RowRejectReason::Invalid,
+                },
+                RejectedRow {
+                    row: 5,
+                    msgid: "Buy a pen!".to_string(),
+                    reason: RowRejectReason::NotFound,
+                },
+            ],
+        }
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "Pens are tools."),
+        ("笔是工具。".to_string(), true)
+    );
+}
+
+#[test]
+fn import_should_fail_without_required_columns() {
+    let mut catalog = load_catalog();
+    let result = SpreadsheetImporter::new(create_config(SpreadsheetFormat::Csv))
+        .import(&mut catalog, "msgid,msgstr\nThis is a pen!,这是笔\n");
+    assert!(result.is_err());
+}
@@ -0,0 +1,104 @@
+use potr::*;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn stats_should_count_messages_by_state_source_and_kind() {
+    let stats = run_stats_test();
+
+    let counts = |count, words, characters| EntryCounts {
+        count,
+        words,
+        characters,
+    };
+    assert_eq!(stats.total.translated, counts(2, 8, 34));
+    assert_eq!(stats.total.fuzzy, counts(1, 4, 17));
+    assert_eq!(stats.total.untranslated, counts(1, 3, 10));
+    assert_eq!(stats.total.obsolete, counts(2, 5, 30));
+    assert_eq!(stats.total.completion, 50.0);
+
+    assert_eq!(stats.by_kind["code_block"].translated.count, 1);
+    assert_eq!(stats.by_kind["text"].translated.count, 1);
+    assert_eq!(stats.by_kind["text"].fuzzy.count, 1);
+
+    // Messages are counted once per source file, even if they are referenced multiple times.
+    assert_eq!(
+        stats.by_source.keys().collect::<Vec<_>>(),
+        vec!["src/SUMMARY.md", "src/api/Pen.md", "src/guide/Pen.md"]
+    );
+    assert_eq!(stats.by_source["src/guide/Pen.md"].translated.count, 2);
+    assert_eq!(stats.by_source["src/guide/Pen.md"].fuzzy.count, 1);
+    assert_eq!(stats.by_source["src/api/Pen.md"].untranslated.count, 1);
+    assert_eq!(stats.by_source["src/api/Pen.md"].completion, 0.0);
+}
+
+#[test]
+fn stats_should_only_count_messages_matching_filter() {
+    let mut stats_config = StatsConfig::default();
+    stats_config.po_file_path = "tests/data/stats-input.po".to_string();
+    stats_config.filter = Some(MessageFilter::parse("source =~ \"src/guide\"").unwrap());
+
+    let stats = PoStatsCollector::new(stats_config)
+        .run()
+        .expect("Failed to run stats");
+    assert_eq!(stats.total.translated.count, 2);
+    assert_eq!(stats.total.fuzzy.count, 1);
+    assert_eq!(stats.total.untranslated.count, 0);
+    assert!(!stats.by_source.contains_key("src/api/Pen.md"));
+}
+
+#[test]
+fn stats_should_reject_files_of_other_formats() {
+    let mut stats_config = StatsConfig::default();
+    stats_config.po_file_path = "tests/data/json-input.json".to_string();
+
+    let error = PoStatsCollector::new(stats_config).run().unwrap_err();
+    assert!(matches!(error, PotrError::Config(_)));
+}
+
+#[test]
+fn stats_should_fail_when_completion_is_below_threshold() {
+    let stats = run_stats_test();
+
+    assert!(stats.check_threshold(50.0).is_ok());
+    assert!(matches!(
+        stats.check_threshold(60.0),
+        Err(PotrError::BelowThreshold { threshold, .. }) if threshold == 60.0
+    ));
+}
+
+#[test]
+fn stats_should_treat_empty_catalog_as_complete() {
+    let mut stats_config = StatsConfig::default();
+    stats_config.po_file_path = "tests/data/stats-input.po".to_string();
+    stats_config.filter = Some(MessageFilter::parse("msgid == \"Not found\"").unwrap());
+
+    let stats = PoStatsCollector::new(stats_config)
+        .run()
+        .expect("Failed to run stats");
+    assert_eq!(stats.total.translated.count, 0);
+    assert_eq!(stats.total.completion, 100.0);
+    assert!(stats.check_threshold(100.0).is_ok());
+}
+
+#[test]
+fn stats_should_be_formatted_as_csv() {
+    let stats = run_stats_test();
+
+    let csv = stats.format(StatsFormat::Csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 7);
+    assert_eq!(
+        lines[0],
+        "group,name,translated,translated_words,translated_characters,fuzzy,fuzzy_words,fuzzy_characters,untranslated,untranslated_words,untranslated_characters,obsolete,obsolete_words,obsolete_characters,completion"
+    );
+    assert_eq!(lines[1], "total,,2,8,34,1,4,17,1,3,10,2,5,30,50.00");
+}
+
+fn run_stats_test() -> PoStats {
+    let mut stats_config = StatsConfig::default();
+    stats_config.po_file_path = "tests/data/stats-input.po".to_string();
+
+    PoStatsCollector::new(stats_config)
+        .run()
+        .expect("Failed to run stats")
+}
@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use potr::*;
+use pretty_assertions::assert_eq;
+use std::str::FromStr;
+
+/// Custom engine that reverses the text.
+struct ReverseTranslator {
+    engine: TranslatorEngine,
+}
+
+#[async_trait]
+impl Translator for ReverseTranslator {
+    fn name(&self) -> TranslatorEngine {
+        self.engine
+    }
+
+    async fn translate(&self, text: &str) -> Result<String, TranslateError> {
+        Ok(text.chars().rev().collect())
+    }
+}
+
+#[tokio::test]
+async fn custom_translator_should_be_created_by_registered_name() {
+    let engine = register_translator("reverse", |config| {
+        Ok(Box::new(ReverseTranslator {
+            engine: config.engine,
+        }))
+    })
+    .unwrap();
+    assert_eq!(engine, TranslatorEngine::Custom("reverse"));
+    assert_eq!(engine.to_string(), "reverse");
+    assert_eq!(TranslatorEngine::from_str("reverse").unwrap(), engine);
+
+    let mut config = TranslatorConfig::default();
+    config.engine = engine;
+    let translator = create(config).unwrap();
+    assert_eq!(translator.name(), engine);
+    assert_eq!(translator.translate("potr").await.unwrap(), "rtop");
+}
+
+#[test]
+fn built_in_engine_names_should_not_be_registered() {
+    let result = register_translator("openai", |config| {
+        Ok(Box::new(ReverseTranslator {
+            engine: config.engine,
+        }))
+    });
+    assert!(matches!(result, Err(TranslateError::InvalidConfig(_))));
+}
+
+#[test]
+fn unregistered_engine_names_should_not_be_parsed() {
+    assert!(TranslatorEngine::from_str("not-registered").is_err());
+    assert_eq!(
+        TranslatorEngine::from_str("deepl").unwrap(),
+        TranslatorEngine::DeepL
+    );
+}
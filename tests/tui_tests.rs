@@ -0,0 +1,107 @@
+use potr::*;
+use pretty_assertions::assert_eq;
+use std::path::Path;
+
+fn create_tui_reviewer(config: TuiConfig) -> TuiReviewer {
+    let mut translator_config = TranslatorConfig::default();
+    let clone_translator = potr::create(translator_config.clone()).unwrap();
+    translator_config.engine = TranslatorEngine::Clear;
+    let clear_translator = potr::create(translator_config).unwrap();
+    TuiReviewer::new(config, vec![clone_translator, clear_translator])
+}
+
+fn msgstr_of(catalog: &polib::catalog::Catalog, msgid: &str) -> (String, bool) {
+    let message = catalog.messages().find(|m| m.msgid() == msgid).unwrap();
+    (message.msgstr().unwrap().to_string(), message.is_fuzzy())
+}
+
+#[tokio::test]
+async fn tui_should_apply_actions_and_save_changes() {
+    let reviewer = create_tui_reviewer(TuiConfig::default());
+    let mut catalog = polib::po_file::parse(Path::new("tests/data/review-input.po")).unwrap();
+
+    // Keep the 1st message, edit the 2nd one after an unknown action, and accept the suggestion
+    // of the 3rd one after regenerating with the clear engine and back.
+    let input = "k\nx\ne\n那是一支铅笔！\nr\nr clone\na\n";
+    let mut output = vec![];
+    let mut save_count = 0;
+    let summary = reviewer
+        .review(&mut catalog, input.as_bytes(), &mut output, |_| {
+            save_count += 1;
+            Ok(())
+        })
+        .await
+        .expect("Failed to review");
+
+    assert_eq!(
+        summary,
+        TuiSummary {
+            reviewed_count: 3,
+            changed_count: 2,
+            remaining_count: 0,
+        }
+    );
+    assert_eq!(save_count, 2);
+    assert_eq!(
+        msgstr_of(&catalog, "This is a pen!"),
+        ("这是一支笔！".to_string(), false)
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "That is a pencil!"),
+        ("那是一支铅笔！".to_string(), false)
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "Pens are tools."),
+        ("Pens are tools.".to_string(), false)
+    );
+    assert_eq!(msgstr_of(&catalog, "Buy a pen!"), ("".to_string(), false));
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("[2/3] src/guide/Pen.md:1"));
+    assert!(output.contains("Unknown action: x"));
+    assert!(output.contains("Suggestion:   (clear)"));
+}
+
+#[tokio::test]
+async fn tui_should_stop_on_quit() {
+    let mut tui_config = TuiConfig::default();
+    tui_config.fuzzy_only = true;
+    let reviewer = create_tui_reviewer(tui_config);
+    let mut catalog = polib::po_file::parse(Path::new("tests/data/review-input.po")).unwrap();
+
+    let summary = reviewer
+        .review(&mut catalog, "q\n".as_bytes(), vec![], |_| Ok(()))
+        .await
+        .expect("Failed to review");
+
+    assert_eq!(
+        summary,
+        TuiSummary {
+            reviewed_count: 0,
+            changed_count: 0,
+            remaining_count: 1,
+        }
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "Pens are tools."),
+        ("笔是工具。".to_string(), true)
+    );
+}
+
+#[tokio::test]
+async fn tui_should_keep_whitespace_of_edited_translation() {
+    let reviewer = create_tui_reviewer(TuiConfig::default());
+    let mut catalog = polib::po_file::parse(Path::new("tests/data/review-input.po")).unwrap();
+
+    // Only the line terminator is removed, so leading and trailing whitespace is kept.
+    let input = "s\ne\n  那是\\n一支铅笔！ \r\nq\n";
+    reviewer
+        .review(&mut catalog, input.as_bytes(), vec![], |_| Ok(()))
+        .await
+        .expect("Failed to review");
+
+    assert_eq!(
+        msgstr_of(&catalog, "That is a pencil!"),
+        ("  那是\n一支铅笔！ ".to_string(), false)
+    );
+}
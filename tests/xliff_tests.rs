@@ -0,0 +1,199 @@
+use polib::message::MessageMutView;
+use potr::*;
+use pretty_assertions::assert_eq;
+use std::path::Path;
+
+fn load_catalog() -> polib::catalog::Catalog {
+    polib::po_file::parse(Path::new("tests/data/xliff-input.po")).unwrap()
+}
+
+fn create_exporter(version: XliffVersion, skip_translated: bool) -> XliffExporter {
+    let mut config = XliffConfig::default();
+    config.po_file_path = "tests/data/xliff-input.po".to_string();
+    config.version = version;
+    config.target_lang = Language::Chinese;
+
+    let mut potr_config = PotrConfig::default();
+    potr_config.skip_translated = skip_translated;
+    XliffExporter::new(config, potr_config)
+}
+
+fn msgstr_of(catalog: &polib::catalog::Catalog, msgid: &str) -> (String, bool) {
+    let message = catalog.messages().find(|m| m.msgid() == msgid).unwrap();
+    (message.msgstr().unwrap().to_string(), message.is_fuzzy())
+}
+
+#[test]
+fn xliff_export_should_select_messages_like_translate() {
+    let catalog = load_catalog();
+
+    let (xliff, exported_count) = create_exporter(XliffVersion::V1_2, true).export(&catalog);
+    assert_eq!(exported_count, 2);
+    assert!(xliff.contains(r#"source-language="en" target-language="zh""#));
+    assert!(xliff.contains("<source>Pen &amp; &lt;Pencil&gt;</source>"));
+    assert!(xliff.contains(r#"<context context-type="x-po-msgctxt">tool</context>"#));
+    assert!(xliff.contains(r#"<context context-type="sourcefile">src/guide/Tools.md</context><context context-type="linenumber">7</context>"#));
+    assert!(xliff.contains(r#"<note from="developer">The writing tool, not the enclosure.</note>"#));
+    assert!(xliff.contains("<source>Buy a pen!</source>"));
+    assert!(!xliff.contains("Pens are tools."));
+    assert!(!xliff.contains("```"));
+
+    let (xliff, exported_count) = create_exporter(XliffVersion::V2_0, false).export(&catalog);
+    assert_eq!(exported_count, 4);
+    assert!(xliff.contains(r#"srcLang="en" trgLang="zh""#));
+    assert!(xliff.contains(r#"<note category="location">src/guide/Pen.md:1</note>"#));
+    assert!(xliff.contains(r#"<segment state="translated" subState="potr:fuzzy">"#));
+    assert!(xliff.contains(r#"<target xml:space="preserve">笔是工具。</target>"#));
+}
+
+#[test]
+fn xliff_1_2_import_should_map_states_to_fuzzy() {
+    let mut catalog = load_catalog();
+    let (xliff, _) = create_exporter(XliffVersion::V1_2, false).export(&catalog);
+
+    let xliff = xliff
+        .replace(
+            "<source>Pen &amp; &lt;Pencil&gt;</source>",
+            r#"<source>Pen &amp; &lt;Pencil&gt;</source><target state="needs-review-translation">钢笔和<b>铅笔</b></target>"#,
+        )
+        .replace(
+            r#"<target state="needs-review-translation">笔是工具。</target>"#,
+            r#"<target state="translated">笔是一种工具。</target>"#,
+        );
+    let report = XliffImporter::new(XliffConfig::default())
+        .import(&mut catalog, &xliff)
+        .unwrap();
+
+    assert_eq!(
+        report,
+        XliffImportReport {
+            imported_count: 3,
+            untranslated_count: 1,
+            outdated_count: 0,
+            conflicts: vec![],
+        }
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "This is a pen!"),
+        ("这是一支笔！".to_string(), false)
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "Pen & <Pencil>"),
+        ("钢笔和铅笔".to_string(), true)
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "Pens are tools."),
+        ("笔是一种工具。".to_string(), false)
+    );
+    assert_eq!(msgstr_of(&catalog, "Buy a pen!"), ("".to_string(), false));
+}
+
+#[test]
+fn xliff_2_0_import_should_report_conflicts() {
+    let mut catalog = load_catalog();
+    let (xliff, _) = create_exporter(XliffVersion::V2_0, false).export(&catalog);
+
+    // The PO file is changed after the export.
+    catalog
+        .find_message_mut(None, "This is a pen!", None)
+        .unwrap()
+        .set_msgstr("这是一支钢笔！".to_string())
+        .unwrap();
+    catalog
+        .find_message_mut(None, "Pens are tools.", None)
+        .unwrap()
+        .set_msgstr("笔是工具！".to_string())
+        .unwrap();
+    catalog.delete_message(None, "Buy a pen!", None);
+
+    let xliff = xliff
+        .replace(
+            r#"<target xml:space="preserve">这是一支笔！</target>"#,
+            r#"<target xml:space="preserve">这是一支圆珠笔！</target>"#,
+        )
+        .replace(
+            r#"<source xml:space="preserve">Buy a pen!</source>"#,
+            r#"<source xml:space="preserve">Buy a pen!</source><target>买一支笔！</target>"#,
+        );
+    let report = XliffImporter::new(XliffConfig::default())
+        .import(&mut catalog, &xliff)
+        .unwrap();
+
+    assert_eq!(
+        report,
+        XliffImportReport {
+            imported_count: 0,
+            untranslated_count: 1,
+            outdated_count: 1,
+            conflicts: vec![
+                XliffConflict {
+                    msgid: "This is a pen!".to_string(),
+                    msgctxt: "".to_string(),
+                    reason: XliffConflictReason::Changed,
+                },
+                XliffConflict {
+                    msgid: "Buy a pen!".to_string(),
+                    msgctxt: "".to_string(),
+                    reason: XliffConflictReason::NotFound,
+                },
+            ],
+        }
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "This is a pen!"),
+        ("这是一支钢笔！".to_string(), false)
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "Pens are tools."),
+        ("笔是工具！".to_string(), true)
+    );
+}
+
+#[test]
+fn xliff_import_should_report_plural_messages_as_conflicts() {
+    let mut catalog = load_catalog();
+    catalog.append_or_update(
+        polib::message::Message::build_plural()
+            .with_msgid("One pen".to_string())
+            .with_msgid_plural("Many pens".to_string())
+            .with_msgstr_plural(vec!["".to_string()])
+            .done(),
+    );
+
+    let xliff = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">
+  <file original="xliff-input.po" source-language="en" target-language="zh" datatype="po">
+    <body>
+      <trans-unit id="1">
+        <source>One pen</source>
+        <target state="translated">一支笔</target>
+      </trans-unit>
+      <trans-unit id="2">
+        <source>Buy a pen!</source>
+        <target state="translated">买一支笔！</target>
+      </trans-unit>
+    </body>
+  </file>
+</xliff>"#;
+    let report = XliffImporter::new(XliffConfig::default())
+        .import(&mut catalog, xliff)
+        .unwrap();
+
+    assert_eq!(
+        report,
+        XliffImportReport {
+            imported_count: 1,
+            untranslated_count: 0,
+            outdated_count: 0,
+            conflicts: vec![XliffConflict {
+                msgid: "One pen".to_string(),
+                msgctxt: "".to_string(),
+                reason: XliffConflictReason::Plural,
+            }],
+        }
+    );
+    assert_eq!(
+        msgstr_of(&catalog, "Buy a pen!"),
+        ("买一支笔！".to_string(), false)
+    );
+}